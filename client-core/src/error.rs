@@ -0,0 +1,55 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The server couldn't be reached to register.
+    Http(reqwest::Error),
+    /// The server turned the registration away, such as once a tournament has started.
+    Rejected(reqwest::StatusCode),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+    /// The TLS settings couldn't be used, such as a certificate authority that isn't valid PEM.
+    Tls(native_tls::Error),
+    /// A message from the server couldn't be read.
+    Json(serde_json::Error),
+    /// The connection to the table has been closed.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "Couldn't reach the server: {}", e),
+            Error::Rejected(status) => write!(f, "The server turned us away: {}", status),
+            Error::WebSocket(e) => write!(f, "Web socket error: {}", e),
+            Error::Tls(e) => write!(f, "TLS error: {}", e),
+            Error::Json(e) => write!(f, "Couldn't read a message from the server: {}", e),
+            Error::Closed => write!(f, "The connection to the table has been closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
+
+impl From<native_tls::Error> for Error {
+    fn from(e: native_tls::Error) -> Self {
+        Error::Tls(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,9 @@
+pub mod error;
+pub mod session;
+pub mod tls;
+pub mod turn;
+
+pub use error::Error;
+pub use session::{register, TableCommands, TableEvents, TableSession};
+pub use tls::TlsOptions;
+pub use turn::{Turn, TurnState};
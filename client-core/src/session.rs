@@ -0,0 +1,202 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use blackjack_shared::{player::Player, web_socket::*};
+use futures_util::{SinkExt, Stream, StreamExt};
+use tokio::{net::TcpStream, sync::mpsc};
+use tokio_tungstenite::{
+    connect_async_tls_with_config, tungstenite::Message, Connector, MaybeTlsStream, WebSocketStream,
+};
+
+use crate::{Error, TlsOptions};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Registers a player with the server at `server_url`, such as `http://127.0.0.1:8000`.
+pub async fn register(
+    server_url: &str,
+    user_name: &str,
+    tls: &TlsOptions,
+) -> Result<RegisterResponse, Error> {
+    let res = tls
+        .http_client()?
+        .post(format!("{}/register", server_url.trim_end_matches('/')))
+        .json(&RegisterRequest {
+            user_name: user_name.to_owned(),
+        })
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        return Err(Error::Rejected(res.status()));
+    }
+    Ok(res.json().await?)
+}
+
+/// A seat at the table. Commands can be sent from anywhere with [`TableCommands`] while the
+/// triggers published by the server are read in order from the events.
+pub struct TableSession {
+    registration: RegisterResponse,
+    commands: TableCommands,
+    events: TableEvents,
+}
+
+impl TableSession {
+    /// Registers with the server and joins the table.
+    pub async fn connect(
+        server_url: &str,
+        user_name: &str,
+        tls: &TlsOptions,
+    ) -> Result<Self, Error> {
+        let registration = register(server_url, user_name, tls).await?;
+        Self::join(registration, tls).await
+    }
+
+    /// Joins the table with a registration that's already been made.
+    pub async fn join(registration: RegisterResponse, tls: &TlsOptions) -> Result<Self, Error> {
+        let connector = Connector::NativeTls(tls.connector()?);
+        let (socket, _) =
+            connect_async_tls_with_config(registration.url.as_str(), None, false, Some(connector))
+                .await?;
+        let (mut sink, stream) = socket.split();
+
+        // Commands go through a channel so they can be sent from more than one task.
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        Ok(TableSession {
+            registration,
+            commands: TableCommands { sender },
+            events: TableEvents { stream },
+        })
+    }
+
+    pub fn registration(&self) -> &RegisterResponse {
+        &self.registration
+    }
+
+    pub fn id(&self) -> &str {
+        &self.registration.id
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.registration.is_host
+    }
+
+    pub fn commands(&self) -> TableCommands {
+        self.commands.clone()
+    }
+
+    /// Waits for the next trigger from the server. Returns `None` once the table has closed the
+    /// connection.
+    pub async fn next_event(&mut self) -> Option<Result<PublishTrigger, Error>> {
+        self.events.next().await
+    }
+
+    /// Splits the session so the events can be read in one task while commands are sent from
+    /// others.
+    pub fn split(self) -> (RegisterResponse, TableCommands, TableEvents) {
+        (self.registration, self.commands, self.events)
+    }
+}
+
+/// Sends requests to the table.
+#[derive(Clone)]
+pub struct TableCommands {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl TableCommands {
+    pub fn send(&self, command: RequestCommand) -> Result<(), Error> {
+        let json = serde_json::to_string(&BlackjackRequest { command })?;
+        self.sender
+            .send(Message::Text(json))
+            .map_err(|_| Error::Closed)
+    }
+
+    /// Sends each of the commands in turn, such as the ones worked out by a
+    /// [`Turn`](crate::Turn).
+    pub fn send_all(&self, commands: Vec<RequestCommand>) -> Result<(), Error> {
+        commands.into_iter().try_for_each(|c| self.send(c))
+    }
+
+    /// Deals the next round. Only the host can start the game.
+    pub fn start(&self) -> Result<(), Error> {
+        self.send(RequestCommand::Start)
+    }
+
+    pub fn bet(&self, amount: u32) -> Result<(), Error> {
+        self.send(RequestCommand::Bet(amount))
+    }
+
+    pub fn draw_cards(&self, n: u16) -> Result<(), Error> {
+        self.send(RequestCommand::DrawCards(n))
+    }
+
+    pub fn hit(&self) -> Result<(), Error> {
+        self.send(RequestCommand::Hit)
+    }
+
+    /// Doubles the bet on the first two cards. The server deals one card and ends the turn.
+    pub fn double(&self) -> Result<(), Error> {
+        self.send(RequestCommand::Double)
+    }
+
+    pub fn end_turn(&self, player: Player) -> Result<(), Error> {
+        self.send(RequestCommand::EndTurn(player))
+    }
+
+    pub fn chat(&self, message: &str) -> Result<(), Error> {
+        self.send(RequestCommand::Chat(message.to_owned()))
+    }
+
+    pub fn client_seed(&self, seed: &str) -> Result<(), Error> {
+        self.send(RequestCommand::ClientSeed(seed.to_owned()))
+    }
+
+    /// Seats a bot playing the named strategy. Only the host can add bots, before the first hand.
+    pub fn add_bot(&self, strategy: &str) -> Result<(), Error> {
+        self.send(RequestCommand::AddBot(strategy.to_owned()))
+    }
+}
+
+/// The triggers published by the server, in the order they were sent.
+pub struct TableEvents {
+    stream: futures_util::stream::SplitStream<Socket>,
+}
+
+impl Stream for TableEvents {
+    type Item = Result<PublishTrigger, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let message = match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(message))) => message,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match message {
+                Message::Text(text) => {
+                    return Poll::Ready(Some(
+                        serde_json::from_str::<PublishRequest>(&text)
+                            .map(|r| r.trigger)
+                            .map_err(Error::from),
+                    ))
+                }
+                Message::Close(_) => return Poll::Ready(None),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use native_tls::{Certificate, TlsConnector};
+
+use crate::Error;
+
+/// How to check the server's certificate on `https` and `wss` connections. By default it has to
+/// be signed by one of the system's trusted authorities.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// A PEM encoded certificate authority to trust as well, such as one made for a local
+    /// network.
+    pub ca_cert: Option<Vec<u8>>,
+    /// Accept any certificate at all. Only for trying out a server with a self-signed
+    /// certificate during development.
+    pub insecure: bool,
+}
+
+impl TlsOptions {
+    /// A connector for opening web sockets with these options.
+    pub fn connector(&self) -> Result<TlsConnector, Error> {
+        let mut builder = TlsConnector::builder();
+        if let Some(pem) = &self.ca_cert {
+            builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+        builder.danger_accept_invalid_certs(self.insecure);
+        Ok(builder.build()?)
+    }
+
+    /// An http client for registering with these options.
+    pub fn http_client(&self) -> Result<reqwest::Client, Error> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(pem) = &self.ca_cert {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        Ok(builder.danger_accept_invalid_certs(self.insecure).build()?)
+    }
+}
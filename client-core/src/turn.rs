@@ -0,0 +1,108 @@
+use blackjack_shared::{
+    card::Card,
+    player::{get_hand_value, Player, PlayerAction},
+    web_socket::RequestCommand,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnState {
+    /// Waiting for a bet to be placed.
+    Betting,
+    /// Waiting for the cards asked for to arrive.
+    Drawing,
+    /// Waiting for the card to double down on.
+    Doubling,
+    /// Waiting to hit, stand or double.
+    Acting,
+    /// The turn has been ended with the server.
+    Finished,
+}
+
+/// Keeps the player's hand through their turn and works out the requests to send for each
+/// move. The turn needs to see every card drawn while it's in progress, including the one card
+/// dealt on a double down, after which the server ends the turn.
+#[derive(Debug, Clone)]
+pub struct Turn {
+    player: Player,
+    state: TurnState,
+}
+
+impl Turn {
+    /// Starts a turn with an empty hand.
+    pub fn new(mut player: Player) -> Self {
+        player.hand = vec![];
+        player.hand_value = 0;
+        player.current_bet = 0;
+
+        Turn {
+            player,
+            state: TurnState::Betting,
+        }
+    }
+
+    pub fn player(&self) -> &Player {
+        &self.player
+    }
+
+    pub fn into_player(self) -> Player {
+        self.player
+    }
+
+    pub fn state(&self) -> TurnState {
+        self.state
+    }
+
+    /// Whether the player has the chips to double their bet.
+    pub fn can_double(&self) -> bool {
+        self.player.current_bet * 2 <= self.player.chips
+    }
+
+    /// Places the bet and asks for the first two cards.
+    pub fn bet(&mut self, amount: u32) -> Vec<RequestCommand> {
+        self.player.current_bet = amount;
+        self.state = TurnState::Drawing;
+        vec![RequestCommand::Bet(amount), RequestCommand::DrawCards(2)]
+    }
+
+    /// Takes the action. Check [`can_double`](Self::can_double) before doubling.
+    pub fn act(&mut self, action: PlayerAction) -> Vec<RequestCommand> {
+        match action {
+            PlayerAction::Hit => {
+                self.state = TurnState::Drawing;
+                vec![RequestCommand::Hit]
+            }
+            PlayerAction::Double => {
+                self.state = TurnState::Doubling;
+                vec![RequestCommand::Double]
+            }
+            PlayerAction::Stand => self.end(),
+        }
+    }
+
+    /// Adds the cards to the hand. Returns the requests that follow from them, which end the
+    /// turn after a bust. The server has already ended the turn after a double down.
+    pub fn cards_drawn(&mut self, cards: &[Card]) -> Vec<RequestCommand> {
+        self.player.hand.extend_from_slice(cards);
+        self.player.hand_value = get_hand_value(self.player.hand.clone());
+
+        if self.state == TurnState::Doubling {
+            self.player.current_bet *= 2;
+            self.state = TurnState::Finished;
+            vec![]
+        } else if self.is_bust() {
+            self.end()
+        } else {
+            self.state = TurnState::Acting;
+            vec![]
+        }
+    }
+
+    pub fn is_bust(&self) -> bool {
+        self.player.hand_value > 21
+    }
+
+    fn end(&mut self) -> Vec<RequestCommand> {
+        self.state = TurnState::Finished;
+        vec![RequestCommand::EndTurn(self.player.clone())]
+    }
+}
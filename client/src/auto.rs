@@ -0,0 +1,123 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use blackjack_shared::{
+    card::Card,
+    counting::{BetSpread, Counter, HiLo},
+    player::PlayerAction,
+    rules::TableRules,
+    strategy::{strategy_from_name, PlayerStrategy},
+};
+use color_eyre::eyre::{eyre, Result};
+
+/// One line of a script.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScriptEntry {
+    Bet(u32),
+    Action(PlayerAction),
+}
+
+/// Plays the client's seat without asking for any input. Bets and actions are taken from the
+/// script while it lasts, then from the strategy and the bet spread.
+pub struct Autopilot {
+    strategy: Box<dyn PlayerStrategy + Send + Sync>,
+    script: VecDeque<ScriptEntry>,
+    counter: Counter,
+    spread: BetSpread,
+    unit: u32,
+    rules: TableRules,
+}
+
+impl Autopilot {
+    pub fn new(
+        strategy_name: &str,
+        rules: &TableRules,
+        spread: BetSpread,
+        unit: u32,
+    ) -> Result<Self> {
+        let strategy = strategy_from_name(strategy_name, rules)
+            .ok_or_else(|| eyre!("Unknown strategy: {}", strategy_name))?;
+
+        Ok(Autopilot {
+            strategy,
+            script: VecDeque::new(),
+            counter: Counter::new(Box::new(HiLo), rules.decks),
+            spread,
+            unit,
+            rules: rules.clone(),
+        })
+    }
+
+    /// Loads a script to play before falling back to the strategy. Each line is either a bet,
+    /// such as `25`, or one of `hit`, `stand` and `double`. Blank lines and lines starting with
+    /// `#` are skipped.
+    pub fn load_script(&mut self, path: &Path) -> Result<()> {
+        let text = fs::read_to_string(path)?;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim().to_lowercase();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let entry = match line.as_str() {
+                "hit" => ScriptEntry::Action(PlayerAction::Hit),
+                "stand" => ScriptEntry::Action(PlayerAction::Stand),
+                "double" => ScriptEntry::Action(PlayerAction::Double),
+                _ => ScriptEntry::Bet(line.parse().map_err(|_| {
+                    eyre!(
+                        "Line {} of {}: '{}' isn't a bet or an action",
+                        number + 1,
+                        path.display(),
+                        line
+                    )
+                })?),
+            };
+            self.script.push_back(entry);
+        }
+        Ok(())
+    }
+
+    /// The next bet. A scripted bet is used if it's next in the script, otherwise the unit is
+    /// spread by the Hi-Lo true count. Either way it's kept within the table limits.
+    pub fn bet(&mut self, chips: u32) -> u32 {
+        let bet = match self.script.front() {
+            Some(&ScriptEntry::Bet(bet)) => {
+                self.script.pop_front();
+                bet
+            }
+            _ => {
+                self.spread
+                    .units(self.counter.true_count().unwrap_or_default())
+                    * self.unit
+            }
+        };
+        let bet = bet
+            .max(self.rules.min_bet)
+            .min(self.rules.max_bet.unwrap_or(u32::MAX));
+        bet.min(chips)
+    }
+
+    /// The next move for the hand. Doubles that aren't allowed or can't be covered are played
+    /// as hits.
+    pub fn action(&mut self, hand: &[Card], dealer_card: &Card, can_double: bool) -> PlayerAction {
+        let action = match self.script.front() {
+            Some(&ScriptEntry::Action(action)) => {
+                self.script.pop_front();
+                action
+            }
+            _ => self.strategy.action(hand, dealer_card),
+        };
+
+        match action {
+            PlayerAction::Double if !can_double || hand.len() != 2 => PlayerAction::Hit,
+            action => action,
+        }
+    }
+
+    pub fn observe(&mut self, cards: &[Card]) {
+        self.counter.observe(cards);
+    }
+
+    pub fn shoe_shuffled(&mut self) {
+        self.counter.reset();
+    }
+}
@@ -0,0 +1,167 @@
+use std::{fs, path::Path};
+
+use blackjack_shared::{card::Card, fairness::verify_shoe};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// A record of every card seen being dealt and the commitments and reveals for each shoe, so the
+/// shuffles can be checked once the server reveals its seeds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FairnessLog {
+    /// Every card seen, in the order it came out of the shoe. The dealer's hole card is `None`
+    /// until it's turned over.
+    pub cards: Vec<Option<Card>>,
+    pub shoes: Vec<ShoeRecord>,
+    #[serde(skip)]
+    hole_card: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoeRecord {
+    pub id: u32,
+    pub decks: u8,
+    pub commitment: String,
+    pub client_seeds: Vec<String>,
+    /// The index in `cards` of the first card dealt from this shoe.
+    pub start: usize,
+    pub server_seed: Option<String>,
+    pub dealt: Option<usize>,
+    #[serde(default)]
+    pub verified: bool,
+}
+
+impl FairnessLog {
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The dealer is dealt their up card and hole card before anyone else.
+    pub fn round_started(&mut self, dealer_card: &Card) {
+        self.cards.push(Some(dealer_card.clone()));
+        self.hole_card = Some(self.cards.len());
+        self.cards.push(None);
+    }
+
+    pub fn cards_dealt(&mut self, cards: &[Card]) {
+        self.cards.extend(cards.iter().cloned().map(Some));
+    }
+
+    /// Fills in the hole card and adds any cards the dealer drew at the end of the round.
+    pub fn dealer_revealed(&mut self, hand: &[Card]) {
+        if let (Some(index), Some(hole_card)) = (self.hole_card.take(), hand.get(1)) {
+            self.cards[index] = Some(hole_card.clone());
+        }
+        self.cards_dealt(hand.get(2..).unwrap_or_default());
+    }
+
+    pub fn shoe_committed(
+        &mut self,
+        id: u32,
+        decks: u8,
+        commitment: String,
+        client_seeds: Vec<String>,
+    ) {
+        // A shoe can run out part way through a round, so the new shoe starts straight after the
+        // cards dealt from the last one rather than after the cards seen so far.
+        let start = match self.shoes.last() {
+            Some(ShoeRecord {
+                start,
+                dealt: Some(dealt),
+                ..
+            }) => start + dealt,
+            _ => self.cards.len(),
+        };
+
+        self.shoes.push(ShoeRecord {
+            id,
+            decks,
+            commitment,
+            client_seeds,
+            start,
+            server_seed: None,
+            dealt: None,
+            verified: false,
+        });
+    }
+
+    /// Returns an error if the reveal doesn't use the seeds that were committed to.
+    pub fn shoe_revealed(
+        &mut self,
+        id: u32,
+        server_seed: String,
+        client_seeds: Vec<String>,
+        dealt: usize,
+    ) -> Result<(), String> {
+        let Some(shoe) = self.shoes.iter_mut().find(|s| s.id == id) else {
+            return Ok(());
+        };
+
+        shoe.server_seed = Some(server_seed);
+        shoe.dealt = Some(dealt);
+        if shoe.client_seeds != client_seeds {
+            return Err(format!(
+                "Shoe {} was revealed with different client seeds to the ones committed to.",
+                id
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks every revealed shoe whose cards have all been seen, returning a message for each.
+    pub fn check_finished_shoes(&mut self) -> Vec<String> {
+        let mut messages = vec![];
+
+        for shoe in self.shoes.iter_mut().filter(|s| !s.verified) {
+            let (Some(server_seed), Some(dealt)) = (&shoe.server_seed, shoe.dealt) else {
+                continue;
+            };
+            let Some(cards) = self.cards.get(shoe.start..shoe.start + dealt) else {
+                continue;
+            };
+            if cards.iter().any(|c| c.is_none()) {
+                continue;
+            }
+
+            shoe.verified = true;
+            messages.push(describe_check(shoe, server_seed, cards));
+        }
+
+        messages
+    }
+
+    /// Checks every revealed shoe, skipping any cards that weren't seen.
+    pub fn check_all_shoes(&self) -> Vec<String> {
+        self.shoes
+            .iter()
+            .map(|shoe| match (&shoe.server_seed, shoe.dealt) {
+                (Some(server_seed), Some(dealt)) => {
+                    let end = (shoe.start + dealt).min(self.cards.len());
+                    let cards = self.cards.get(shoe.start..end).unwrap_or_default();
+                    describe_check(shoe, server_seed, cards)
+                }
+                _ => format!("Shoe {} hasn't been revealed yet.", shoe.id),
+            })
+            .collect()
+    }
+}
+
+fn describe_check(shoe: &ShoeRecord, server_seed: &str, cards: &[Option<Card>]) -> String {
+    match verify_shoe(
+        shoe.decks,
+        &shoe.commitment,
+        server_seed,
+        &shoe.client_seeds,
+        cards,
+    ) {
+        Ok(checked) => format!(
+            "Shoe {} is fair: the seed matches the commitment and all {} cards seen match the shuffle.",
+            shoe.id, checked
+        ),
+        Err(e) => format!("Shoe {} FAILED verification: {}", shoe.id, e),
+    }
+}
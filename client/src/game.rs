@@ -1,121 +1,265 @@
-use std::{io::Read, io::Write};
-
-use blackjack_shared::player::{get_hand_value, PlayerAction};
+use std::path::PathBuf;
+
+use blackjack_client_core::{Turn, TurnState};
+use blackjack_shared::card_art::{render_hand, ArtStyle};
+use blackjack_shared::ev::{ActionValues, EvCalculator, ShoeComposition};
+use blackjack_shared::player::PlayerAction;
+use blackjack_shared::rules::TableRules;
+use blackjack_shared::strategy::StrategyTable;
+use blackjack_shared::tournament::Standing;
 use blackjack_shared::web_socket::*;
 use blackjack_shared::{card::Card, player::Player};
-use tungstenite::WebSocket;
 
-use crate::*;
+use crate::{auto::Autopilot, fairness::FairnessLog, stats::SessionStats, trainer::Trainer, *};
 
-pub fn start_turn<S>(socket: &mut WebSocket<S>, me: &mut Player, dealer_card: Card)
-where
-    S: Read + Write,
-{
-    let draw_req = BlackjackRequest {
-        command: RequestCommand::DrawCards(2),
-    };
+/// Settings that change how the player's turn is played.
+pub struct TurnSettings {
+    /// Basic strategy for the table's rules, used for hints.
+    pub strategy: StrategyTable,
+    /// Warn before taking an action that goes against basic strategy.
+    pub warn_on_deviation: bool,
+    /// The table's rules, for its bet limits.
+    pub rules: TableRules,
+}
 
-    me.current_bet = bet(me.chips);
+/// State kept for the whole session that's updated as cards are dealt.
+pub struct Session {
+    pub trainer: Option<Trainer>,
+    /// Plays the turns without prompting when the client is run with `--auto`.
+    pub autopilot: Option<Autopilot>,
+    pub stats: SessionStats,
+    /// Where to export the stats when the client exits.
+    pub stats_path: Option<PathBuf>,
+    decks: u8,
+    /// The cards that haven't been seen since the last shuffle.
+    shoe: ShoeComposition,
+    ev: EvCalculator,
+    fairness: FairnessLog,
+    /// Where to save the fairness log so it can be checked later with `verify`.
+    pub fairness_log_path: Option<PathBuf>,
+}
 
-    send_request(
-        BlackjackRequest {
-            command: RequestCommand::Bet(me.current_bet),
-        },
-        socket,
-    );
+impl Session {
+    pub fn new(rules: &TableRules, trainer: Option<Trainer>) -> Self {
+        Session {
+            trainer,
+            autopilot: None,
+            stats: SessionStats::default(),
+            stats_path: None,
+            decks: rules.decks,
+            shoe: ShoeComposition::new(rules.decks),
+            ev: EvCalculator::new(rules),
+            fairness: FairnessLog::default(),
+            fairness_log_path: None,
+        }
+    }
 
-    let draw_res = send_request_and_wait_for_response(draw_req, socket);
+    /// Records cards that have been dealt from the shoe.
+    pub fn cards_seen(&mut self, cards: &[Card]) {
+        self.count(cards);
+        self.fairness.cards_dealt(cards);
+    }
 
-    let drawn_cards = handle_draw_cards(draw_res.as_str());
+    /// Records the dealer's up card at the start of a round.
+    pub fn round_started(&mut self, dealer_card: &Card) {
+        self.count(std::slice::from_ref(dealer_card));
+        self.fairness.round_started(dealer_card);
+    }
 
-    me.hand = drawn_cards.clone();
+    /// Records the rest of the dealer's hand once it's turned over at the end of the round.
+    /// Returns messages about any shoes that could be checked now that every card is known.
+    pub fn dealer_revealed(&mut self, hand: &[Card]) -> Vec<String> {
+        // The up card was counted when the round started.
+        self.count(hand.get(1..).unwrap_or_default());
+        self.fairness.dealer_revealed(hand);
+        self.check_shoes()
+    }
 
-    print!("You drew the following card(s): ");
-    print_cards_in_hand(drawn_cards.clone(), None);
-    println!();
+    /// Handles the triggers about the shoe itself, returning messages to show the player.
+    pub fn shoe_event(&mut self, trigger: PublishTrigger) -> Vec<String> {
+        match trigger {
+            PublishTrigger::ShoeShuffled { decks } => {
+                self.shoe = ShoeComposition::new(self.decks);
+                if let Some(trainer) = &mut self.trainer {
+                    trainer.shoe_shuffled();
+                }
+                if let Some(autopilot) = &mut self.autopilot {
+                    autopilot.shoe_shuffled();
+                }
+                vec![format!("The {} deck shoe has been shuffled.", decks)]
+            }
+            PublishTrigger::ShoeCommitted {
+                shoe_id,
+                decks,
+                commitment,
+                client_seeds,
+            } => {
+                let message = format!("Shoe {} is committed to with hash {}.", shoe_id, commitment);
+                self.fairness
+                    .shoe_committed(shoe_id, decks, commitment, client_seeds);
+                let mut messages = vec![message];
+                messages.extend(self.check_shoes());
+                messages
+            }
+            PublishTrigger::ShoeRevealed {
+                shoe_id,
+                server_seed,
+                client_seeds,
+                dealt,
+            } => {
+                let mut messages =
+                    vec![format!("The seed for shoe {} has been revealed.", shoe_id)];
+                if let Err(e) =
+                    self.fairness
+                        .shoe_revealed(shoe_id, server_seed, client_seeds, dealt)
+                {
+                    messages.push(e);
+                }
+                messages.extend(self.check_shoes());
+                messages
+            }
+            _ => vec![],
+        }
+    }
 
-    println!("Your hand value is: {}", get_hand_value(me.hand.clone()));
+    /// The exact expected value of each action for the hand, given the cards seen so far.
+    pub fn action_values(&mut self, hand: &[Card], dealer_card: &Card) -> ActionValues {
+        self.ev.evaluate(hand, dealer_card, &self.shoe)
+    }
 
-    println!("The dealer's face card is: {}", dealer_card);
+    /// Exports the stats if a file was given. Returns a message saying where they went.
+    pub fn export_stats(&self) -> Option<String> {
+        let path = self.stats_path.as_ref()?;
+        Some(match self.stats.export(path) {
+            Ok(()) => format!("Saved the session stats to {}.", path.display()),
+            Err(e) => format!("Couldn't save the session stats: {}", e),
+        })
+    }
 
-    play_turn(me, socket);
+    fn count(&mut self, cards: &[Card]) {
+        self.shoe.remove_all(cards);
+        if let Some(trainer) = &mut self.trainer {
+            trainer.observe(cards);
+        }
+        if let Some(autopilot) = &mut self.autopilot {
+            autopilot.observe(cards);
+        }
+    }
 
-    println!("Your turn has ended.");
+    fn check_shoes(&mut self) -> Vec<String> {
+        let mut messages = self.fairness.check_finished_shoes();
+        if let Some(path) = &self.fairness_log_path {
+            if let Err(e) = self.fairness.save(path) {
+                messages.push(format!("Couldn't save the fairness log: {}", e));
+            }
+        }
+        messages
+    }
+}
+
+/// Why our turn ended before we'd finished it.
+#[derive(Debug, PartialEq)]
+pub enum TurnEnded {
+    /// The server has closed the connection.
+    Closed,
+    /// We fell behind and the table has moved on to another seat.
+    Skipped,
+}
 
-    let end_turn_req = BlackjackRequest {
-        command: RequestCommand::EndTurn(me.clone()),
+pub fn start_turn(
+    socket: &mut LineSocket,
+    me: &mut Player,
+    dealer_card: Card,
+    settings: &TurnSettings,
+    session: &mut Session,
+) -> Result<(), TurnEnded> {
+    let mut turn = Turn::new(me.clone());
+
+    let amount = match &mut session.autopilot {
+        Some(autopilot) => {
+            let amount = autopilot.bet(me.chips);
+            println!("You bet {} chips.", amount);
+            amount
+        }
+        None => bet(me.chips, &settings.rules),
     };
+    socket.send_commands(turn.bet(amount));
 
-    send_request(end_turn_req, socket);
-}
+    let drawn_cards = wait_for_drawn_cards(socket, session, turn.player())?;
+    let commands = turn.cards_drawn(&drawn_cards);
 
-fn play_turn<S>(player: &mut Player, socket: &mut WebSocket<S>)
-where
-    S: Read + Write,
-{
-    let mut can_take_action = true;
+    println!("You drew the following card(s):");
+    print_cards_in_hand(drawn_cards, None);
 
-    while can_take_action {
-        println!("\nWhat action would you like to take? (Hit, Stand, Double or Split)");
-        let action = get_player_action();
+    println!("Your hand value is: {}", turn.player().hand_value);
 
-        match action {
-            PlayerAction::Hit => {
-                hit(player, socket);
-            }
-            PlayerAction::Stand => {
-                can_take_action = false;
-            }
-            PlayerAction::Double => {
-                if player.current_bet * 2 > player.chips {
-                    println!("You don't have enough chips to double your bet!");
-                    continue;
-                }
+    println!("The dealer's face card is:");
+    print_art(render_hand(
+        std::slice::from_ref(&dealer_card),
+        1,
+        ArtStyle::detect(),
+    ));
 
-                hit(player, socket);
+    socket.send_commands(commands);
+    play_turn(&mut turn, socket, &dealer_card, settings, session)?;
 
-                player.current_bet *= 2;
-                send_request(
-                    BlackjackRequest {
-                        command: RequestCommand::Bet(player.current_bet),
-                    },
-                    socket,
-                );
+    println!("Your turn has ended.");
+    *me = turn.into_player();
+    Ok(())
+}
 
-                can_take_action = false;
+fn play_turn(
+    turn: &mut Turn,
+    socket: &mut LineSocket,
+    dealer_card: &Card,
+    settings: &TurnSettings,
+    session: &mut Session,
+) -> Result<(), TurnEnded> {
+    while turn.state() == TurnState::Acting {
+        let hand = turn.player().hand.clone();
+        let action = match &mut session.autopilot {
+            Some(autopilot) => {
+                let action = autopilot.action(&hand, dealer_card, turn.can_double());
+                println!("\nYou chose to {}.", format!("{:?}", action).to_lowercase());
+                action
             }
-        }
-        println!("Your hand is now: ");
-        print_cards_in_hand(player.hand.clone(), None);
-        println!();
-
-        player.hand_value = get_hand_value(player.hand.clone());
-        println!("Your hand value is: {}", player.hand_value);
+            None => {
+                println!("\nWhat action would you like to take? (Hit, Stand or Double)");
+                println!("Type 'hint' to see the basic strategy move and the odds of each action, or 'stats' for the session so far.");
+                get_player_action(&hand, dealer_card, settings, session)
+            }
+        };
 
-        if player.hand_value > 21 {
-            println!("You busted!");
-            can_take_action = false;
+        if action == PlayerAction::Double && !turn.can_double() {
+            println!("You don't have enough chips to double your bet!");
+            continue;
         }
-    }
-}
+        session
+            .stats
+            .decision(&settings.strategy, &hand, dealer_card, action);
 
-fn hit(player: &mut Player, socket: &mut WebSocket<impl Read + Write>) {
-    let req = BlackjackRequest {
-        command: RequestCommand::Hit,
-    };
-
-    // TODO: Might want the card to be returned in the message.
-    send_request(req, socket);
+        socket.send_commands(turn.act(action));
+        if turn.state() == TurnState::Finished {
+            break;
+        }
 
-    let res = wait_for_message(socket);
+        // TODO: Might want the card to be returned in the message.
+        let cards_drawn = wait_for_drawn_cards(socket, session, turn.player())?;
+        let commands = turn.cards_drawn(&cards_drawn);
 
-    let cards_drawn = handle_draw_cards(res.into_text().unwrap().as_str());
+        println!("You drew the following card(s):");
+        print_cards_in_hand(cards_drawn, None);
 
-    print!("You drew the following card(s): ");
-    print_cards_in_hand(cards_drawn.clone(), None);
-    println!();
+        println!("Your hand is now:");
+        print_cards_in_hand(turn.player().hand.clone(), None);
+        println!("Your hand value is: {}", turn.player().hand_value);
 
-    player.hand.extend(cards_drawn);
+        if turn.is_bust() {
+            println!("You busted!");
+        }
+        socket.send_commands(commands);
+    }
+    Ok(())
 }
 
 pub fn handle_bets(player: &Player, end_state: &EndState, is_current_player: bool) {
@@ -142,19 +286,170 @@ pub fn handle_bets(player: &Player, end_state: &EndState, is_current_player: boo
     }
 }
 
-fn handle_draw_cards(msg: &str) -> Vec<Card> {
-    let mut drawn_cards: Vec<Card> = vec![];
+/// Waits for the next set of drawn cards. The bet we've just placed being published back to us is
+/// skipped, and anything else that arrives first is put aside to be handled after our turn. If
+/// we fell behind, the cards are taken from the snapshot of the table sent in their place.
+fn wait_for_drawn_cards(
+    socket: &mut LineSocket,
+    session: &mut Session,
+    me: &Player,
+) -> Result<Vec<Card>, TurnEnded> {
+    loop {
+        let trigger = socket.receive().ok_or(TurnEnded::Closed)?;
+        if let PublishTrigger::TableSnapshot {
+            active_player,
+            players,
+            ..
+        } = &trigger
+        {
+            let our_turn = active_player.as_deref() == Some(me.user_name.as_str());
+            let hand = players
+                .iter()
+                .find(|p| p.user_name == me.user_name)
+                .map(|p| p.hand.clone())
+                .unwrap_or_default();
+            socket.defer(trigger);
+
+            if !our_turn {
+                return Err(TurnEnded::Skipped);
+            }
+            if hand.len() > me.hand.len() {
+                let cards = hand[me.hand.len()..].to_vec();
+                session.cards_seen(&cards);
+                return Ok(cards);
+            }
+            continue;
+        }
 
-    if let Ok(res) = serde_json::from_str::<PublishRequest>(msg) {
-        if let PublishTrigger::CardsDrawn { cards } = res.trigger {
-            drawn_cards = cards;
+        match trigger {
+            PublishTrigger::CardsDrawn { cards } => {
+                session.cards_seen(&cards);
+                return Ok(cards);
+            }
+            trigger @ (PublishTrigger::ShoeShuffled { .. }
+            | PublishTrigger::ShoeCommitted { .. }
+            | PublishTrigger::ShoeRevealed { .. }) => {
+                session
+                    .shoe_event(trigger)
+                    .iter()
+                    .for_each(|m| println!("{}", m));
+            }
+            PublishTrigger::BetPlaced { user_name, .. } if user_name == me.user_name => {}
+            trigger => socket.defer(trigger),
         }
     }
+}
 
-    drawn_cards
+/// Describes an operator changing a player's chips.
+pub fn chips_adjusted_line(user_name: &str, chips: u32, change: i64, reason: &str) -> String {
+    format!(
+        "{}'s chips were adjusted by {:+} to {}: {}",
+        user_name, change, chips, reason
+    )
 }
 
+/// Describes the server shutting down.
+pub fn shutting_down_line(finish_round: bool) -> String {
+    let when = if finish_round {
+        "once this round is over"
+    } else {
+        "now"
+    };
+    format!(
+        "The server is shutting down {}. Join again under the same name when it's back to keep \
+         your seat and chips.",
+        when
+    )
+}
+
+/// Describes how a player's connection is doing.
+pub fn connection_line(
+    user_name: &str,
+    quality: ConnectionQuality,
+    latency_ms: Option<u64>,
+) -> String {
+    let latency = latency_ms
+        .map(|latency| format!(" ({} ms)", latency))
+        .unwrap_or_default();
+    match quality {
+        ConnectionQuality::Good => format!("{}'s connection is good{}.", user_name, latency),
+        ConnectionQuality::Slow => format!("{}'s connection is slow{}.", user_name, latency),
+        ConnectionQuality::Unresponsive => format!("{} isn't responding.", user_name),
+        ConnectionQuality::Disconnected => {
+            format!("{} has disconnected and left the table.", user_name)
+        }
+    }
+}
+
+/// Describes the table from a snapshot, sent when the connection fell too far behind to be sent
+/// everything that happened.
+pub fn snapshot_lines(
+    round: Option<u64>,
+    active_player: Option<&str>,
+    players: &[Player],
+) -> Vec<String> {
+    let mut lines = vec![match (round, active_player) {
+        (Some(round), Some(active)) => format!(
+            "Caught up with the table after falling behind. Round {}, {}'s turn:",
+            round, active
+        ),
+        (Some(round), None) => format!(
+            "Caught up with the table after falling behind. Round {}:",
+            round
+        ),
+        (None, _) => "Caught up with the table after falling behind:".to_owned(),
+    }];
+    for player in players {
+        let mut line = format!("  {}: {} chips", player.user_name, player.chips);
+        if player.current_bet > 0 {
+            line.push_str(&format!(", bet {}", player.current_bet));
+        }
+        if !player.hand.is_empty() {
+            let cards = player
+                .hand
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+            line.push_str(&format!(", {} ({})", cards, player.hand_value));
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Describes the tournament standings after a hand.
+pub fn standings_lines(
+    hand: u32,
+    hands: u32,
+    standings: &[Standing],
+    eliminated: &[String],
+) -> Vec<String> {
+    let mut lines = vec![format!("Standings after hand {} of {}:", hand, hands)];
+    lines.extend(standings.iter().map(|s| format!("  {}", s)));
+    if !eliminated.is_empty() {
+        lines.push(format!("Knocked out: {}", eliminated.join(", ")));
+    }
+    lines
+}
+
+/// Describes the final standings of a tournament.
+pub fn winner_lines(winner: &str, standings: &[Standing]) -> Vec<String> {
+    let mut lines = vec![format!("The tournament is over. {} wins!", winner)];
+    lines.extend(standings.iter().map(|s| format!("  {}", s)));
+    lines
+}
+
+/// Draws the cards in the hand. Any cards after the first `num_to_show` are drawn face down.
 pub fn print_cards_in_hand(hand: Vec<Card>, num_to_show: Option<usize>) {
-    let num = num_to_show.unwrap_or(hand.len());
-    (0..num).for_each(|n| print!("{} ", hand[n]));
+    let num = num_to_show.unwrap_or(hand.len()).min(hand.len());
+    print_art(render_hand(
+        &hand[..num],
+        hand.len() - num,
+        ArtStyle::detect(),
+    ));
+}
+
+fn print_art(lines: Vec<String>) {
+    lines.iter().for_each(|l| println!("{}", l));
 }
@@ -1,26 +1,162 @@
+mod auto;
+mod fairness;
 mod game;
 mod player_input;
+mod replay;
+mod stats;
+mod trainer;
+mod tui;
 mod web_socket;
 
-use crate::{game::*, player_input::*, web_socket::*};
+use std::{fs, net::TcpStream, path::PathBuf, thread, time::Duration};
 
+use crate::{
+    auto::Autopilot, fairness::FairnessLog, game::*, player_input::*, trainer::Trainer,
+    web_socket::*,
+};
+
+use blackjack_client_core::{register, TableSession, TlsOptions};
 use blackjack_shared::{
+    counting::{counting_system_from_name, BetSpread},
     player::{Player, PlayerType},
+    strategy::StrategyTable,
     web_socket::*,
 };
+use clap::{Parser, Subcommand};
 use color_eyre::eyre::Result;
-use tungstenite::connect;
-use url::Url;
+use tungstenite::{stream::MaybeTlsStream, WebSocket};
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Parser)]
+#[command(about = "A cli based blackjack client")]
+struct Args {
+    /// Use the plain line-based interface instead of the full-screen table view.
+    #[arg(long)]
+    line: bool,
+
+    /// Warn before taking an action that goes against basic strategy.
+    #[arg(long)]
+    warn_deviations: bool,
+
+    /// Join with this name instead of asking for one.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Connect to this server instead of asking for one.
+    #[arg(long)]
+    url: Option<String>,
+
+    /// Trust this PEM certificate authority as well as the system ones for `https` servers.
+    #[arg(long, value_name = "FILE")]
+    ca_cert: Option<PathBuf>,
+
+    /// Accept any certificate from an `https` server. Only for development against a
+    /// self-signed certificate.
+    #[arg(long, conflicts_with = "ca_cert")]
+    insecure: bool,
+
+    /// Play every hand with this strategy without any prompts, then exit with a summary when
+    /// the game finishes. Uses the line-based interface.
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        value_parser = ["basic", "mimic-dealer", "never-bust", "always-stand"],
+        conflicts_with_all = ["trainer", "warn_deviations"]
+    )]
+    auto: Option<String>,
+
+    /// Play the bets and actions in this file first, one per line, before falling back to the
+    /// `--auto` strategy. Turns on `--auto basic` if no strategy is given.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["trainer", "warn_deviations"])]
+    script: Option<PathBuf>,
+
+    /// Units to bet in auto mode at each Hi-Lo true count, starting from a true count of one or
+    /// less.
+    #[arg(long, default_value = "1")]
+    bet_spread: BetSpread,
+
+    /// The chip value of one betting unit in auto mode.
+    #[arg(long, default_value_t = 10)]
+    unit: u32,
+
+    /// How many seconds the host waits for players to join before starting in auto mode.
+    #[arg(long, default_value_t = 0)]
+    start_delay: u64,
+
+    /// Keep a card count with this system and get quizzed on it during play.
+    #[arg(long, value_name = "SYSTEM", value_parser = ["hi-lo", "ko", "omega-ii"])]
+    trainer: Option<String>,
+
+    /// The chance of being quizzed each time cards are dealt when the trainer is on.
+    #[arg(long, default_value_t = 0.2)]
+    quiz_chance: f64,
+
+    /// A seed of your own to mix into the server's next shuffle.
+    #[arg(long)]
+    client_seed: Option<String>,
+
+    /// Save the cards dealt and the server's shoe commitments here, to check with `verify`.
+    #[arg(long, value_name = "FILE")]
+    fairness_log: Option<PathBuf>,
+
+    /// Save the session stats here when the client exits. Files ending in `.csv` are written as
+    /// CSV and anything else as JSON.
+    #[arg(long, value_name = "FILE")]
+    export_stats: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Checks every revealed shoe in a fairness log against the server's commitments.
+    Verify {
+        /// A log saved with `--fairness-log`.
+        file: PathBuf,
+    },
+    /// Steps through the rounds in a hand history log written by the server, marking plays
+    /// that went against basic strategy.
+    Replay {
+        /// A hand history log, such as the server's `hand_history.jsonl`.
+        file: PathBuf,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
+    let args = Args::parse();
 
-    println!("Please enter your username:");
-    let my_user_name = get_user_input();
+    match &args.command {
+        Some(Command::Verify { file }) => {
+            let log = FairnessLog::load(file)?;
+            if log.shoes.is_empty() {
+                println!("The log doesn't have any shoes in it.");
+            }
+            log.check_all_shoes().iter().for_each(|m| println!("{}", m));
+            return Ok(());
+        }
+        Some(Command::Replay { file }) => return replay::run(file),
+        None => {}
+    }
 
-    println!("Please enter the server url");
-    let mut url = get_user_input();
+    let my_user_name = match args.name {
+        Some(name) => name,
+        None => {
+            println!("Please enter your username:");
+            get_user_input()
+        }
+    };
+
+    let mut url = match args.url {
+        Some(url) => url,
+        None => {
+            println!("Please enter the server url");
+            get_user_input()
+        }
+    };
 
     // Temp to make testing easier.
     if url.is_empty() {
@@ -29,39 +165,105 @@ async fn main() -> Result<()> {
     }
 
     // TODO: Could add a room code to allow multiple games to be played at once.
-    let http_client = reqwest::Client::new();
-    let res_json = http_client
-        .post(url + "/register")
-        .json(&RegisterRequest {
-            user_name: my_user_name.to_string(),
-        })
-        .send()
-        .await?
-        .text()
-        .await?;
+    let tls = TlsOptions {
+        ca_cert: args.ca_cert.as_ref().map(fs::read).transpose()?,
+        insecure: args.insecure,
+    };
+    let res = register(&url, &my_user_name, &tls).await?;
+
+    let settings = TurnSettings {
+        strategy: StrategyTable::new(&res.rules),
+        warn_on_deviation: args.warn_deviations,
+        rules: res.rules.clone(),
+    };
+    let trainer = args.trainer.map(|name| {
+        Trainer::new(
+            counting_system_from_name(&name).unwrap(),
+            res.rules.decks,
+            args.quiz_chance.clamp(0.0, 1.0),
+        )
+    });
+    let mut session = Session::new(&res.rules, trainer);
+    session.fairness_log_path = args.fairness_log;
+    session.stats_path = args.export_stats;
+
+    let auto = args.auto.is_some() || args.script.is_some();
+    if auto {
+        let strategy = args.auto.as_deref().unwrap_or("basic");
+        let mut autopilot = Autopilot::new(strategy, &res.rules, args.bet_spread, args.unit)?;
+        if let Some(script) = &args.script {
+            autopilot.load_script(script)?;
+        }
+        session.autopilot = Some(autopilot);
+    }
 
-    let res: RegisterResponse = serde_json::from_str(res_json.as_str())?;
+    let client_seed = args.client_seed.map(RequestCommand::ClientSeed);
 
-    let (mut socket, _) = connect(Url::parse(&res.url).unwrap()).expect("Can't connect");
+    // The line client sits at the table through a session, while the table view polls its own
+    // socket between key presses.
+    if args.line || auto {
+        let socket = LineSocket::new(TableSession::join(res.clone(), &tls).await?);
+        print_connected(&res);
+        socket.send_commands(client_seed.into_iter().collect());
+
+        let start_delay = Duration::from_secs(args.start_delay);
+        // The line client blocks on its prompts, so the runtime moves the session's tasks
+        // elsewhere.
+        tokio::task::block_in_place(|| {
+            run_line_client(socket, &res, my_user_name, &settings, session, start_delay)
+        })
+    } else {
+        let mut socket = connect(&res.url, &tls)?;
+        print_connected(&res);
+        if let Some(command) = client_seed {
+            send_request(BlackjackRequest { command }, &mut socket);
+        }
+        tui::run(&mut socket, &res, my_user_name, settings, session)
+    }
+}
 
+fn print_connected(res: &RegisterResponse) {
     println!("Connected to the server");
+    if let Some(tournament) = &res.tournament {
+        println!(
+            "This table is running a tournament of {} hands. Everyone starts with {} chips.",
+            tournament.hands, tournament.starting_chips
+        );
+    }
+}
+
+/// Plays the game using plain prompts on stdin and stdout.
+fn run_line_client(
+    mut socket: LineSocket,
+    res: &RegisterResponse,
+    my_user_name: String,
+    settings: &TurnSettings,
+    mut session: Session,
+    start_delay: Duration,
+) -> Result<()> {
     let client_id = res.id.clone();
 
-    if res.is_host {
+    if res.is_host && session.autopilot.is_some() {
+        println!("You are the host. Starting the game...");
+        thread::sleep(start_delay);
+        socket.send(RequestCommand::Start);
+    } else if res.is_host {
         loop {
             println!("You are the host. Enter 'start' to begin the game.");
+            println!(
+                "Enter 'bot <strategy>' to seat a bot first. The strategies are basic, \
+                 mimic-dealer, never-bust, always-stand and counter."
+            );
             let input = get_user_input();
             if input == "start" {
-                let req = BlackjackRequest {
-                    command: RequestCommand::Start,
-                };
-                send_request(req, &mut socket);
+                socket.send(RequestCommand::Start);
                 break;
+            } else if let Some(strategy) = input.strip_prefix("bot ") {
+                socket.send(RequestCommand::AddBot(strategy.trim().to_owned()));
             }
         }
     } else {
         println!("Waiting for the host to start the game...");
-        wait_for_message(&mut socket);
     }
 
     println!("The game is starting.");
@@ -71,7 +273,7 @@ async fn main() -> Result<()> {
         player_type: PlayerType::Human,
         hand: vec![],
         hand_value: 0,
-        chips: 500,
+        chips: res.chips,
         current_bet: 0,
     };
 
@@ -79,48 +281,148 @@ async fn main() -> Result<()> {
     //Otherwise wait in a loop checking messages and updating the output as required.
     //Once a start message with our name has been send start playing the game.
     let mut current_player_name = String::new();
+    let mut round_in_progress = false;
     loop {
-        // TODO: Better formatting of text. Could add colours or a library.
         // TODO: Add testing.
         // TODO: Try and limit cloning - change functions to borrow where possible.
-        let message = wait_for_message(&mut socket);
-        let request: PublishRequest = serde_json::from_str(message.into_text().unwrap().as_str())?;
+        let Some(trigger) = socket.wait_for_trigger() else {
+            println!("The server has closed the connection.");
+            break;
+        };
 
-        match request.trigger {
+        match trigger {
             PublishTrigger::StartTurn {
                 active_client_id,
                 user_name,
                 dealer_card,
             } => {
                 current_player_name = user_name.clone();
+
+                // Every turn shows the same dealer card so only count it once per round.
+                if !round_in_progress {
+                    round_in_progress = true;
+                    if let Some(card) = &dealer_card {
+                        session.round_started(card);
+                    }
+                }
+
                 if active_client_id.to_lowercase() == client_id.to_lowercase() {
                     println!();
                     println!("It's your turn!");
                     // TODO: The chips and bet amounts are not shared across clients.
-                    start_turn(&mut socket, &mut me, dealer_card.unwrap());
+                    let turn = start_turn(
+                        &mut socket,
+                        &mut me,
+                        dealer_card.unwrap(),
+                        settings,
+                        &mut session,
+                    );
+                    if !turn_played(turn) {
+                        break;
+                    }
                 } else {
                     println!("It's {}'s turn.", current_player_name);
                     println!("Waiting for our turn...");
                 }
             }
             PublishTrigger::CardsDrawn { cards } => {
-                // FIX: The username does not populate for the none host player.
-                print!("{} drew the following card(s): ", current_player_name);
-                print_cards_in_hand(cards, None);
-                println!();
+                println!("{} drew the following card(s):", current_player_name);
+                print_cards_in_hand(cards.clone(), None);
+
+                session.cards_seen(&cards);
+                if let Some(trainer) = &mut session.trainer {
+                    quiz_player(trainer);
+                }
+            }
+            trigger @ (PublishTrigger::ShoeShuffled { .. }
+            | PublishTrigger::ShoeCommitted { .. }
+            | PublishTrigger::ShoeRevealed { .. }) => {
+                session
+                    .shoe_event(trigger)
+                    .iter()
+                    .for_each(|m| println!("{}", m));
+            }
+            PublishTrigger::BetPlaced { user_name, amount } => {
+                println!("{} bet {} chips.", user_name, amount);
+            }
+            PublishTrigger::ChatMessage { user_name, message } => {
+                println!("[{}] {}", user_name, message);
+            }
+            PublishTrigger::Announcement { message } => {
+                println!("*** {} ***", message);
+            }
+            PublishTrigger::ChipsAdjusted {
+                user_name,
+                chips,
+                change,
+                reason,
+            } => {
+                if user_name.to_lowercase() == my_user_name.to_lowercase() {
+                    me.chips = chips;
+                }
+                println!(
+                    "{}",
+                    chips_adjusted_line(&user_name, chips, change, &reason)
+                );
+            }
+            PublishTrigger::ServerShuttingDown { finish_round } => {
+                println!("*** {} ***", shutting_down_line(finish_round));
+            }
+            PublishTrigger::ConnectionChanged {
+                user_name,
+                quality,
+                latency_ms,
+            } => {
+                println!("{}", connection_line(&user_name, quality, latency_ms));
+            }
+            PublishTrigger::TableSnapshot {
+                round,
+                dealer_card,
+                active_player,
+                players,
+            } => {
+                snapshot_lines(round, active_player.as_deref(), &players)
+                    .iter()
+                    .for_each(|l| println!("{}", l));
+                round_in_progress = round.is_some();
+                current_player_name = active_player.clone().unwrap_or_default();
+
+                let mine = players
+                    .iter()
+                    .find(|p| p.user_name.to_lowercase() == my_user_name.to_lowercase());
+                if let Some(mine) = mine {
+                    me.chips = mine.chips;
+                    // The start of our turn was among the messages that were dropped.
+                    if mine.hand.is_empty() && current_player_name == mine.user_name {
+                        if let Some(card) = dealer_card {
+                            println!();
+                            println!("It's your turn!");
+                            let turn =
+                                start_turn(&mut socket, &mut me, card, settings, &mut session);
+                            if !turn_played(turn) {
+                                break;
+                            }
+                        }
+                    }
+                }
             }
             PublishTrigger::RoundFinished(results) => {
                 println!("The round has finished.");
                 println!();
                 for result in results {
                     if result.player.player_type == PlayerType::Dealer {
-                        println!("The dealer's hand is: ");
-                        print_cards_in_hand(result.player.hand, None);
+                        println!("The dealer's hand is:");
+                        print_cards_in_hand(result.player.hand.clone(), None);
                         println!("The dealer's hand value is: {}", result.player.hand_value);
+                        session
+                            .dealer_revealed(&result.player.hand)
+                            .iter()
+                            .for_each(|m| println!("{}", m));
                         println!();
                     } else if result.player.user_name.to_lowercase() == my_user_name.to_lowercase()
                     {
                         me = result.player.clone();
+                        session.stats.hand_finished(&me, &result.end_state);
                         handle_bets(&me, &result.end_state, true);
                         me.current_bet = 0;
                     } else {
@@ -128,21 +430,66 @@ async fn main() -> Result<()> {
                     }
                 }
 
+                round_in_progress = false;
+                if let Some(trainer) = &mut session.trainer {
+                    quiz_player(trainer);
+                }
+
                 // Start the next round.
                 // If the game is over the server will let all clients know.
                 if res.is_host {
-                    let req = BlackjackRequest {
-                        command: RequestCommand::Start,
-                    };
-                    send_request(req, &mut socket);
+                    socket.send(RequestCommand::Start);
                 }
             }
+            PublishTrigger::TournamentStandings {
+                hand,
+                hands,
+                standings,
+                eliminated,
+            } => {
+                standings_lines(hand, hands, &standings, &eliminated)
+                    .iter()
+                    .for_each(|l| println!("{}", l));
+                println!();
+            }
+            PublishTrigger::TournamentFinished { winner, standings } => {
+                winner_lines(&winner, &standings)
+                    .iter()
+                    .for_each(|l| println!("{}", l));
+            }
             PublishTrigger::GameFinished => {
                 println!("The game has finished.");
+                session
+                    .stats
+                    .summary()
+                    .iter()
+                    .for_each(|l| println!("{}", l));
+                if let Some(trainer) = &session.trainer {
+                    println!("{}", trainer.summary());
+                }
                 break;
             }
         }
     }
 
+    if let Some(message) = session.export_stats() {
+        println!("{}", message);
+    }
+
     Ok(())
 }
+
+/// Reports a turn that ended early, returning whether the game can carry on.
+fn turn_played(turn: std::result::Result<(), TurnEnded>) -> bool {
+    match turn {
+        Ok(()) => true,
+        Err(TurnEnded::Skipped) => {
+            println!("The table has moved on without us.");
+            true
+        }
+        Err(TurnEnded::Closed) => {
+            println!("The server has closed the connection.");
+            false
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use std::io;
 
-use blackjack_shared::player::PlayerAction;
+use blackjack_shared::{card::Card, player::PlayerAction, rules::TableRules};
+
+use crate::{
+    game::{Session, TurnSettings},
+    trainer::Trainer,
+};
 
 pub fn get_user_input() -> String {
     let mut input = String::new();
@@ -12,7 +17,7 @@ pub fn get_user_input() -> String {
     input.trim().to_lowercase()
 }
 
-pub fn bet(chips: u32) -> u32 {
+pub fn bet(chips: u32, rules: &TableRules) -> u32 {
     println!("You have {} chips.", chips);
     println!("Place your bet: ");
 
@@ -24,10 +29,10 @@ pub fn bet(chips: u32) -> u32 {
             .expect("Failed to read the bet.");
 
         if let Ok(input_num) = input.trim().parse::<u32>() {
-            if input_num <= chips {
-                break input_num;
+            match rules.check_bet(input_num, chips) {
+                Ok(()) => break input_num,
+                Err(e) => println!("{}", e),
             }
-            println!("You don't have enough chips to cover that bet!");
             println!("Please enter a new bet: ");
         } else {
             println!("Please enter a vaild number: ")
@@ -37,7 +42,16 @@ pub fn bet(chips: u32) -> u32 {
     bet
 }
 
-pub fn get_player_action() -> PlayerAction {
+/// Asks the player for their next move. Typing `hint` shows the basic strategy move and the
+/// expected value of each action, and `stats` shows the session stats so far.
+pub fn get_player_action(
+    hand: &[Card],
+    dealer_card: &Card,
+    settings: &TurnSettings,
+    session: &mut Session,
+) -> PlayerAction {
+    let recommended = settings.strategy.playable_action(hand, dealer_card);
+
     loop {
         let mut input = String::new();
 
@@ -47,12 +61,53 @@ pub fn get_player_action() -> PlayerAction {
 
         let trimmed_input = input.trim().to_lowercase();
 
-        match trimmed_input.as_str() {
-            "hit" => return PlayerAction::Hit,
-            "stand" => return PlayerAction::Stand,
-            "double" => return PlayerAction::Double,
-            _ => println!("Move not recognised. Please enter a vaild move:"),
+        let action = match trimmed_input.as_str() {
+            "hit" => PlayerAction::Hit,
+            "stand" => PlayerAction::Stand,
+            "double" => PlayerAction::Double,
+            "hint" => {
+                println!("Basic strategy says: {}", recommended);
+                println!(
+                    "Expected value: {}",
+                    session.action_values(hand, dealer_card)
+                );
+                continue;
+            }
+            "stats" => {
+                session
+                    .stats
+                    .summary()
+                    .iter()
+                    .for_each(|l| println!("{}", l));
+                continue;
+            }
+            _ => {
+                println!("Move not recognised. Please enter a vaild move:");
+                println!();
+                continue;
+            }
         };
-        println!();
+
+        if settings.warn_on_deviation && !recommended.matches(&action) {
+            println!(
+                "Basic strategy says to {} here. Are you sure? (y/n)",
+                recommended
+            );
+            if get_user_input() != "y" {
+                println!("Please enter a move:");
+                continue;
+            }
+        }
+
+        return action;
+    }
+}
+
+/// Asks the player any quiz questions the trainer has for them right now.
+pub fn quiz_player(trainer: &mut Trainer) {
+    for question in trainer.quiz() {
+        println!("{}", question.prompt());
+        let answer = get_user_input();
+        println!("{}", trainer.answer(question, &answer));
     }
 }
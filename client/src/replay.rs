@@ -0,0 +1,416 @@
+use std::{fs, path::Path};
+
+use blackjack_shared::{
+    card::Card,
+    history::{parse_history, RoundEventKind, RoundRecord},
+    player::{get_hand_value, PlayerAction},
+    strategy::{hand_total, PlayerStrategy, StrategyTable},
+    web_socket::EndState,
+};
+use color_eyre::eyre::Result;
+
+use crate::{game::print_cards_in_hand, player_input::get_user_input};
+
+/// The table as it was after one event in a recorded round.
+#[derive(Debug, Clone)]
+struct TableState {
+    dealer_hand: Vec<Card>,
+    /// The dealer's hole card stays hidden until the dealer plays.
+    dealer_hidden: bool,
+    seats: Vec<ReplaySeat>,
+    active_seat: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ReplaySeat {
+    user_name: String,
+    hand: Vec<Card>,
+    bet: u32,
+    chips: u32,
+    result: Option<EndState>,
+}
+
+#[derive(Debug)]
+struct Step {
+    state: TableState,
+    description: String,
+    /// Set when the player went against basic strategy.
+    mistake: Option<String>,
+}
+
+#[derive(Debug)]
+struct ReplayRound {
+    record: RoundRecord,
+    steps: Vec<Step>,
+}
+
+/// Steps through a hand history log, marking any plays that went against basic strategy.
+pub fn run(path: &Path) -> Result<()> {
+    let rounds: Vec<ReplayRound> = parse_history(&fs::read_to_string(path)?)
+        .into_iter()
+        .map(build_round)
+        .collect();
+
+    if rounds.is_empty() {
+        println!("The log doesn't have any rounds in it.");
+        return Ok(());
+    }
+
+    let mistakes = rounds
+        .iter()
+        .flat_map(|r| &r.steps)
+        .filter(|s| s.mistake.is_some())
+        .count();
+    println!(
+        "Loaded {} rounds with {} plays against basic strategy.",
+        rounds.len(),
+        mistakes
+    );
+
+    let (mut round, mut step) = (0, 0);
+    loop {
+        show_step(&rounds, round, step);
+        println!(
+            "n: next  p: previous  nr/pr: next/previous round  m: next mistake  \
+             g <round>: go to round  q: quit"
+        );
+
+        let input = get_user_input();
+        let last_step = rounds[round].steps.len() - 1;
+        match input.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [] | ["n"] => {
+                if step < last_step {
+                    step += 1;
+                } else if round + 1 < rounds.len() {
+                    (round, step) = (round + 1, 0);
+                } else {
+                    println!("That's the end of the log.");
+                }
+            }
+            ["p"] => {
+                if step > 0 {
+                    step -= 1;
+                } else if round > 0 {
+                    round -= 1;
+                    step = rounds[round].steps.len() - 1;
+                } else {
+                    println!("That's the start of the log.");
+                }
+            }
+            ["nr"] => (round, step) = ((round + 1).min(rounds.len() - 1), 0),
+            ["pr"] => (round, step) = (round.saturating_sub(1), 0),
+            ["m"] => match next_mistake(&rounds, round, step) {
+                Some(position) => (round, step) = position,
+                None => println!("There are no more mistakes in the log."),
+            },
+            ["g", number] => match number
+                .parse::<u64>()
+                .ok()
+                .and_then(|n| rounds.iter().position(|r| r.record.round == n))
+            {
+                Some(index) => (round, step) = (index, 0),
+                None => println!("There's no round {} in the log.", number),
+            },
+            ["q"] => break,
+            _ => println!("Command not recognised."),
+        }
+    }
+
+    Ok(())
+}
+
+fn next_mistake(rounds: &[ReplayRound], round: usize, step: usize) -> Option<(usize, usize)> {
+    rounds
+        .iter()
+        .enumerate()
+        .skip(round)
+        .flat_map(|(r, replay_round)| {
+            replay_round
+                .steps
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| s.mistake.is_some())
+                .map(move |(s, _)| (r, s))
+        })
+        .find(|position| *position > (round, step))
+}
+
+/// Plays the round's events back in order, keeping a copy of the table after each one.
+fn build_round(record: RoundRecord) -> ReplayRound {
+    let strategy = StrategyTable::new(&record.rules);
+    let mut state = TableState {
+        dealer_hand: vec![],
+        dealer_hidden: true,
+        seats: record
+            .seats
+            .iter()
+            .map(|s| ReplaySeat {
+                user_name: s.user_name.clone(),
+                hand: vec![],
+                bet: 0,
+                chips: s.chips,
+                result: None,
+            })
+            .collect(),
+        active_seat: None,
+    };
+    let mut steps = vec![];
+
+    for (i, event) in record.events.iter().enumerate() {
+        let mut mistake = None;
+
+        let description = match &event.kind {
+            RoundEventKind::DealerDealt { cards } => {
+                state.dealer_hand = cards.clone();
+                match cards.first() {
+                    Some(card) => format!("The dealer was dealt {} and a hole card.", card),
+                    None => "The dealer was dealt their cards.".to_owned(),
+                }
+            }
+            RoundEventKind::TurnStarted { user_name } => {
+                state.active_seat = Some(user_name.clone());
+                format!("It's {}'s turn.", user_name)
+            }
+            RoundEventKind::Bet { user_name, amount } => {
+                seat_mut(&mut state, user_name).bet = *amount;
+                format!("{} bet {} chips.", user_name, amount)
+            }
+            RoundEventKind::Double { user_name, amount } => {
+                // Older logs record a double as a hit followed by the raised bet, which is
+                // checked at the hit.
+                if seat_mut(&mut state, user_name).hand.len() == 2 {
+                    mistake = check_play(&strategy, &state, user_name, PlayerAction::Double);
+                }
+                seat_mut(&mut state, user_name).bet = *amount;
+                format!("{} doubled their bet to {} chips.", user_name, amount)
+            }
+            RoundEventKind::Hit { user_name } => {
+                // Older logs record a double as a hit followed by the raised bet.
+                let doubled = record.events[i + 1..]
+                    .iter()
+                    .take_while(|e| !ends_decision(&e.kind, user_name))
+                    .any(|e| is_double(&e.kind, user_name));
+                let action = if doubled {
+                    PlayerAction::Double
+                } else {
+                    PlayerAction::Hit
+                };
+                mistake = check_play(&strategy, &state, user_name, action);
+
+                if doubled {
+                    format!("{} doubled down.", user_name)
+                } else {
+                    format!("{} hit.", user_name)
+                }
+            }
+            RoundEventKind::CardsDealt { user_name, cards } => {
+                seat_mut(&mut state, user_name)
+                    .hand
+                    .extend(cards.iter().cloned());
+                format!("{} was dealt {}.", user_name, join_cards(cards))
+            }
+            RoundEventKind::Stand {
+                user_name,
+                hand_value,
+            } => {
+                // Turns end on their own after a bust or a double, so only a stand by choice is
+                // checked.
+                let doubled = record.events[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|e| !matches!(&e.kind, RoundEventKind::TurnStarted { .. }))
+                    .any(|e| is_double(&e.kind, user_name));
+                if *hand_value <= 21 && !doubled {
+                    mistake = check_play(&strategy, &state, user_name, PlayerAction::Stand);
+                }
+
+                if *hand_value > 21 {
+                    format!("{} busted with {}.", user_name, hand_value)
+                } else {
+                    format!("{} stood on {}.", user_name, hand_value)
+                }
+            }
+            RoundEventKind::DealerDrew { card } => {
+                state.active_seat = None;
+                state.dealer_hidden = false;
+                state.dealer_hand.push(card.clone());
+                format!("The dealer drew {}.", card)
+            }
+            RoundEventKind::ShoeShuffled { shoe_id, .. } => {
+                format!("The shoe was shuffled. Dealing from shoe {}.", shoe_id)
+            }
+        };
+
+        steps.push(Step {
+            state: state.clone(),
+            description,
+            mistake,
+        });
+    }
+
+    state.active_seat = None;
+    state.dealer_hidden = false;
+    state.dealer_hand = record.dealer_hand.clone();
+    let mut results = vec![];
+    for settlement in &record.settlements {
+        let seat = seat_mut(&mut state, &settlement.user_name);
+        seat.hand = settlement.hand.clone();
+        seat.bet = settlement.bet;
+        seat.chips = settlement.chips;
+        seat.result = Some(settlement.end_state.clone());
+        results.push(format!(
+            "{}: {}",
+            settlement.user_name,
+            describe_end_state(&settlement.end_state)
+        ));
+    }
+    steps.push(Step {
+        state,
+        description: format!(
+            "The dealer finished on {}. {}",
+            record.dealer_hand_value,
+            results.join(", ")
+        ),
+        mistake: None,
+    });
+
+    ReplayRound { record, steps }
+}
+
+/// Whether the event means the player has finished deciding on their last action.
+fn ends_decision(kind: &RoundEventKind, user_name: &str) -> bool {
+    match kind {
+        RoundEventKind::Hit { user_name: name }
+        | RoundEventKind::Stand {
+            user_name: name, ..
+        } => name == user_name,
+        RoundEventKind::TurnStarted { .. } | RoundEventKind::DealerDrew { .. } => true,
+        _ => false,
+    }
+}
+
+fn is_double(kind: &RoundEventKind, user_name: &str) -> bool {
+    matches!(kind, RoundEventKind::Double { user_name: name, .. } if name == user_name)
+}
+
+/// Returns a note if basic strategy would have played the hand differently.
+fn check_play(
+    strategy: &StrategyTable,
+    state: &TableState,
+    user_name: &str,
+    action: PlayerAction,
+) -> Option<String> {
+    let hand = &state.seats.iter().find(|s| s.user_name == user_name)?.hand;
+    let dealer_up = state.dealer_hand.first()?;
+    if hand.is_empty() {
+        return None;
+    }
+
+    let recommended = PlayerStrategy::action(strategy, hand, dealer_up);
+    if recommended == action {
+        return None;
+    }
+
+    let (total, soft) = hand_total(hand);
+    Some(format!(
+        "{} chose to {} on {} {} against a {}, but basic strategy says to {}.",
+        user_name,
+        action_name(action),
+        if soft { "soft" } else { "hard" },
+        total,
+        dealer_up,
+        action_name(recommended)
+    ))
+}
+
+fn show_step(rounds: &[ReplayRound], index: usize, step: usize) {
+    let round = &rounds[index];
+    let record = &round.record;
+    let current = &round.steps[step];
+    let state = &current.state;
+
+    println!();
+    println!(
+        "=== Table {} - round {} ({} of {}) - step {} of {} ===",
+        record.table_id,
+        record.round,
+        index + 1,
+        rounds.len(),
+        step + 1,
+        round.steps.len()
+    );
+
+    println!("Dealer:");
+    if state.dealer_hidden {
+        print_cards_in_hand(state.dealer_hand.clone(), Some(1));
+    } else {
+        print_cards_in_hand(state.dealer_hand.clone(), None);
+        println!("Value: {}", get_hand_value(state.dealer_hand.clone()));
+    }
+
+    for seat in &state.seats {
+        let active = state.active_seat.as_deref() == Some(seat.user_name.as_str());
+        println!();
+        println!(
+            "{}{} - bet {} - chips {}{}",
+            if active { "> " } else { "" },
+            seat.user_name,
+            seat.bet,
+            seat.chips,
+            seat.result
+                .as_ref()
+                .map(|r| format!(" - {}", describe_end_state(r)))
+                .unwrap_or_default()
+        );
+        if !seat.hand.is_empty() {
+            print_cards_in_hand(seat.hand.clone(), None);
+            println!("Value: {}", get_hand_value(seat.hand.clone()));
+        }
+    }
+
+    println!();
+    println!("{}", current.description);
+    if let Some(mistake) = &current.mistake {
+        println!("MISTAKE: {}", mistake);
+    }
+}
+
+fn seat_mut<'a>(state: &'a mut TableState, user_name: &str) -> &'a mut ReplaySeat {
+    if let Some(index) = state.seats.iter().position(|s| s.user_name == user_name) {
+        return &mut state.seats[index];
+    }
+
+    state.seats.push(ReplaySeat {
+        user_name: user_name.to_owned(),
+        hand: vec![],
+        bet: 0,
+        chips: 0,
+        result: None,
+    });
+    state.seats.last_mut().unwrap()
+}
+
+fn join_cards(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn action_name(action: PlayerAction) -> &'static str {
+    match action {
+        PlayerAction::Hit => "hit",
+        PlayerAction::Stand => "stand",
+        PlayerAction::Double => "double",
+    }
+}
+
+fn describe_end_state(end_state: &EndState) -> &'static str {
+    match end_state {
+        EndState::Win => "win",
+        EndState::Loss => "loss",
+        EndState::Blackjack => "blackjack",
+        EndState::Push => "push",
+    }
+}
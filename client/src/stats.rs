@@ -0,0 +1,138 @@
+use std::{fs, path::Path};
+
+use blackjack_shared::{
+    card::Card,
+    player::{Player, PlayerAction},
+    strategy::{PlayerStrategy, StrategyTable},
+    web_socket::EndState,
+};
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+/// Results and decisions for our own hands over the session.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SessionStats {
+    pub hands: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub pushes: u32,
+    pub blackjacks: u32,
+    /// Chips won less chips lost.
+    pub net_chips: i64,
+    pub biggest_win: u32,
+    pub biggest_loss: u32,
+    pub doubles: u32,
+    /// Stays at zero until the server supports splitting.
+    pub splits: u32,
+    /// Every hit, stand or double we chose.
+    pub decisions: u32,
+    /// The decisions that matched basic strategy.
+    pub correct_decisions: u32,
+}
+
+impl SessionStats {
+    /// Records an action taken on our hand, checking it against basic strategy.
+    pub fn decision(
+        &mut self,
+        strategy: &StrategyTable,
+        hand: &[Card],
+        dealer_card: &Card,
+        action: PlayerAction,
+    ) {
+        self.decisions += 1;
+        // Splitting and surrendering can't be taken yet, so pairs are judged on their total,
+        // the same as in `replay`.
+        if PlayerStrategy::action(strategy, hand, dealer_card) == action {
+            self.correct_decisions += 1;
+        }
+        if action == PlayerAction::Double {
+            self.doubles += 1;
+        }
+    }
+
+    /// Records how our hand was settled.
+    pub fn hand_finished(&mut self, player: &Player, end_state: &EndState) {
+        self.hands += 1;
+
+        match end_state {
+            EndState::Win => self.wins += 1,
+            EndState::Blackjack => self.blackjacks += 1,
+            EndState::Loss => self.losses += 1,
+            EndState::Push => self.pushes += 1,
+        }
+
+        let won = end_state.net_chips(player.current_bet);
+        self.net_chips += won;
+        if won > 0 {
+            self.biggest_win = self.biggest_win.max(won as u32);
+        } else {
+            self.biggest_loss = self.biggest_loss.max(won.unsigned_abs() as u32);
+        }
+    }
+
+    /// The percentage of decisions that matched basic strategy.
+    pub fn accuracy(&self) -> f64 {
+        if self.decisions == 0 {
+            0.0
+        } else {
+            self.correct_decisions as f64 / self.decisions as f64 * 100.0
+        }
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Hands played: {} ({} won, {} lost, {} pushed, {} blackjacks)",
+                self.hands, self.wins, self.losses, self.pushes, self.blackjacks
+            ),
+            format!(
+                "Net chips: {:+} (biggest win {}, biggest loss {})",
+                self.net_chips, self.biggest_win, self.biggest_loss
+            ),
+            format!("Doubles: {}  Splits: {}", self.doubles, self.splits),
+            format!(
+                "Strategy accuracy: {} of {} decisions ({:.0}%)",
+                self.correct_decisions,
+                self.decisions,
+                self.accuracy()
+            ),
+        ]
+    }
+
+    /// Writes the stats as CSV if the file ends in `.csv`, otherwise as JSON.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+
+        let contents = if is_csv {
+            self.to_csv()
+        } else {
+            serde_json::to_string_pretty(self)?
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// A header line followed by a single line of values.
+    fn to_csv(&self) -> String {
+        let fields = [
+            ("hands", self.hands.to_string()),
+            ("wins", self.wins.to_string()),
+            ("losses", self.losses.to_string()),
+            ("pushes", self.pushes.to_string()),
+            ("blackjacks", self.blackjacks.to_string()),
+            ("net_chips", self.net_chips.to_string()),
+            ("biggest_win", self.biggest_win.to_string()),
+            ("biggest_loss", self.biggest_loss.to_string()),
+            ("doubles", self.doubles.to_string()),
+            ("splits", self.splits.to_string()),
+            ("decisions", self.decisions.to_string()),
+            ("correct_decisions", self.correct_decisions.to_string()),
+            ("accuracy", format!("{:.1}", self.accuracy())),
+        ];
+
+        let (header, values): (Vec<&str>, Vec<String>) = fields.into_iter().unzip();
+        format!("{}\n{}\n", header.join(","), values.join(","))
+    }
+}
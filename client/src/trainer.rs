@@ -0,0 +1,111 @@
+use blackjack_shared::{
+    card::Card,
+    counting::{Counter, CountingSystem},
+};
+use rand::Rng;
+
+/// How far a true count answer can be from the exact value and still be marked correct.
+const TRUE_COUNT_TOLERANCE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Question {
+    RunningCount,
+    TrueCount,
+}
+
+impl Question {
+    pub fn prompt(&self) -> &'static str {
+        match self {
+            Question::RunningCount => "Quiz: what's the running count?",
+            Question::TrueCount => "Quiz: what's the true count?",
+        }
+    }
+}
+
+/// Keeps the count for every card dealt and quizzes the player on it at random moments.
+pub struct Trainer {
+    counter: Counter,
+    quiz_chance: f64,
+    asked: u32,
+    correct: u32,
+}
+
+impl Trainer {
+    pub fn new(system: Box<dyn CountingSystem + Send>, decks: u8, quiz_chance: f64) -> Self {
+        Trainer {
+            counter: Counter::new(system, decks),
+            quiz_chance,
+            asked: 0,
+            correct: 0,
+        }
+    }
+
+    pub fn observe(&mut self, cards: &[Card]) {
+        self.counter.observe(cards);
+    }
+
+    pub fn shoe_shuffled(&mut self) {
+        self.counter.reset();
+    }
+
+    /// Returns the questions to ask now. Most of the time this is empty.
+    pub fn quiz(&mut self) -> Vec<Question> {
+        if !rand::thread_rng().gen_bool(self.quiz_chance) {
+            return vec![];
+        }
+
+        if self.counter.system().is_balanced() {
+            vec![Question::RunningCount, Question::TrueCount]
+        } else {
+            vec![Question::RunningCount]
+        }
+    }
+
+    /// Marks the player's answer and returns the feedback to show them.
+    pub fn answer(&mut self, question: Question, input: &str) -> String {
+        self.asked += 1;
+
+        let (is_correct, actual) = match question {
+            Question::RunningCount => {
+                let actual = self.counter.running_count();
+                (
+                    input.trim().parse::<i32>().is_ok_and(|a| a == actual),
+                    actual.to_string(),
+                )
+            }
+            Question::TrueCount => {
+                let actual = self.counter.true_count().unwrap_or_default();
+                (
+                    input
+                        .trim()
+                        .parse::<f64>()
+                        .is_ok_and(|a| (a - actual).abs() <= TRUE_COUNT_TOLERANCE),
+                    format!("{:.1}", actual),
+                )
+            }
+        };
+
+        if is_correct {
+            self.correct += 1;
+            format!("Correct! The count is {}.", actual)
+        } else {
+            format!("Not quite, the count is {}.", actual)
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let accuracy = if self.asked == 0 {
+            0.0
+        } else {
+            self.correct as f64 / self.asked as f64 * 100.0
+        };
+
+        format!(
+            "{} trainer: {} of {} answers correct ({:.0}%).",
+            self.counter.system().name(),
+            self.correct,
+            self.asked,
+            accuracy
+        )
+    }
+}
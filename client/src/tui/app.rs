@@ -0,0 +1,709 @@
+use blackjack_client_core::{Turn, TurnState};
+use blackjack_shared::{
+    card::Card,
+    player::{get_hand_value, Player, PlayerAction, PlayerType},
+    strategy::StrategyAction,
+    web_socket::*,
+};
+use crossterm::event::{KeyCode, KeyEvent};
+
+use crate::{
+    game::{
+        chips_adjusted_line, connection_line, shutting_down_line, snapshot_lines, standings_lines,
+        winner_lines, Session, TurnSettings,
+    },
+    trainer::Question,
+};
+
+/// The most lines kept in the event log and chat panes.
+const MAX_LINES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Phase {
+    /// Waiting for the host to deal the first round.
+    Lobby,
+    /// Another seat is playing or the round is being settled.
+    Waiting,
+    /// It's our turn and we need to place a bet.
+    Betting,
+    /// We've asked the server for cards and are waiting for them to arrive.
+    Drawing,
+    /// It's our turn and we can hit, stand or double.
+    Acting,
+    /// The round has been settled and the next one hasn't been dealt yet.
+    RoundOver,
+    GameOver,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputMode {
+    Normal,
+    Bet,
+    Chat,
+    /// Answering the card counting trainer's questions.
+    Quiz,
+}
+
+#[derive(Debug, Clone)]
+pub struct Seat {
+    pub user_name: String,
+    pub hand: Vec<Card>,
+    pub chips: Option<u32>,
+    pub bet: u32,
+    pub result: Option<EndState>,
+    pub connection: ConnectionQuality,
+    /// The time the last ping took, sent along with changes in the connection.
+    pub latency_ms: Option<u64>,
+}
+
+impl Seat {
+    fn new(user_name: &str) -> Self {
+        Seat {
+            user_name: user_name.to_owned(),
+            hand: vec![],
+            chips: None,
+            bet: 0,
+            result: None,
+            connection: ConnectionQuality::Good,
+            latency_ms: None,
+        }
+    }
+
+    pub fn hand_value(&self) -> u32 {
+        get_hand_value(self.hand.clone())
+    }
+}
+
+pub struct App {
+    pub me: Player,
+    /// Our hand while it's our turn.
+    turn: Option<Turn>,
+    pub client_id: String,
+    pub is_host: bool,
+    pub phase: Phase,
+    pub input_mode: InputMode,
+    pub input: String,
+    pub dealer_hand: Vec<Card>,
+    /// The dealer's hole card stays hidden until the round is settled.
+    pub dealer_hidden: bool,
+    pub seats: Vec<Seat>,
+    pub active_seat: Option<String>,
+    pub log: Vec<String>,
+    pub chat: Vec<String>,
+    pub connected: bool,
+    pub should_quit: bool,
+    settings: TurnSettings,
+    pub session: Session,
+    questions: Vec<Question>,
+    /// An action that goes against basic strategy and is waiting for the key to be pressed again.
+    unconfirmed_action: Option<PlayerAction>,
+    outgoing: Vec<BlackjackRequest>,
+}
+
+impl App {
+    pub fn new(
+        user_name: String,
+        client_id: String,
+        is_host: bool,
+        chips: u32,
+        settings: TurnSettings,
+        session: Session,
+    ) -> Self {
+        let mut app = App {
+            me: Player {
+                user_name: user_name.clone(),
+                player_type: PlayerType::Human,
+                hand: vec![],
+                hand_value: 0,
+                chips,
+                current_bet: 0,
+            },
+            turn: None,
+            client_id,
+            is_host,
+            phase: Phase::Lobby,
+            input_mode: InputMode::Normal,
+            input: String::new(),
+            dealer_hand: vec![],
+            dealer_hidden: true,
+            seats: vec![],
+            active_seat: None,
+            log: vec![],
+            chat: vec![],
+            connected: true,
+            should_quit: false,
+            settings,
+            session,
+            questions: vec![],
+            unconfirmed_action: None,
+            outgoing: vec![],
+        };
+
+        app.seat_mut(&user_name).chips = Some(chips);
+        app.push_log("Connected to the server.".to_owned());
+        if is_host {
+            app.push_log("You are the host. Press 'n' to deal the first round.".to_owned());
+            app.push_log(
+                "To seat a bot, press 'c' and send '/bot <strategy>' with basic, mimic-dealer, \
+                 never-bust, always-stand or counter."
+                    .to_owned(),
+            );
+        } else {
+            app.push_log("Waiting for the host to start the game...".to_owned());
+        }
+
+        app
+    }
+
+    /// Returns the requests queued since the last call so they can be sent to the server.
+    pub fn take_outgoing(&mut self) -> Vec<BlackjackRequest> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// A one line description of what the player can do right now.
+    pub fn prompt(&self) -> String {
+        match self.input_mode {
+            InputMode::Bet => format!(
+                "You have {} chips. Type your bet and press Enter: {}",
+                self.me.chips, self.input
+            ),
+            InputMode::Chat => format!("Say: {}", self.input),
+            InputMode::Quiz => match self.questions.first() {
+                Some(question) => format!("{} {}", question.prompt(), self.input),
+                None => String::new(),
+            },
+            InputMode::Normal => match self.phase {
+                Phase::Lobby if self.is_host => "Press 'n' to deal the first round.".to_owned(),
+                Phase::Lobby => "Waiting for the host to start the game...".to_owned(),
+                Phase::Waiting => match &self.active_seat {
+                    Some(name) => format!("It's {}'s turn.", name),
+                    None => "Waiting...".to_owned(),
+                },
+                Phase::Betting => "Press 'b' to place your bet.".to_owned(),
+                Phase::Drawing => "Drawing cards...".to_owned(),
+                Phase::Acting => {
+                    "Your turn: (h)it, (s)tand, (d)ouble or s(p)lit. Press '?' for a hint."
+                        .to_owned()
+                }
+                Phase::RoundOver if self.is_host => "Press 'n' to deal the next round.".to_owned(),
+                Phase::RoundOver => "Waiting for the host to deal the next round...".to_owned(),
+                Phase::GameOver => "The game has finished. Press 'q' to quit.".to_owned(),
+            },
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        match self.input_mode {
+            InputMode::Normal => self.handle_normal_key(key.code),
+            InputMode::Bet | InputMode::Chat | InputMode::Quiz => self.handle_text_key(key.code),
+        }
+    }
+
+    fn handle_normal_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+            KeyCode::Char('c') | KeyCode::Char('t') => self.input_mode = InputMode::Chat,
+            KeyCode::Char('n') if self.is_host && self.can_deal() => {
+                self.outgoing.push(BlackjackRequest {
+                    command: RequestCommand::Start,
+                });
+                self.phase = Phase::Waiting;
+            }
+            KeyCode::Char('b') if self.phase == Phase::Betting => {
+                self.input_mode = InputMode::Bet;
+            }
+            KeyCode::Char('h') if self.phase == Phase::Acting => {
+                self.take_action(PlayerAction::Hit)
+            }
+            KeyCode::Char('s') if self.phase == Phase::Acting => {
+                self.take_action(PlayerAction::Stand)
+            }
+            KeyCode::Char('d') if self.phase == Phase::Acting => {
+                self.take_action(PlayerAction::Double)
+            }
+            KeyCode::Char('p') if self.phase == Phase::Acting => {
+                self.push_log("Splitting isn't supported at this table.".to_owned());
+            }
+            KeyCode::Char('i') => {
+                for line in self.session.stats.summary() {
+                    self.push_log(line);
+                }
+            }
+            KeyCode::Char('?') if self.phase == Phase::Acting => {
+                if let Some(recommended) = self.recommended_action() {
+                    self.push_log(format!("Basic strategy says: {}", recommended));
+                    let values = self
+                        .session
+                        .action_values(&self.me.hand, &self.dealer_hand[0]);
+                    self.push_log(format!("Expected value: {}", values));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn recommended_action(&self) -> Option<StrategyAction> {
+        let dealer_card = self.dealer_hand.first()?;
+        Some(
+            self.settings
+                .strategy
+                .playable_action(&self.me.hand, dealer_card),
+        )
+    }
+
+    fn take_action(&mut self, action: PlayerAction) {
+        if self.settings.warn_on_deviation && self.unconfirmed_action.as_ref() != Some(&action) {
+            if let Some(recommended) = self.recommended_action() {
+                if !recommended.matches(&action) {
+                    self.push_log(format!(
+                        "Basic strategy says to {} here. Press the key again to confirm.",
+                        recommended
+                    ));
+                    self.unconfirmed_action = Some(action);
+                    return;
+                }
+            }
+        }
+        self.unconfirmed_action = None;
+
+        let Some(turn) = &mut self.turn else {
+            return;
+        };
+        if action == PlayerAction::Double && !turn.can_double() {
+            self.push_log("You don't have enough chips to double your bet!".to_owned());
+            return;
+        }
+        if let Some(dealer_card) = self.dealer_hand.first() {
+            self.session.stats.decision(
+                &self.settings.strategy,
+                &self.me.hand,
+                dealer_card,
+                action,
+            );
+        }
+
+        let Some(turn) = &mut self.turn else {
+            return;
+        };
+        let commands = turn.act(action);
+        self.phase = Phase::Drawing;
+        self.turn_commands(commands);
+    }
+
+    fn handle_text_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc => {
+                self.input.clear();
+                self.questions.clear();
+                self.input_mode = InputMode::Normal;
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter => {
+                let input = std::mem::take(&mut self.input);
+                match self.input_mode {
+                    InputMode::Bet => self.place_bet(input.trim()),
+                    InputMode::Chat => self.send_chat(input.trim()),
+                    InputMode::Quiz => self.answer_question(input.trim()),
+                    InputMode::Normal => {}
+                }
+            }
+            KeyCode::Char(c) if self.input_mode == InputMode::Chat || c.is_ascii_digit() => {
+                self.input.push(c);
+            }
+            KeyCode::Char(c @ ('-' | '.')) if self.input_mode == InputMode::Quiz => {
+                self.input.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// Gives the trainer a chance to quiz the player, as long as they aren't busy with their
+    /// own turn.
+    fn maybe_quiz(&mut self) {
+        let busy = matches!(self.phase, Phase::Betting | Phase::Drawing | Phase::Acting);
+        if busy || self.input_mode != InputMode::Normal {
+            return;
+        }
+
+        if let Some(trainer) = &mut self.session.trainer {
+            self.questions = trainer.quiz();
+            if !self.questions.is_empty() {
+                self.input_mode = InputMode::Quiz;
+            }
+        }
+    }
+
+    fn answer_question(&mut self, input: &str) {
+        if self.questions.is_empty() {
+            self.input_mode = InputMode::Normal;
+            return;
+        }
+
+        let question = self.questions.remove(0);
+        if let Some(trainer) = &mut self.session.trainer {
+            let feedback = trainer.answer(question, input);
+            self.push_log(feedback);
+        }
+
+        if self.questions.is_empty() {
+            self.input_mode = InputMode::Normal;
+        }
+    }
+
+    fn can_deal(&self) -> bool {
+        self.phase == Phase::Lobby || self.phase == Phase::RoundOver
+    }
+
+    fn place_bet(&mut self, input: &str) {
+        let amount = match input.parse::<u32>() {
+            Ok(amount) => amount,
+            Err(_) => {
+                self.push_log("Please enter a vaild number.".to_owned());
+                return;
+            }
+        };
+
+        if let Err(e) = self.settings.rules.check_bet(amount, self.me.chips) {
+            self.push_log(e);
+            return;
+        }
+
+        let mut turn = Turn::new(self.me.clone());
+        let commands = turn.bet(amount);
+        self.turn = Some(turn);
+        self.input_mode = InputMode::Normal;
+        self.phase = Phase::Drawing;
+        self.turn_commands(commands);
+    }
+
+    fn send_chat(&mut self, message: &str) {
+        self.input_mode = InputMode::Normal;
+        if message.is_empty() {
+            return;
+        }
+
+        // The host seats bots from the lobby with `/bot <strategy>`.
+        let command = match message.strip_prefix("/bot ") {
+            Some(strategy) => RequestCommand::AddBot(strategy.trim().to_owned()),
+            None => RequestCommand::Chat(message.to_owned()),
+        };
+        self.outgoing.push(BlackjackRequest { command });
+    }
+
+    /// Queues the requests worked out by the turn and keeps our seat in step with it.
+    fn turn_commands(&mut self, commands: Vec<RequestCommand>) {
+        self.outgoing.extend(
+            commands
+                .into_iter()
+                .map(|command| BlackjackRequest { command }),
+        );
+
+        let Some(turn) = &self.turn else {
+            return;
+        };
+        self.me = turn.player().clone();
+        if turn.state() == TurnState::Finished {
+            self.turn = None;
+            self.phase = Phase::Waiting;
+            self.push_log("Your turn has ended.".to_owned());
+        }
+    }
+
+    pub fn handle_disconnect(&mut self) {
+        self.connected = false;
+        self.push_log("The server closed the connection. Press 'q' to quit.".to_owned());
+    }
+
+    pub fn handle_message(&mut self, text: &str) {
+        let request: PublishRequest = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(e) => {
+                self.push_log(format!("Couldn't read a message from the server: {}", e));
+                return;
+            }
+        };
+
+        match request.trigger {
+            PublishTrigger::StartTurn {
+                active_client_id,
+                user_name,
+                dealer_card,
+            } => self.start_turn(active_client_id, user_name, dealer_card),
+            PublishTrigger::CardsDrawn { cards } => self.cards_drawn(cards),
+            trigger @ (PublishTrigger::ShoeShuffled { .. }
+            | PublishTrigger::ShoeCommitted { .. }
+            | PublishTrigger::ShoeRevealed { .. }) => {
+                for message in self.session.shoe_event(trigger) {
+                    self.push_log(message);
+                }
+            }
+            PublishTrigger::BetPlaced { user_name, amount } => {
+                self.seat_mut(&user_name).bet = amount;
+                self.push_log(format!("{} bet {} chips.", user_name, amount));
+            }
+            PublishTrigger::ChatMessage { user_name, message } => {
+                self.chat.push(format!("{}: {}", user_name, message));
+                trim_lines(&mut self.chat);
+            }
+            PublishTrigger::Announcement { message } => {
+                self.push_log(format!("Announcement: {}", message));
+            }
+            PublishTrigger::ChipsAdjusted {
+                user_name,
+                chips,
+                change,
+                reason,
+            } => {
+                if user_name.to_lowercase() == self.me.user_name.to_lowercase() {
+                    self.me.chips = chips;
+                }
+                self.seat_mut(&user_name).chips = Some(chips);
+                self.push_log(chips_adjusted_line(&user_name, chips, change, &reason));
+            }
+            PublishTrigger::ServerShuttingDown { finish_round } => {
+                self.push_log(shutting_down_line(finish_round));
+            }
+            PublishTrigger::ConnectionChanged {
+                user_name,
+                quality,
+                latency_ms,
+            } => {
+                let seat = self.seat_mut(&user_name);
+                seat.connection = quality;
+                seat.latency_ms = latency_ms;
+                self.push_log(connection_line(&user_name, quality, latency_ms));
+            }
+            PublishTrigger::TableSnapshot {
+                round,
+                dealer_card,
+                active_player,
+                players,
+            } => self.table_snapshot(round, dealer_card, active_player, players),
+            PublishTrigger::RoundFinished(results) => self.round_finished(results),
+            PublishTrigger::TournamentStandings {
+                hand,
+                hands,
+                standings,
+                eliminated,
+            } => {
+                for line in standings_lines(hand, hands, &standings, &eliminated) {
+                    self.push_log(line);
+                }
+            }
+            PublishTrigger::TournamentFinished { winner, standings } => {
+                for line in winner_lines(&winner, &standings) {
+                    self.push_log(line);
+                }
+            }
+            PublishTrigger::GameFinished => {
+                self.phase = Phase::GameOver;
+                self.push_log("The game has finished.".to_owned());
+                for line in self.session.stats.summary() {
+                    self.push_log(line);
+                }
+                if let Some(trainer) = &self.session.trainer {
+                    self.push_log(trainer.summary());
+                }
+            }
+        }
+    }
+
+    fn start_turn(
+        &mut self,
+        active_client_id: String,
+        user_name: String,
+        dealer_card: Option<Card>,
+    ) {
+        // The first turn after a round has been settled is the start of a new round.
+        if self.active_seat.is_none() {
+            self.dealer_hand = dealer_card.into_iter().collect();
+            if let Some(card) = self.dealer_hand.first() {
+                self.session.round_started(card);
+            }
+            self.dealer_hidden = true;
+            self.seats.iter_mut().for_each(|s| {
+                s.hand = vec![];
+                s.bet = 0;
+                s.result = None;
+            });
+            self.push_log("A new round has been dealt.".to_owned());
+        }
+
+        self.seat_mut(&user_name);
+        self.active_seat = Some(user_name.clone());
+
+        if active_client_id.to_lowercase() == self.client_id.to_lowercase() {
+            self.phase = Phase::Betting;
+            self.input_mode = InputMode::Bet;
+            self.push_log("It's your turn! Place your bet.".to_owned());
+        } else {
+            self.phase = Phase::Waiting;
+            self.push_log(format!("It's {}'s turn.", user_name));
+        }
+    }
+
+    fn cards_drawn(&mut self, cards: Vec<Card>) {
+        let Some(active) = self.active_seat.clone() else {
+            return;
+        };
+
+        let drawn = cards
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        self.push_log(format!("{} drew {}", active, drawn));
+        self.seat_mut(&active).hand.extend(cards.clone());
+        self.session.cards_seen(&cards);
+
+        if let (Phase::Drawing, Some(turn)) = (&self.phase, &mut self.turn) {
+            let commands = turn.cards_drawn(&cards);
+            if turn.is_bust() {
+                self.push_log("You busted!".to_owned());
+            }
+            self.phase = Phase::Acting;
+            self.turn_commands(commands);
+        } else {
+            self.maybe_quiz();
+        }
+    }
+
+    /// Catches up with the table after the connection fell too far behind. The seats are
+    /// replaced by the snapshot's, keeping what's known about each player's connection.
+    fn table_snapshot(
+        &mut self,
+        round: Option<u64>,
+        dealer_card: Option<Card>,
+        active_player: Option<String>,
+        players: Vec<Player>,
+    ) {
+        for line in snapshot_lines(round, active_player.as_deref(), &players) {
+            self.push_log(line);
+        }
+
+        self.seats.retain(|s| {
+            players
+                .iter()
+                .any(|p| p.user_name.to_lowercase() == s.user_name.to_lowercase())
+        });
+        for player in &players {
+            let seat = self.seat_mut(&player.user_name);
+            seat.hand = player.hand.clone();
+            seat.chips = Some(player.chips);
+            seat.bet = player.current_bet;
+            seat.result = None;
+        }
+        self.dealer_hand = dealer_card.into_iter().collect();
+        self.dealer_hidden = true;
+        self.active_seat = active_player.clone();
+
+        let me = players
+            .iter()
+            .find(|p| p.user_name.to_lowercase() == self.me.user_name.to_lowercase());
+        if let Some(me) = me {
+            self.me.chips = me.chips;
+        }
+        let my_turn = active_player
+            .is_some_and(|name| name.to_lowercase() == self.me.user_name.to_lowercase());
+
+        if round.is_none() {
+            if !matches!(self.phase, Phase::Lobby | Phase::GameOver) {
+                self.turn = None;
+                self.phase = Phase::RoundOver;
+            }
+        } else if !my_turn {
+            self.turn = None;
+            self.phase = Phase::Waiting;
+        } else if self.turn.is_none() && me.is_some_and(|p| p.hand.is_empty()) {
+            // The start of our turn was among the messages that were dropped.
+            self.phase = Phase::Betting;
+            self.input_mode = InputMode::Bet;
+            self.push_log("It's your turn! Place your bet.".to_owned());
+        }
+    }
+
+    fn round_finished(&mut self, results: Vec<TurnResult>) {
+        self.push_log("The round has finished.".to_owned());
+        self.active_seat = None;
+        self.dealer_hidden = false;
+
+        for result in results {
+            if result.player.player_type == PlayerType::Dealer {
+                self.dealer_hand = result.player.hand.clone();
+                self.push_log(format!(
+                    "The dealer's hand value is {}.",
+                    result.player.hand_value
+                ));
+                for message in self.session.dealer_revealed(&result.player.hand) {
+                    self.push_log(message);
+                }
+                continue;
+            }
+
+            let is_me = result.player.user_name.to_lowercase() == self.me.user_name.to_lowercase();
+            let name = if is_me {
+                "You".to_owned()
+            } else {
+                result.player.user_name.clone()
+            };
+
+            self.push_log(match result.end_state {
+                EndState::Win => format!("{} won {} chips.", name, result.player.current_bet),
+                EndState::Loss => format!("{} lost {} chips.", name, result.player.current_bet),
+                EndState::Blackjack => format!(
+                    "{} got a blackjack! {} chips paid out.",
+                    name,
+                    result.player.current_bet * 3
+                ),
+                EndState::Push => format!("{} and the dealer drew.", name),
+            });
+
+            let seat = self.seat_mut(&result.player.user_name);
+            seat.chips = Some(result.player.chips);
+            seat.bet = result.player.current_bet;
+            seat.result = Some(result.end_state.clone());
+
+            if is_me {
+                self.session
+                    .stats
+                    .hand_finished(&result.player, &result.end_state);
+                self.me.chips = result.player.chips;
+                self.me.current_bet = 0;
+            }
+        }
+
+        if self.phase != Phase::GameOver {
+            self.phase = Phase::RoundOver;
+        }
+        self.maybe_quiz();
+    }
+
+    fn seat_mut(&mut self, user_name: &str) -> &mut Seat {
+        let index = match self
+            .seats
+            .iter()
+            .position(|s| s.user_name.to_lowercase() == user_name.to_lowercase())
+        {
+            Some(index) => index,
+            None => {
+                self.seats.push(Seat::new(user_name));
+                self.seats.len() - 1
+            }
+        };
+
+        &mut self.seats[index]
+    }
+
+    pub fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        trim_lines(&mut self.log);
+    }
+}
+
+fn trim_lines(lines: &mut Vec<String>) {
+    if lines.len() > MAX_LINES {
+        lines.drain(..lines.len() - MAX_LINES);
+    }
+}
@@ -0,0 +1,109 @@
+mod app;
+mod ui;
+
+use std::{io::ErrorKind, time::Duration};
+
+use blackjack_shared::web_socket::RegisterResponse;
+use color_eyre::eyre::Result;
+use crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use tungstenite::Message;
+
+use crate::{
+    game::{Session, TurnSettings},
+    web_socket::{send_request, set_read_timeout},
+    Socket,
+};
+use app::App;
+
+/// How long to wait for a key press before checking the socket for new messages.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs the full-screen table view until the player quits or the game finishes.
+pub fn run(
+    socket: &mut Socket,
+    res: &RegisterResponse,
+    my_user_name: String,
+    settings: TurnSettings,
+    session: Session,
+) -> Result<()> {
+    // The terminal and the socket are polled on the same thread so reads must not block for long.
+    set_read_timeout(socket, POLL_INTERVAL)?;
+
+    let mut app = App::new(
+        my_user_name,
+        res.id.clone(),
+        res.is_host,
+        res.chips,
+        settings,
+        session,
+    );
+    if let Some(tournament) = &res.tournament {
+        app.push_log(format!(
+            "This table is running a tournament of {} hands. Everyone starts with {} chips.",
+            tournament.hands, tournament.starting_chips
+        ));
+    }
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, socket, &mut app);
+    ratatui::restore();
+
+    app.session
+        .stats
+        .summary()
+        .iter()
+        .for_each(|l| println!("{}", l));
+    if let Some(trainer) = &app.session.trainer {
+        println!("{}", trainer.summary());
+    }
+    if let Some(message) = app.session.export_stats() {
+        println!("{}", message);
+    }
+
+    result
+}
+
+fn run_app(terminal: &mut DefaultTerminal, socket: &mut Socket, app: &mut App) -> Result<()> {
+    while !app.should_quit {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key);
+                }
+            }
+        }
+
+        if app.connected {
+            read_messages(socket, app)?;
+
+            for request in app.take_outgoing() {
+                send_request(request, socket);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Hands every message waiting on the socket to the app without blocking for new ones.
+fn read_messages(socket: &mut Socket, app: &mut App) -> Result<()> {
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => app.handle_message(&text),
+            Ok(Message::Close(_))
+            | Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                app.handle_disconnect();
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+            {
+                return Ok(())
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
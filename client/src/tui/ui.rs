@@ -0,0 +1,214 @@
+use blackjack_shared::{
+    card::{Card, Suit},
+    player::get_hand_value,
+    web_socket::{ConnectionQuality, EndState},
+};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    Frame,
+};
+
+use super::app::{App, InputMode, Seat};
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, chat, status, help] = Layout::vertical([
+        Constraint::Min(10),
+        Constraint::Length(8),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let [table, log] =
+        Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(main);
+
+    draw_table(frame, app, table);
+    draw_lines(frame, " Events ", &app.log, log);
+    draw_lines(frame, " Chat ", &app.chat, chat);
+    draw_status(frame, app, status);
+
+    frame.render_widget(
+        Paragraph::new(help_text(app)).style(Style::default().fg(Color::DarkGray)),
+        help,
+    );
+}
+
+fn draw_table(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Blackjack ")
+        .border_style(Style::default().fg(Color::Green));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let [dealer, seats] =
+        Layout::vertical([Constraint::Length(5), Constraint::Min(5)]).areas(inner);
+
+    let dealer_value = if app.dealer_hidden || app.dealer_hand.is_empty() {
+        String::new()
+    } else {
+        format!("Value: {}", get_hand_value(app.dealer_hand.clone()))
+    };
+    let mut dealer_cards = cards_line(&app.dealer_hand);
+    if app.dealer_hidden && !app.dealer_hand.is_empty() {
+        dealer_cards
+            .spans
+            .push(Span::styled("[??]", Style::default().fg(Color::DarkGray)));
+    }
+
+    frame.render_widget(
+        Paragraph::new(vec![dealer_cards, Line::from(dealer_value)])
+            .block(Block::default().borders(Borders::ALL).title(" Dealer "))
+            .centered(),
+        dealer,
+    );
+
+    if app.seats.is_empty() {
+        return;
+    }
+
+    let seat_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![
+            Constraint::Ratio(1, app.seats.len() as u32);
+            app.seats.len()
+        ])
+        .split(seats);
+
+    for (seat, area) in app.seats.iter().zip(seat_areas.iter()) {
+        draw_seat(frame, app, seat, *area);
+    }
+}
+
+fn draw_seat(frame: &mut Frame, app: &App, seat: &Seat, area: Rect) {
+    let is_me = seat.user_name.to_lowercase() == app.me.user_name.to_lowercase();
+    let is_active = app
+        .active_seat
+        .as_ref()
+        .is_some_and(|a| a.to_lowercase() == seat.user_name.to_lowercase());
+
+    let title = if is_me {
+        format!(" {} (you) ", seat.user_name)
+    } else {
+        format!(" {} ", seat.user_name)
+    };
+    let border_style = if is_active {
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    };
+
+    let chips = match seat.chips {
+        Some(chips) => chips.to_string(),
+        None => "?".to_owned(),
+    };
+    let mut lines = vec![
+        cards_line(&seat.hand),
+        Line::from(if seat.hand.is_empty() {
+            String::new()
+        } else {
+            format!("Value: {}", seat.hand_value())
+        }),
+        Line::from(format!("Bet: {}", seat.bet)),
+        Line::from(format!("Chips: {}", chips)),
+    ];
+
+    if let Some(result) = &seat.result {
+        let (text, color) = match result {
+            EndState::Win => ("Win", Color::Green),
+            EndState::Blackjack => ("Blackjack!", Color::Green),
+            EndState::Loss => ("Loss", Color::Red),
+            EndState::Push => ("Push", Color::Yellow),
+        };
+        lines.push(Line::styled(text, Style::default().fg(color)));
+    }
+
+    let connection = match (seat.connection, seat.latency_ms) {
+        (ConnectionQuality::Good, _) => None,
+        (ConnectionQuality::Slow, Some(latency)) => {
+            Some((format!("Slow ({} ms)", latency), Color::Yellow))
+        }
+        (ConnectionQuality::Slow, None) => Some(("Slow".to_owned(), Color::Yellow)),
+        (ConnectionQuality::Unresponsive, _) => Some(("Not responding".to_owned(), Color::Red)),
+        (ConnectionQuality::Disconnected, _) => Some(("Disconnected".to_owned(), Color::DarkGray)),
+    };
+    if let Some((text, color)) = connection {
+        lines.push(Line::styled(text, Style::default().fg(color)));
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .border_style(border_style),
+            )
+            .centered(),
+        area,
+    );
+}
+
+fn draw_lines(frame: &mut Frame, title: &str, lines: &[String], area: Rect) {
+    // Only show the most recent lines that fit in the pane.
+    let height = area.height.saturating_sub(2) as usize;
+    let items: Vec<ListItem> = lines
+        .iter()
+        .skip(lines.len().saturating_sub(height))
+        .map(|l| ListItem::new(l.as_str()))
+        .collect();
+
+    frame.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let style = match app.input_mode {
+        InputMode::Normal => Style::default(),
+        InputMode::Bet | InputMode::Chat | InputMode::Quiz => Style::default().fg(Color::Yellow),
+    };
+    let title = if app.connected {
+        " Status "
+    } else {
+        " Status (disconnected) "
+    };
+
+    frame.render_widget(
+        Paragraph::new(app.prompt())
+            .style(style)
+            .wrap(Wrap { trim: true })
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+fn help_text(app: &App) -> &'static str {
+    match app.input_mode {
+        InputMode::Normal => {
+            " h: hit  s: stand  d: double  ?: hint  i: stats  b: bet  n: deal  c: chat  q: quit"
+        }
+        InputMode::Bet | InputMode::Chat | InputMode::Quiz => " Enter: send  Esc: cancel",
+    }
+}
+
+fn cards_line(cards: &[Card]) -> Line<'static> {
+    Line::from(
+        cards
+            .iter()
+            .map(|c| {
+                let color = match c.suit {
+                    Suit::Hearts | Suit::Diamonds => Color::Red,
+                    Suit::Spades | Suit::Clubs => Color::White,
+                };
+                Span::styled(format!("[{}] ", c), Style::default().fg(color))
+            })
+            .collect::<Vec<Span>>(),
+    )
+}
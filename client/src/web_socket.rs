@@ -1,44 +1,109 @@
-use std::{io::Read, io::Write};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::mpsc,
+    time::Duration,
+};
 
+use blackjack_client_core::{Error, TableCommands, TableSession, TlsOptions};
 use blackjack_shared::web_socket::*;
-use tungstenite::{Message, WebSocket};
+use color_eyre::eyre::{eyre, Result};
+use futures_util::StreamExt;
+use tungstenite::{client_tls_with_config, stream::MaybeTlsStream, Connector, Message, WebSocket};
+use url::Url;
 
-pub fn send_request<S>(request: BlackjackRequest, socket: &mut WebSocket<S>)
-where
-    S: Read + Write,
-{
-    let json = serde_json::to_string(&request).unwrap();
-    socket.send(Message::Text(json)).unwrap()
+use crate::Socket;
+
+/// Opens the web socket handed out at registration, over TLS for `wss` urls.
+pub fn connect(url: &str, tls: &TlsOptions) -> Result<Socket> {
+    let url = Url::parse(url)?;
+    let stream = TcpStream::connect(&*url.socket_addrs(|| None)?)?;
+    let connector = Connector::NativeTls(tls.connector()?);
+    let (socket, _) = client_tls_with_config(url.as_str(), stream, None, Some(connector))
+        .map_err(|e| eyre!("Couldn't connect to {}: {}", url, e))?;
+    Ok(socket)
 }
 
-pub fn send_request_and_wait_for_response<S>(
-    request: BlackjackRequest,
-    socket: &mut WebSocket<S>,
-) -> String
-where
-    S: Read + Write,
-{
-    send_request(request, socket);
+/// Stops reads on the socket blocking for longer than the timeout.
+pub fn set_read_timeout(socket: &mut Socket, timeout: Duration) -> io::Result<()> {
+    match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(timeout)),
+        MaybeTlsStream::NativeTls(stream) => stream.get_mut().set_read_timeout(Some(timeout)),
+        _ => Ok(()),
+    }
+}
 
-    let msg = wait_for_message(socket);
+/// The line client's seat at the table. A task keeps reading the session's events so the
+/// server's pings are answered while the player is sitting at a prompt, and hands the triggers
+/// over in order.
+pub struct LineSocket {
+    commands: TableCommands,
+    triggers: mpsc::Receiver<PublishTrigger>,
+    /// Triggers put aside during our turn, to be handled once it's over.
+    deferred: VecDeque<PublishTrigger>,
+}
+
+impl LineSocket {
+    pub fn new(session: TableSession) -> Self {
+        let (_, commands, mut events) = session.split();
+        let (sender, triggers) = mpsc::channel();
 
-    match msg {
-        msg @ Message::Text(_) => msg.into_text().unwrap(),
-        Message::Close(_) => "CLOSE".to_string(),
-        _ => String::new(),
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                match event {
+                    Ok(trigger) => {
+                        if sender.send(trigger).is_err() {
+                            return;
+                        }
+                    }
+                    Err(Error::Json(_)) => {}
+                    Err(e) => {
+                        println!("Error reading message: {}", e);
+                        return;
+                    }
+                }
+            }
+        });
+
+        LineSocket {
+            commands,
+            triggers,
+            deferred: VecDeque::new(),
+        }
+    }
+
+    /// Sends a command. One that can't be sent because the connection has gone is dropped, as
+    /// the close is picked up by the next wait for a trigger.
+    pub fn send(&self, command: RequestCommand) {
+        let _ = self.commands.send(command);
+    }
+
+    pub fn send_commands(&self, commands: Vec<RequestCommand>) {
+        let _ = self.commands.send_all(commands);
+    }
+
+    /// Takes the next trigger put aside, or waits for the next one from the server. Returns
+    /// `None` once the server has closed the connection.
+    pub fn wait_for_trigger(&mut self) -> Option<PublishTrigger> {
+        self.deferred.pop_front().or_else(|| self.receive())
+    }
+
+    /// Waits for the next trigger from the server, skipping any put aside.
+    pub fn receive(&self) -> Option<PublishTrigger> {
+        self.triggers.recv().ok()
+    }
+
+    /// Puts a trigger aside until the next call to [`wait_for_trigger`](Self::wait_for_trigger).
+    pub fn defer(&mut self, trigger: PublishTrigger) {
+        self.deferred.push_back(trigger);
     }
 }
 
-pub fn wait_for_message<S>(socket: &mut WebSocket<S>) -> Message
+pub fn send_request<S>(request: BlackjackRequest, socket: &mut WebSocket<S>)
 where
     S: Read + Write,
 {
-    let mut msg: Message;
-    loop {
-        msg = socket.read().expect("Error reading message");
-        if msg.is_text() {
-            break;
-        }
-    }
-    msg
+    let json = serde_json::to_string(&request).unwrap();
+    socket.send(Message::Text(json)).unwrap()
 }
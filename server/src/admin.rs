@@ -0,0 +1,801 @@
+use std::{fs::OpenOptions, io::Write, net::SocketAddr, path::PathBuf};
+
+use blackjack_shared::{
+    admin::*,
+    web_socket::{PublishRequest, PublishTrigger},
+};
+use clap::ValueEnum;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use tracing::{error, info, warn};
+use warp::{
+    http::StatusCode,
+    reject::Rejection,
+    reply::{json, with_status, Reply},
+};
+
+use crate::{
+    client::Client,
+    game::void_round,
+    handlers::{publish, publish_results, publish_shoe_events, send_to, stand_for_leaver},
+    history::{now, HandHistory},
+    log::{self, LogLevel},
+    tournament::Tournament,
+    Audit, Clients, Config, Dealer, History, SharedAdmin, SharedShoe, SharedTournament,
+};
+
+/// What the operators have done to the table.
+#[derive(Debug, Default)]
+pub struct AdminState {
+    /// Closed tables turn everyone away.
+    pub closed: bool,
+    /// A draining table finishes the round being played and then ends the game, so the server
+    /// can be restarted.
+    pub draining: bool,
+    /// Set once the server has been told to stop. Nobody can join and no more rounds are dealt.
+    pub shutting_down: bool,
+    bans: Vec<Ban>,
+}
+
+impl AdminState {
+    /// Starts with the bans saved when the server last stopped.
+    pub fn with_bans(bans: Vec<Ban>) -> Self {
+        AdminState {
+            bans,
+            ..AdminState::default()
+        }
+    }
+
+    pub fn is_banned(&self, user_name: &str) -> bool {
+        self.bans
+            .iter()
+            .any(|b| b.user_name.eq_ignore_ascii_case(user_name))
+    }
+
+    pub fn bans(&self) -> &[Ban] {
+        &self.bans
+    }
+
+    /// Returns false if the name was already banned.
+    fn ban(&mut self, user_name: &str, reason: Option<&str>) -> bool {
+        if self.is_banned(user_name) {
+            return false;
+        }
+        self.bans.push(Ban {
+            user_name: user_name.to_owned(),
+            reason: reason.map(str::to_owned),
+            at: now(),
+        });
+        true
+    }
+
+    /// Returns false if the name wasn't banned.
+    fn unban(&mut self, user_name: &str) -> bool {
+        let before = self.bans.len();
+        self.bans
+            .retain(|b| !b.user_name.eq_ignore_ascii_case(user_name));
+        self.bans.len() != before
+    }
+}
+
+/// Who sent an admin request.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub authorization: Option<String>,
+    pub remote: Option<SocketAddr>,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    at: u64,
+    remote: Option<SocketAddr>,
+    action: &'a str,
+    /// The table, seat or name acted on.
+    target: Option<&'a str>,
+    /// `ok`, or why the request was refused.
+    outcome: &'a str,
+    detail: Option<&'a str>,
+}
+
+/// Appends a line of JSON to the audit log for every admin request.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf) -> Self {
+        AuditLog { path }
+    }
+
+    fn record(&mut self, entry: &AuditEntry) {
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(entry).unwrap()));
+
+        if let Err(e) = result {
+            error!(
+                error = %e,
+                path = %self.path.display(),
+                "Couldn't write to the audit log"
+            );
+        }
+    }
+}
+
+async fn audit_record(
+    audit: &Audit,
+    caller: &Caller,
+    action: &str,
+    target: Option<&str>,
+    outcome: &str,
+    detail: Option<&str>,
+) {
+    audit.lock().await.record(&AuditEntry {
+        at: now(),
+        remote: caller.remote,
+        action,
+        target,
+        outcome,
+        detail,
+    });
+}
+
+/// Checks the request carries the admin token, logging the attempt if it doesn't. The admin API
+/// is hidden entirely when no token is configured.
+async fn authorize(
+    action: &str,
+    caller: &Caller,
+    config: &Config,
+    audit: &Audit,
+) -> Result<(), StatusCode> {
+    let Some(token) = &config.admin.token else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let presented = caller
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if presented.is_some_and(|presented| tokens_match(presented, token)) {
+        return Ok(());
+    }
+
+    warn!(action, remote = ?caller.remote, "Refused an admin request");
+    audit_record(audit, caller, action, None, "unauthorized", None).await;
+    Err(StatusCode::UNAUTHORIZED)
+}
+
+/// Compares the tokens without stopping at the first difference, so the time taken doesn't give
+/// away how much of a guess was right.
+fn tokens_match(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Checks the request is for this server's table.
+async fn find_table(
+    table_id: &str,
+    action: &str,
+    caller: &Caller,
+    history: &History,
+    audit: &Audit,
+) -> Result<(), StatusCode> {
+    if history.lock().await.table_id() == table_id {
+        return Ok(());
+    }
+    audit_record(audit, caller, action, Some(table_id), "not_found", None).await;
+    Err(StatusCode::NOT_FOUND)
+}
+
+fn seat_phase(
+    client: &Client,
+    history: &HandHistory,
+    tournament: Option<&Tournament>,
+) -> SeatPhase {
+    let name = client.player.user_name.as_str();
+    if tournament.is_some_and(|t| t.is_eliminated(&client.id)) {
+        SeatPhase::SittingOut
+    } else if client.sender.is_none() {
+        SeatPhase::Disconnected
+    } else if history.active_player() == Some(name) {
+        SeatPhase::Acting
+    } else if history.has_stood(name) {
+        SeatPhase::Done
+    } else {
+        SeatPhase::Waiting
+    }
+}
+
+fn seat_summary(
+    client: &Client,
+    history: &HandHistory,
+    tournament: Option<&Tournament>,
+) -> SeatSummary {
+    SeatSummary {
+        id: client.id.clone(),
+        user_name: client.player.user_name.clone(),
+        position: client.position,
+        bot: client.bot.is_some(),
+        phase: seat_phase(client, history, tournament),
+        chips: client.player.chips,
+        bet: client.player.current_bet,
+    }
+}
+
+async fn table_summary(
+    clients: &Clients,
+    history: &History,
+    tournament: &SharedTournament,
+    admin: &SharedAdmin,
+) -> TableSummary {
+    let (closed, draining) = {
+        let admin = admin.lock().await;
+        (admin.closed, admin.draining || admin.shutting_down)
+    };
+
+    let clients_lock = clients.lock().await;
+    let history_lock = history.lock().await;
+    let tournament_lock = tournament.lock().await;
+
+    let phase = if closed {
+        TablePhase::Closed
+    } else if draining && !history_lock.in_round() {
+        TablePhase::Drained
+    } else if draining {
+        TablePhase::Draining
+    } else if history_lock.in_round() {
+        TablePhase::Playing
+    } else if history_lock.rounds() == 0 {
+        TablePhase::Waiting
+    } else {
+        TablePhase::BetweenRounds
+    };
+
+    let mut seats: Vec<SeatSummary> = clients_lock
+        .iter()
+        .map(|c| seat_summary(c, &history_lock, tournament_lock.as_ref()))
+        .collect();
+    seats.sort_by_key(|s| s.position);
+
+    TableSummary {
+        table_id: history_lock.table_id().to_owned(),
+        phase,
+        rounds: history_lock.rounds(),
+        seats,
+    }
+}
+
+/// Sends an announcement to everyone at the table.
+pub async fn announcement_handler(
+    caller: Caller,
+    body: AnnouncementRequest,
+    clients: Clients,
+    config: Config,
+    audit: Audit,
+) -> Result<impl Reply, Rejection> {
+    let action = "announcement";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let message = body.message.trim();
+    if message.is_empty() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    info!("Announcing: {}", message);
+    audit_record(&audit, &caller, action, None, "ok", Some(message)).await;
+    announce(message, &clients).await;
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn announce(message: &str, clients: &Clients) {
+    let pub_req = PublishRequest {
+        trigger: PublishTrigger::Announcement {
+            message: message.to_owned(),
+        },
+    };
+    let _ = publish(pub_req, clients.clone(), None).await;
+}
+
+/// Ends the round being played, returning every bet. Replies with a conflict between rounds.
+#[allow(clippy::too_many_arguments)]
+pub async fn end_round_handler(
+    caller: Caller,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+) -> Result<impl Reply, Rejection> {
+    let action = "end_round";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let round = history.lock().await.rounds();
+    let Some((pub_reqs, continue_playing)) =
+        void_round(&clients, &dealer, &history, &tournament).await
+    else {
+        audit_record(&audit, &caller, action, None, "no_round", None).await;
+        return Ok(StatusCode::CONFLICT.into_response());
+    };
+
+    let detail = format!("round {}", round);
+    info!("Ended {} early, every bet has been returned", detail);
+    audit_record(&audit, &caller, action, None, "ok", Some(&detail)).await;
+
+    announce(
+        "The round has been called off and every bet returned.",
+        &clients,
+    )
+    .await;
+    publish_shoe_events(&shoe, &history, &clients).await;
+    publish_results(pub_reqs, continue_playing, &shoe, &history, &clients).await;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Lists the tables on this server and their seats.
+pub async fn tables_handler(
+    caller: Caller,
+    clients: Clients,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "list_tables";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    audit_record(&audit, &caller, action, None, "ok", None).await;
+    let summary = table_summary(&clients, &history, &tournament, &admin).await;
+    Ok(json(&vec![summary]).into_response())
+}
+
+/// Everything about a table, hole card and all.
+#[allow(clippy::too_many_arguments)]
+pub async fn table_handler(
+    table_id: String,
+    caller: Caller,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "inspect_table";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+    if let Err(status) = find_table(&table_id, action, &caller, &history, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    audit_record(&audit, &caller, action, Some(&table_id), "ok", None).await;
+    let summary = table_summary(&clients, &history, &tournament, &admin).await;
+
+    let mut players: Vec<Client> = clients.lock().await.clone();
+    players.sort_by_key(|c| c.position);
+    let shoe = {
+        let shoe = shoe.lock().await;
+        ShoeStatus {
+            shoe_id: shoe.id(),
+            decks: shoe.decks(),
+            dealt: shoe.dealt(),
+            needs_shuffle: shoe.needs_shuffle(),
+        }
+    };
+
+    let detail = TableDetail {
+        summary,
+        rules: config.rules.clone(),
+        tournament: tournament.lock().await.as_ref().map(|t| t.config().clone()),
+        players: players.into_iter().map(|c| c.player).collect(),
+        dealer: dealer.lock().await.clone(),
+        shoe,
+        round: history.lock().await.current().cloned(),
+        bans: admin.lock().await.bans().to_vec(),
+    };
+    Ok(json(&detail).into_response())
+}
+
+/// Removes a player from the table, and bans their name if asked to. If it was their turn, their
+/// hand stands so the round can carry on.
+#[allow(clippy::too_many_arguments)]
+pub async fn kick_handler(
+    table_id: String,
+    seat_id: String,
+    caller: Caller,
+    body: KickRequest,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "kick";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+    if let Err(status) = find_table(&table_id, action, &caller, &history, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let seat = clients
+        .lock()
+        .await
+        .iter()
+        .find(|c| c.id == seat_id)
+        .cloned();
+    let Some(seat) = seat else {
+        audit_record(&audit, &caller, action, Some(&seat_id), "not_found", None).await;
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let name = seat.player.user_name.clone();
+    let reason = body
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    let banned = body.ban && admin.lock().await.ban(&name, reason);
+    let detail = match (reason, banned) {
+        (Some(reason), true) => format!("banned: {}", reason),
+        (Some(reason), false) => reason.to_owned(),
+        (None, true) => "banned".to_owned(),
+        (None, false) => String::new(),
+    };
+    info!("Removing {} from the table", name);
+    audit_record(
+        &audit,
+        &caller,
+        action,
+        Some(&name),
+        "ok",
+        Some(&detail).filter(|d| !d.is_empty()).map(|d| d.as_str()),
+    )
+    .await;
+
+    // Let the player know why before closing their connection.
+    let message = match reason {
+        Some(reason) => format!("You have been removed from the table: {}", reason),
+        None => "You have been removed from the table.".to_owned(),
+    };
+    let pub_req = PublishRequest {
+        trigger: PublishTrigger::Announcement { message },
+    };
+    send_to(pub_req, &clients, &seat.id).await;
+    if let Some(sender) = &seat.sender {
+        sender.close();
+    }
+    clients.lock().await.retain(|c| c.id != seat.id);
+    announce(
+        &format!("{} has been removed from the table.", name),
+        &clients,
+    )
+    .await;
+
+    stand_for_leaver(
+        seat,
+        &clients,
+        &dealer,
+        &shoe,
+        &config,
+        &history,
+        &tournament,
+    )
+    .await;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Adds to or takes from a player's chips between rounds.
+#[allow(clippy::too_many_arguments)]
+pub async fn chips_handler(
+    table_id: String,
+    seat_id: String,
+    caller: Caller,
+    body: ChipsAdjustment,
+    clients: Clients,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+) -> Result<impl Reply, Rejection> {
+    let action = "adjust_chips";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+    if let Err(status) = find_table(&table_id, action, &caller, &history, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        audit_record(&audit, &caller, action, Some(&seat_id), "no_reason", None).await;
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let mut clients_lock = clients.lock().await;
+    let history_lock = history.lock().await;
+    // Bets are settled against the chips, so they can't change under a round being played.
+    if history_lock.in_round() {
+        audit_record(
+            &audit,
+            &caller,
+            action,
+            Some(&seat_id),
+            "in_round",
+            Some(reason),
+        )
+        .await;
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+    let Some(seat) = clients_lock.iter_mut().find(|c| c.id == seat_id) else {
+        audit_record(
+            &audit,
+            &caller,
+            action,
+            Some(&seat_id),
+            "not_found",
+            Some(reason),
+        )
+        .await;
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let before = seat.player.chips;
+    let chips = (before as i64)
+        .checked_add(body.change)
+        .and_then(|chips| u32::try_from(chips).ok());
+    let Some(chips) = chips else {
+        audit_record(
+            &audit,
+            &caller,
+            action,
+            Some(&seat_id),
+            "out_of_range",
+            Some(reason),
+        )
+        .await;
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    seat.player.chips = chips;
+
+    let name = seat.player.user_name.clone();
+    let detail = format!("{} -> {} ({:+}): {}", before, chips, body.change, reason);
+    info!("Adjusted {}'s chips {}", name, detail);
+    audit_record(&audit, &caller, action, Some(&name), "ok", Some(&detail)).await;
+
+    let summary = seat_summary(seat, &history_lock, tournament.lock().await.as_ref());
+    drop(history_lock);
+    drop(clients_lock);
+
+    let pub_req = PublishRequest {
+        trigger: PublishTrigger::ChipsAdjusted {
+            user_name: name,
+            chips,
+            change: body.change,
+            reason: reason.to_owned(),
+        },
+    };
+    let _ = publish(pub_req, clients, None).await;
+    Ok(json(&summary).into_response())
+}
+
+/// Closes the table. Any round being played is called off, the game is ended and everyone is
+/// disconnected. Nobody can register afterwards.
+#[allow(clippy::too_many_arguments)]
+pub async fn close_handler(
+    table_id: String,
+    caller: Caller,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "close_table";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+    if let Err(status) = find_table(&table_id, action, &caller, &history, &audit).await {
+        return Ok(status.into_response());
+    }
+    if std::mem::replace(&mut admin.lock().await.closed, true) {
+        audit_record(&audit, &caller, action, Some(&table_id), "closed", None).await;
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    info!("Closing table {}", table_id);
+    audit_record(&audit, &caller, action, Some(&table_id), "ok", None).await;
+
+    announce("This table has been closed.", &clients).await;
+    let pub_reqs = match void_round(&clients, &dealer, &history, &tournament).await {
+        Some((pub_reqs, _)) => pub_reqs,
+        None => vec![],
+    };
+    publish_results(pub_reqs, false, &shoe, &history, &clients).await;
+
+    for client in clients.lock().await.drain(..) {
+        if let Some(sender) = &client.sender {
+            sender.close();
+        }
+    }
+
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Stops new players and rounds so the server can be restarted. The round being played is
+/// finished first. Poll `GET /admin/tables` until the table is `drained`.
+#[allow(clippy::too_many_arguments)]
+pub async fn drain_handler(
+    caller: Caller,
+    clients: Clients,
+    shoe: SharedShoe,
+    history: History,
+    tournament: SharedTournament,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "drain";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let already_draining = {
+        let mut admin = admin.lock().await;
+        std::mem::replace(&mut admin.draining, true) || admin.closed
+    };
+    audit_record(&audit, &caller, action, None, "ok", None).await;
+
+    if !already_draining {
+        info!("Draining the server");
+        if history.lock().await.in_round() {
+            announce(
+                "The server is restarting. This is the last round.",
+                &clients,
+            )
+            .await;
+        } else {
+            announce("The server is restarting.", &clients).await;
+            publish_results(vec![], false, &shoe, &history, &clients).await;
+        }
+    }
+
+    let summary = table_summary(&clients, &history, &tournament, &admin).await;
+    Ok(with_status(json(&vec![summary]), StatusCode::ACCEPTED).into_response())
+}
+
+/// Lists the banned names.
+pub async fn bans_handler(
+    caller: Caller,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "list_bans";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    audit_record(&audit, &caller, action, None, "ok", None).await;
+    Ok(json(&admin.lock().await.bans()).into_response())
+}
+
+/// Stops a name from registering. Anyone already seated under it is left to play on.
+pub async fn ban_handler(
+    caller: Caller,
+    body: BanRequest,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "ban";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let user_name = body.user_name.trim();
+    let reason = body
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if user_name.is_empty() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    if !admin.lock().await.ban(user_name, reason) {
+        audit_record(&audit, &caller, action, Some(user_name), "banned", reason).await;
+        return Ok(StatusCode::CONFLICT.into_response());
+    }
+
+    info!("Banned {}", user_name);
+    audit_record(&audit, &caller, action, Some(user_name), "ok", reason).await;
+    Ok(StatusCode::CREATED.into_response())
+}
+
+/// Lets a banned name register again. The name is percent-encoded in the path.
+pub async fn unban_handler(
+    user_name: String,
+    caller: Caller,
+    config: Config,
+    audit: Audit,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let action = "unban";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let user_name = percent_decode_str(&user_name)
+        .decode_utf8_lossy()
+        .into_owned();
+    if !admin.lock().await.unban(&user_name) {
+        audit_record(&audit, &caller, action, Some(&user_name), "not_found", None).await;
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    info!("Unbanned {}", user_name);
+    audit_record(&audit, &caller, action, Some(&user_name), "ok", None).await;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Changes how much the server logs until it's restarted.
+pub async fn log_level_handler(
+    caller: Caller,
+    body: LogLevelRequest,
+    config: Config,
+    audit: Audit,
+) -> Result<impl Reply, Rejection> {
+    let action = "log_level";
+    if let Err(status) = authorize(action, &caller, &config, &audit).await {
+        return Ok(status.into_response());
+    }
+
+    let Ok(level) = LogLevel::from_str(body.level.trim(), true) else {
+        audit_record(&audit, &caller, action, Some(&body.level), "unknown", None).await;
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+    if let Err(e) = log::set_level(level) {
+        error!(error = %e, "Couldn't change the log level");
+        audit_record(
+            &audit,
+            &caller,
+            action,
+            Some(&body.level),
+            "error",
+            Some(&e),
+        )
+        .await;
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    warn!(level = ?level, "Log level changed");
+    audit_record(&audit, &caller, action, Some(&body.level), "ok", None).await;
+    Ok(StatusCode::OK.into_response())
+}
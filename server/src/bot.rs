@@ -0,0 +1,392 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use blackjack_shared::{
+    card::Card,
+    counting::{BetSpread, Counter, HiLo},
+    player::{get_hand_value, Player, PlayerAction, PlayerType},
+    rules::TableRules,
+    strategy::{strategy_from_name, PlayerStrategy, StrategyTable},
+    web_socket::*,
+};
+use tracing::{info, info_span, Instrument};
+use uuid::Uuid;
+use warp::ws::Message;
+
+use crate::{
+    client::{next_position, Client},
+    handlers::handle_client_msg,
+    metrics,
+    outbox::{Outbox, Outgoing},
+    shutdown::SavedSeat,
+    Clients, Config, Dealer, History, SharedAdmin, SharedShoe, SharedTournament,
+};
+
+/// The chips a bot bets for each unit of its bet.
+const BET_UNIT: u32 = 10;
+/// How long a bot waits before each move so the humans at the table can follow along.
+const THINKING_TIME: Duration = Duration::from_millis(500);
+/// The strategies a bot can be added with. `counter` plays basic strategy and raises its bet
+/// with the Hi-Lo count.
+pub const BOT_STRATEGIES: [&str; 5] = [
+    "basic",
+    "mimic-dealer",
+    "never-bust",
+    "always-stand",
+    "counter",
+];
+
+/// How a bot decides what to bet.
+enum Betting {
+    Flat,
+    Counting { counter: Counter, spread: BetSpread },
+}
+
+/// A seat played by the server. Bots only see what a human client would and act by sending the
+/// same requests.
+struct Bot {
+    id: String,
+    player: Player,
+    strategy: Box<dyn PlayerStrategy + Send + Sync>,
+    betting: Betting,
+    /// The table's rules, for its bet limits.
+    rules: TableRules,
+    dealer_card: Option<Card>,
+    round_in_progress: bool,
+    my_turn: bool,
+    /// Set between asking for the card to double down on and it arriving.
+    doubling: bool,
+}
+
+impl Bot {
+    fn new(strategy_name: &str, id: String, player: Player, rules: &TableRules) -> Option<Self> {
+        let (strategy, betting): (Box<dyn PlayerStrategy + Send + Sync>, Betting) =
+            if strategy_name == "counter" {
+                (
+                    Box::new(StrategyTable::new(rules)),
+                    Betting::Counting {
+                        counter: Counter::new(Box::new(HiLo), rules.decks),
+                        spread: "1,2,4,8".parse().unwrap(),
+                    },
+                )
+            } else {
+                (strategy_from_name(strategy_name, rules)?, Betting::Flat)
+            };
+
+        Some(Bot {
+            id,
+            player,
+            strategy,
+            betting,
+            rules: rules.clone(),
+            dealer_card: None,
+            round_in_progress: false,
+            my_turn: false,
+            doubling: false,
+        })
+    }
+
+    /// Returns the requests to send in reply to a message from the table.
+    fn handle(&mut self, trigger: PublishTrigger) -> Vec<RequestCommand> {
+        match trigger {
+            PublishTrigger::StartTurn {
+                active_client_id,
+                dealer_card,
+                ..
+            } => {
+                // Every turn shows the same dealer card so only count it once per round.
+                if !self.round_in_progress {
+                    self.round_in_progress = true;
+                    if let Some(card) = &dealer_card {
+                        self.observe(std::slice::from_ref(card));
+                    }
+                    self.dealer_card = dealer_card;
+                }
+
+                if active_client_id != self.id {
+                    return vec![];
+                }
+
+                self.my_turn = true;
+                self.player.current_bet = self.bet();
+                vec![
+                    RequestCommand::Bet(self.player.current_bet),
+                    RequestCommand::DrawCards(2),
+                ]
+            }
+            PublishTrigger::CardsDrawn { cards } => {
+                self.observe(&cards);
+                if !self.my_turn {
+                    return vec![];
+                }
+
+                self.player.hand.extend(cards);
+                self.player.hand_value = get_hand_value(self.player.hand.clone());
+                self.play()
+            }
+            PublishTrigger::ChipsAdjusted {
+                user_name, chips, ..
+            } => {
+                if user_name == self.player.user_name {
+                    self.player.chips = chips;
+                }
+                vec![]
+            }
+            PublishTrigger::ShoeShuffled { .. } => {
+                if let Betting::Counting { counter, .. } = &mut self.betting {
+                    counter.reset();
+                }
+                vec![]
+            }
+            PublishTrigger::RoundFinished(results) => {
+                for result in results {
+                    if result.player.player_type == PlayerType::Dealer {
+                        // The hole card and any cards the dealer drew.
+                        self.observe(result.player.hand.get(1..).unwrap_or_default());
+                    } else if result.player.user_name == self.player.user_name {
+                        self.player.chips = result.player.chips;
+                    }
+                }
+
+                self.player.hand = vec![];
+                self.player.hand_value = 0;
+                self.player.current_bet = 0;
+                self.round_in_progress = false;
+                self.dealer_card = None;
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Decides what to do with the hand now that a card has arrived.
+    fn play(&mut self) -> Vec<RequestCommand> {
+        // The server ends the turn after the card dealt on a double.
+        if self.doubling {
+            self.doubling = false;
+            self.player.current_bet *= 2;
+            self.my_turn = false;
+            return vec![];
+        }
+
+        let Some(dealer_card) = &self.dealer_card else {
+            return self.end_turn();
+        };
+        if self.player.hand_value > 21 {
+            return self.end_turn();
+        }
+
+        match self.strategy.action(&self.player.hand, dealer_card) {
+            PlayerAction::Stand => self.end_turn(),
+            PlayerAction::Double
+                if self.player.hand.len() == 2
+                    && self.player.current_bet * 2 <= self.player.chips =>
+            {
+                self.doubling = true;
+                vec![RequestCommand::Double]
+            }
+            PlayerAction::Hit | PlayerAction::Double => vec![RequestCommand::Hit],
+        }
+    }
+
+    fn end_turn(&mut self) -> Vec<RequestCommand> {
+        self.my_turn = false;
+        vec![RequestCommand::EndTurn(self.player.clone())]
+    }
+
+    fn bet(&self) -> u32 {
+        let units = match &self.betting {
+            Betting::Flat => 1,
+            Betting::Counting { counter, spread } => {
+                spread.units(counter.true_count().unwrap_or_default())
+            }
+        };
+        (units * BET_UNIT)
+            .max(self.rules.min_bet)
+            .min(self.rules.max_bet.unwrap_or(u32::MAX))
+            .min(self.player.chips)
+    }
+
+    fn observe(&mut self, cards: &[Card]) {
+        if let Betting::Counting { counter, .. } = &mut self.betting {
+            counter.observe(cards);
+        }
+    }
+}
+
+/// Seats a bot playing the named strategy and starts it listening to the table. Returns the
+/// bot's name, or `None` if the strategy isn't known.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_bot(
+    strategy_name: &str,
+    chips: u32,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+) -> Option<String> {
+    let strategy_name = strategy_name.trim().to_lowercase();
+    if !BOT_STRATEGIES.contains(&strategy_name.as_str()) {
+        return None;
+    }
+
+    let outbox = Outbox::unbounded();
+    let id = format!("bot-{}", Uuid::new_v4().simple());
+
+    let mut clients_lock = clients.lock().await;
+    let number = clients_lock.iter().filter(|c| c.bot.is_some()).count() + 1;
+    let player = Player {
+        user_name: format!("Bot {} ({})", number, strategy_name),
+        player_type: PlayerType::Human,
+        hand: vec![],
+        hand_value: 0,
+        chips,
+        current_bet: 0,
+    };
+    let bot = Bot::new(&strategy_name, id.clone(), player.clone(), &config.rules)?;
+
+    let position = next_position(&clients_lock);
+    clients_lock.push(Client {
+        id,
+        sender: Some(outbox.clone()),
+        position,
+        player,
+        bot: Some(strategy_name),
+    });
+    drop(clients_lock);
+
+    Some(
+        spawn_bot(
+            bot, outbox, clients, dealer, shoe, config, history, tournament, admin,
+        )
+        .await,
+    )
+}
+
+/// Seats a bot saved when the server last stopped, with its chips and place at the table.
+/// Returns the bot's name, or `None` if the seat wasn't a bot's.
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_bot(
+    seat: SavedSeat,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+) -> Option<String> {
+    let strategy_name = seat.bot.clone()?;
+    let outbox = Outbox::unbounded();
+    let mut client = seat.into_client();
+    let bot = Bot::new(
+        &strategy_name,
+        client.id.clone(),
+        client.player.clone(),
+        &config.rules,
+    )?;
+    client.sender = Some(outbox.clone());
+    clients.lock().await.push(client);
+
+    Some(
+        spawn_bot(
+            bot, outbox, clients, dealer, shoe, config, history, tournament, admin,
+        )
+        .await,
+    )
+}
+
+/// Starts playing a bot's seat. Returns the bot's name.
+#[allow(clippy::too_many_arguments)]
+async fn spawn_bot(
+    bot: Bot,
+    outbox: Outbox,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+) -> String {
+    let name = bot.player.user_name.clone();
+    // The bot outlives the command that added it, so its span starts a trace of its own.
+    let room = history.lock().await.table_id().to_owned();
+    let span =
+        info_span!(parent: None, "bot", client_id = %bot.id, user_name = %name, room = %room);
+    tokio::spawn(
+        run_bot(
+            bot, outbox, clients, dealer, shoe, config, history, tournament, admin,
+        )
+        .instrument(span),
+    );
+    name
+}
+
+/// Plays the bot's seat until it's removed from the table. The future is boxed because the bot
+/// sends its requests through the same handler that adds bots.
+#[allow(clippy::too_many_arguments)]
+fn run_bot(
+    mut bot: Bot,
+    outbox: Outbox,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        info!("Joined the table");
+        metrics::bot_joined();
+
+        'listening: while let Some(Outgoing::Message(message)) = outbox.next().await {
+            let Ok(text) = message.to_str() else {
+                continue;
+            };
+            let Ok(request) = serde_json::from_str::<PublishRequest>(text) else {
+                continue;
+            };
+            if request.trigger == PublishTrigger::GameFinished {
+                break;
+            }
+
+            for command in bot.handle(request.trigger) {
+                tokio::time::sleep(THINKING_TIME).await;
+
+                let client = clients
+                    .lock()
+                    .await
+                    .iter()
+                    .find(|c| c.id == bot.id)
+                    .cloned();
+                let Some(client) = client else {
+                    break 'listening;
+                };
+
+                let message =
+                    Message::text(serde_json::to_string(&BlackjackRequest { command }).unwrap());
+                handle_client_msg(
+                    &bot.id,
+                    message,
+                    clients.clone(),
+                    client,
+                    dealer.clone(),
+                    shoe.clone(),
+                    config.clone(),
+                    history.clone(),
+                    tournament.clone(),
+                    admin.clone(),
+                )
+                .await;
+            }
+        }
+
+        clients.lock().await.retain(|c| c.id != bot.id);
+        metrics::bot_left();
+        info!("Left the table");
+    })
+}
@@ -1,41 +1,183 @@
 use blackjack_shared::card::Card;
-use blackjack_shared::helpers::{rank_from_int, suit_from_int};
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-
-pub fn draw_cards(num_to_draw: u16) -> Vec<Card> {
-    // The state of the deck is somewhat irrelevant for blackjack games so create a new one each
-    // time.
-    let mut deck = create_playing_deck(1);
+use blackjack_shared::fairness::{commitment, shuffled_shoe, ServerSeed};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The most client seeds mixed into one shuffle, and the longest seed kept.
+const MAX_CLIENT_SEEDS: usize = 32;
+const MAX_CLIENT_SEED_LEN: usize = 64;
+
+/// The cards waiting to be dealt. Cards are dealt from the same shoe across rounds until the cut
+/// card is reached, at which point the shoe is reshuffled before the next round.
+///
+/// Every shuffle uses a server seed drawn from the shoe's own generator, so a shoe created from a
+/// fixed seed always deals the same cards unless clients send in seeds of their own.
+#[derive(Debug)]
+pub struct Shoe {
+    cards: Vec<Card>,
+    decks: u8,
+    /// Set when the shoe is reshuffled and cleared once the clients have been told.
+    shuffled: bool,
+    rng: ChaCha8Rng,
+    /// Counts up with every shuffle so the clients can match reveals to commitments.
+    id: u32,
+    server_seed: ServerSeed,
+    client_seeds: Vec<String>,
+    /// Seeds sent in by the clients that will be used for the next shuffle.
+    pending_client_seeds: Vec<String>,
+    commitment: String,
+    /// Whether the commitment for this shoe has been sent to the clients.
+    committed: bool,
+    /// A committed shoe that has been replaced and is waiting to be revealed.
+    finished: Option<FinishedShoe>,
+    /// The number of cards dealt since the last shuffle.
+    dealt: usize,
+    /// Set while dealing a pre-arranged sequence of cards. The cut card is ignored until it runs
+    /// out.
+    stacked: bool,
+}
+
+/// The seeds of a shoe that has been dealt, ready to be revealed to the clients.
+#[derive(Debug, Clone)]
+pub struct FinishedShoe {
+    pub id: u32,
+    pub server_seed: ServerSeed,
+    pub client_seeds: Vec<String>,
+    pub dealt: usize,
+}
+
+impl Shoe {
+    pub fn new(decks: u8) -> Self {
+        Shoe::seeded(decks, thread_rng().gen())
+    }
+
+    /// A shoe whose shuffles all come from the given seed.
+    pub fn seeded(decks: u8, seed: u64) -> Self {
+        let mut shoe = Shoe {
+            cards: vec![],
+            decks,
+            shuffled: false,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            id: 0,
+            server_seed: [0; 32],
+            client_seeds: vec![],
+            pending_client_seeds: vec![],
+            commitment: String::new(),
+            committed: false,
+            finished: None,
+            dealt: 0,
+            stacked: false,
+        };
+        shoe.shuffle();
+        shoe.shuffled = false;
+        shoe
+    }
+
+    /// A shoe that deals the given cards in order. Once they've all been dealt the shoe is
+    /// shuffled from the seed as usual. Stacked cards can't be proven fair so they're never
+    /// committed to.
+    pub fn stacked(decks: u8, seed: u64, cards: Vec<Card>) -> Self {
+        let mut shoe = Shoe::seeded(decks, seed);
+        shoe.cards = cards.into_iter().rev().collect();
+        shoe.stacked = true;
+        shoe
+    }
+
+    pub fn decks(&self) -> u8 {
+        self.decks
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn server_seed(&self) -> &ServerSeed {
+        &self.server_seed
+    }
+
+    /// The commitment to the shoe being dealt, which is safe to share before it's revealed.
+    pub fn commitment(&self) -> &str {
+        &self.commitment
+    }
+
+    pub fn dealt(&self) -> usize {
+        self.dealt
+    }
+
+    /// Whether the cut card has been reached. The cut card is placed a quarter of the way from
+    /// the back of the shoe.
+    pub fn needs_shuffle(&self) -> bool {
+        !self.stacked && self.cards.len() < self.decks as usize * 52 / 4
+    }
+
+    pub fn shuffle(&mut self) {
+        if self.committed {
+            self.finished = Some(FinishedShoe {
+                id: self.id,
+                server_seed: self.server_seed,
+                client_seeds: std::mem::take(&mut self.client_seeds),
+                dealt: self.dealt,
+            });
+        }
+
+        self.id += 1;
+        self.server_seed = self.rng.gen();
+        self.client_seeds = std::mem::take(&mut self.pending_client_seeds);
+        self.cards = shuffled_shoe(self.decks, &self.server_seed, &self.client_seeds);
+
+        let deal_order: Vec<Card> = self.cards.iter().rev().cloned().collect();
+        self.commitment = commitment(&self.server_seed, &deal_order);
+        self.committed = false;
+        self.shuffled = true;
+        self.stacked = false;
+        self.dealt = 0;
+    }
+
+    /// Returns true once after each reshuffle.
+    pub fn take_shuffled(&mut self) -> bool {
+        std::mem::take(&mut self.shuffled)
+    }
+
+    /// Adds a client's seed to the next shuffle. Returns false if the seed was turned away.
+    pub fn add_client_seed(&mut self, seed: String) -> bool {
+        if seed.is_empty() || self.pending_client_seeds.len() >= MAX_CLIENT_SEEDS {
+            return false;
+        }
+        self.pending_client_seeds
+            .push(seed.chars().take(MAX_CLIENT_SEED_LEN).collect());
+        true
+    }
+
+    /// Returns the commitment for the current shoe the first time it's called after a shuffle.
+    pub fn take_commitment(&mut self) -> Option<(String, Vec<String>)> {
+        if self.committed || self.stacked {
+            return None;
+        }
+        self.committed = true;
+        Some((self.commitment.clone(), self.client_seeds.clone()))
+    }
+
+    /// Returns the last shoe that was committed to and has since been replaced.
+    pub fn take_finished(&mut self) -> Option<FinishedShoe> {
+        self.finished.take()
+    }
+}
+
+pub fn draw_cards(shoe: &mut Shoe, num_to_draw: u16) -> Vec<Card> {
     let mut cards: Vec<Card> = Vec::new();
     let mut num = num_to_draw;
 
     while num > 0 {
-        cards.push(match deck.pop() {
+        cards.push(match shoe.cards.pop() {
             Some(card) => card,
             None => {
-                let new_deck = create_playing_deck(1);
-                deck.extend(new_deck);
-                deck.pop().unwrap()
+                // Only happens if a round uses more cards than are left behind the cut card.
+                shoe.shuffle();
+                shoe.cards.pop().unwrap()
             }
         });
+        shoe.dealt += 1;
         num -= 1;
     }
     cards
 }
-
-pub fn create_playing_deck(num_of_decks: u8) -> Vec<Card> {
-    let mut deck: Vec<Card> = (0..num_of_decks)
-        .flat_map(|_d| {
-            (0..4).flat_map(|s| {
-                (0..13).map(move |r| Card {
-                    rank: rank_from_int(r),
-                    suit: suit_from_int(s),
-                })
-            })
-        })
-        .collect();
-
-    deck.shuffle(&mut thread_rng());
-    deck
-}
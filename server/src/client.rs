@@ -1,11 +1,19 @@
 use blackjack_shared::player::Player;
-use tokio::sync::mpsc;
-use warp::filters::ws::Message;
+
+use crate::outbox::Outbox;
 
 #[derive(Debug, Clone)]
 pub struct Client {
     pub id: String,
-    pub sender: Option<mpsc::UnboundedSender<std::result::Result<Message, warp::Error>>>,
+    pub sender: Option<Outbox>,
     pub position: usize,
     pub player: Player,
+    /// The strategy of a seat played by the server.
+    pub bot: Option<String>,
+}
+
+/// The position for a new seat, after everyone already at the table. Positions aren't reused
+/// when someone leaves.
+pub fn next_position(clients: &[Client]) -> usize {
+    clients.iter().map(|c| c.position + 1).max().unwrap_or(0)
 }
@@ -0,0 +1,339 @@
+use std::{fs, net::SocketAddr, path::Path, path::PathBuf};
+
+use blackjack_shared::rules::TableRules;
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::log::{LogFormat, LogLevel};
+
+/// The server's settings, read from a TOML file. Anything left out of the file keeps its
+/// default, and the command line and environment can override the rest.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// The address the server listens on.
+    pub bind: SocketAddr,
+    /// The address players reach the server at, such as `http://blackjack.example.com:8000`.
+    /// Web socket urls handed out at registration are built from it. Defaults to the bind
+    /// address.
+    pub public_url: Option<String>,
+    pub log_level: LogLevel,
+    pub log_format: LogFormat,
+    /// The rules every table is dealt with, including the bet limits.
+    pub rules: TableRules,
+    pub limits: TableLimits,
+    pub storage: StorageConfig,
+    /// Serve `https` and `wss` with this certificate instead of plain `http` and `ws`.
+    pub tls: Option<TlsConfig>,
+    pub admin: AdminConfig,
+    pub shutdown: ShutdownConfig,
+    pub heartbeat: HeartbeatConfig,
+    pub queue: QueueConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: ([127, 0, 0, 1], 8000).into(),
+            public_url: None,
+            log_level: LogLevel::Info,
+            log_format: LogFormat::Pretty,
+            rules: TableRules::default(),
+            limits: TableLimits::default(),
+            storage: StorageConfig::default(),
+            tls: None,
+            admin: AdminConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            heartbeat: HeartbeatConfig::default(),
+            queue: QueueConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TableLimits {
+    /// The chips each player sits down with, unless a tournament says otherwise.
+    pub starting_chips: u32,
+    /// The most seats at the table, bots included.
+    pub max_players: Option<usize>,
+}
+
+impl Default for TableLimits {
+    fn default() -> Self {
+        TableLimits {
+            starting_chips: 500,
+            max_players: None,
+        }
+    }
+}
+
+/// Where the hand history is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Appended to a JSON Lines file and reloaded for the leaderboard on start up.
+    File,
+    /// Kept in memory only, so the leaderboard starts empty each time.
+    Memory,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    /// The hand history file used by the `file` backend.
+    pub path: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            backend: StorageBackend::File,
+            path: PathBuf::from("hand_history.jsonl"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// The PEM encoded certificate chain.
+    pub cert: PathBuf,
+    /// The PEM encoded private key for the certificate.
+    pub key: PathBuf,
+}
+
+/// The admin API used by the people running the server.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// The bearer token admin requests must carry. The admin API is turned off without one.
+    pub token: Option<String>,
+    /// Every admin request, allowed or not, is appended to this JSON Lines file.
+    pub audit_log: PathBuf,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        AdminConfig {
+            token: None,
+            audit_log: PathBuf::from("admin_audit.jsonl"),
+        }
+    }
+}
+
+/// What happens to the round being played when the server is stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundPolicy {
+    /// Let the round be played out, calling it off if it isn't over by the end of the grace
+    /// period.
+    Finish,
+    /// Call the round off straight away and return every bet.
+    Void,
+}
+
+/// Stopping the server with Ctrl-C or SIGTERM.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    pub round_policy: RoundPolicy,
+    /// How long the `finish` policy waits for the round to be played out, in seconds.
+    pub grace_secs: u64,
+    /// The seats, chips, tournament and bans are saved here when the server stops, and reloaded
+    /// when it starts.
+    pub state_file: PathBuf,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            round_policy: RoundPolicy::Finish,
+            grace_secs: 30,
+            state_file: PathBuf::from("table_state.json"),
+        }
+    }
+}
+
+/// Pinging the players' web sockets to spot connections that have died without closing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    /// Seconds between pings.
+    pub interval_secs: u64,
+    /// A player who hasn't been heard from for this many seconds is disconnected. If it was their
+    /// turn their hand stands.
+    pub timeout_secs: u64,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            interval_secs: 15,
+            timeout_secs: 60,
+        }
+    }
+}
+
+/// What happens to a player whose connection falls so far behind that their queue fills up.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverflowPolicy {
+    /// Close the connection. The player's seat is given up as if they had left.
+    Disconnect,
+    /// Throw away the messages waiting to be sent and send a snapshot of the table instead, once
+    /// the connection has caught up.
+    Snapshot,
+}
+
+/// The messages waiting to be sent to each player's web socket.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    /// The most messages waiting for a player before the overflow policy kicks in.
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capacity: 256,
+            overflow: OverflowPolicy::Snapshot,
+        }
+    }
+}
+
+/// Admin tokens shorter than this are too easy to guess.
+const MIN_ADMIN_TOKEN_LEN: usize = 16;
+
+impl ServerConfig {
+    /// Reads the config from a TOML file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read the config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Checks the settings make sense together, returning every problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = vec![];
+
+        if let Some(url) = &self.public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                problems.push(format!(
+                    "public_url '{}' must start with http:// or https://",
+                    url
+                ));
+            }
+        }
+        if !(1..=8).contains(&self.rules.decks) {
+            problems.push(format!(
+                "rules.decks is {} but must be between 1 and 8",
+                self.rules.decks
+            ));
+        }
+        // The server only deals single hands that can be hit, stood or doubled.
+        for (name, enabled) in [
+            ("rules.splitting", self.rules.splitting),
+            ("rules.double_after_split", self.rules.double_after_split),
+            ("rules.surrender", self.rules.surrender),
+        ] {
+            if enabled {
+                problems.push(format!("{} isn't supported by the server yet", name));
+            }
+        }
+        if self.rules.min_bet == 0 {
+            problems.push("rules.min_bet must be at least 1".to_owned());
+        }
+        if let Some(max_bet) = self.rules.max_bet {
+            if max_bet < self.rules.min_bet {
+                problems.push(format!(
+                    "rules.max_bet ({}) is below rules.min_bet ({})",
+                    max_bet, self.rules.min_bet
+                ));
+            }
+        }
+        if self.limits.starting_chips < self.rules.min_bet {
+            problems.push(format!(
+                "limits.starting_chips ({}) won't cover rules.min_bet ({})",
+                self.limits.starting_chips, self.rules.min_bet
+            ));
+        }
+        if self.limits.max_players == Some(0) {
+            problems.push("limits.max_players must be at least 1".to_owned());
+        }
+        if self.storage.backend == StorageBackend::File && self.storage.path.as_os_str().is_empty()
+        {
+            problems.push("storage.path must be set for the file backend".to_owned());
+        }
+
+        if let Some(tls) = &self.tls {
+            for (name, path) in [("tls.cert", &tls.cert), ("tls.key", &tls.key)] {
+                if let Err(e) = fs::metadata(path) {
+                    problems.push(format!("{} {} can't be read: {}", name, path.display(), e));
+                }
+            }
+        }
+
+        if let Some(token) = &self.admin.token {
+            if token.len() < MIN_ADMIN_TOKEN_LEN {
+                problems.push(format!(
+                    "admin.token must be at least {} characters long",
+                    MIN_ADMIN_TOKEN_LEN
+                ));
+            }
+            if self.admin.audit_log.as_os_str().is_empty() {
+                problems.push("admin.audit_log must be set when admin.token is".to_owned());
+            }
+        }
+
+        if self.heartbeat.interval_secs == 0 {
+            problems.push("heartbeat.interval_secs must be at least 1".to_owned());
+        }
+        if self.heartbeat.timeout_secs <= self.heartbeat.interval_secs {
+            problems.push(format!(
+                "heartbeat.timeout_secs ({}) must be longer than heartbeat.interval_secs ({})",
+                self.heartbeat.timeout_secs, self.heartbeat.interval_secs
+            ));
+        }
+
+        if self.queue.capacity == 0 {
+            problems.push("queue.capacity must be at least 1".to_owned());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid config:\n  {}", problems.join("\n  ")))
+        }
+    }
+
+    /// The base url players reach the server at.
+    pub fn public_url(&self) -> String {
+        match &self.public_url {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None if self.tls.is_some() => format!("https://{}", self.bind),
+            None => format!("http://{}", self.bind),
+        }
+    }
+
+    /// The web socket url for a registered client.
+    pub fn ws_url(&self, id: &str) -> String {
+        let base = self.public_url();
+        let base = match base.strip_prefix("https://") {
+            Some(rest) => format!("wss://{}", rest),
+            None => format!("ws://{}", base.trim_start_matches("http://")),
+        };
+        format!("{}/ws/{}", base, id)
+    }
+
+    /// Where to write the hand history, or `None` to keep it in memory.
+    pub fn history_path(&self) -> Option<&Path> {
+        match self.storage.backend {
+            StorageBackend::File => Some(&self.storage.path),
+            StorageBackend::Memory => None,
+        }
+    }
+}
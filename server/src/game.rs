@@ -1,14 +1,52 @@
+use std::iter::successors;
+
+use blackjack_shared::card::Card;
+use blackjack_shared::history::RoundEventKind;
 use blackjack_shared::player::*;
+use blackjack_shared::rules::TableRules;
+use blackjack_shared::strategy::hand_total;
 use blackjack_shared::web_socket::*;
+use tracing::{debug, info};
 
-use crate::card::draw_cards;
+use crate::card::{draw_cards, Shoe};
 use crate::client::Client;
+use crate::history::HandHistory;
+use crate::log::redacted;
 use crate::Clients;
 use crate::Dealer;
+use crate::History;
+use crate::SharedShoe;
+use crate::SharedTournament;
+
+/// Starts a new turn for the game. The shoe is reshuffled first if the cut card has been reached.
+pub async fn start_turn(
+    client: &Client,
+    clients: &Clients,
+    dealer: &Dealer,
+    shoe: &SharedShoe,
+    history: &History,
+) -> PublishRequest {
+    let seats = clients.lock().await.clone();
+    let mut shoe_lock = shoe.lock().await;
+    if shoe_lock.needs_shuffle() {
+        info!("Cut card reached, reshuffling the shoe");
+        shoe_lock.shuffle();
+    }
+    info!(
+        shoe = shoe_lock.id(),
+        commitment = shoe_lock.commitment(),
+        dealt = shoe_lock.dealt(),
+        "Dealing a round"
+    );
+    let dealer_cards = draw_cards(&mut shoe_lock, 2);
 
-/// Starts a new turn for the game.
-pub async fn start_turn(client: &Client, dealer: &Dealer) -> PublishRequest {
-    let dealer_cards = draw_cards(2);
+    let mut history_lock = history.lock().await;
+    history_lock.start_round(&seats, &shoe_lock, &dealer_cards);
+    history_lock.record(RoundEventKind::TurnStarted {
+        user_name: client.player.user_name.clone(),
+    });
+    drop(history_lock);
+    drop(shoe_lock);
     let mut dealer_lock = dealer.lock().await;
 
     dealer_lock.hand = dealer_cards.clone();
@@ -24,21 +62,131 @@ pub async fn start_turn(client: &Client, dealer: &Dealer) -> PublishRequest {
     }
 }
 
-/// Sets the player's bet amount using the given id of the client.
-pub async fn bet(clients: &Clients, id: &str, amount: u32) {
+/// Records the opening bet of the client with the given id, kept within the table limits, and
+/// returns the amount actually bet. A bet placed once the player has cards is refused with `None`,
+/// as a double is its own command.
+pub async fn bet(
+    clients: &Clients,
+    id: &str,
+    amount: u32,
+    rules: &TableRules,
+    history: &History,
+) -> Option<u32> {
     let mut clients_lock = clients.lock().await;
     let client_mut = clients_lock.iter_mut().find(|c| c.id == id).unwrap();
+    let user_name = client_mut.player.user_name.clone();
+
+    let mut history_lock = history.lock().await;
+    if history_lock.has_cards(&user_name) {
+        return None;
+    }
+    let amount = amount
+        .max(rules.min_bet)
+        .min(rules.max_bet.unwrap_or(u32::MAX))
+        .min(client_mut.player.chips);
     client_mut.player.current_bet = amount;
+    history_lock.record(RoundEventKind::Bet { user_name, amount });
+    Some(amount)
 }
 
-pub async fn draw_cards_for_publish(n: u16, clients: &Clients, client: &Client) -> PublishRequest {
+/// Doubles the bet of the client with the given id, returning the raised bet. Only a hand of the
+/// first two cards can be doubled, and only if the player's chips cover it, otherwise it's
+/// refused with `None`. The caller deals the one card and ends the turn.
+pub async fn double(clients: &Clients, id: &str, history: &History) -> Option<u32> {
+    let mut clients_lock = clients.lock().await;
+    let client_mut = clients_lock.iter_mut().find(|c| c.id == id).unwrap();
+    let player = &mut client_mut.player;
+
+    let mut history_lock = history.lock().await;
+    let amount = player.current_bet.checked_mul(2)?;
+    if player.hand.len() != 2
+        || history_lock.has_doubled(&player.user_name)
+        || amount > player.chips
+    {
+        return None;
+    }
+    player.current_bet = amount;
+    history_lock.record(RoundEventKind::Double {
+        user_name: player.user_name.clone(),
+        amount,
+    });
+    Some(amount)
+}
+
+/// Returns the requests letting the clients know what has happened to the shoe since the last
+/// time this was called. A finished shoe is revealed, and its seeds added to the hand history,
+/// before the next one is committed to.
+pub async fn shoe_events(shoe: &SharedShoe, history: &History) -> Vec<PublishRequest> {
+    let mut shoe_lock = shoe.lock().await;
+    let mut events = vec![];
+
+    if let Some(finished) = shoe_lock.take_finished() {
+        // Nothing more is dealt from the shoe, so its seed can't give away any cards.
+        info!(
+            shoe = finished.id,
+            seed = %hex::encode(finished.server_seed),
+            dealt = finished.dealt,
+            "Shoe revealed"
+        );
+        history.lock().await.reveal_shoe(&finished);
+        events.push(PublishTrigger::ShoeRevealed {
+            shoe_id: finished.id,
+            server_seed: hex::encode(finished.server_seed),
+            client_seeds: finished.client_seeds,
+            dealt: finished.dealt,
+        });
+    }
+
+    if shoe_lock.take_shuffled() {
+        info!(
+            shoe = shoe_lock.id(),
+            commitment = shoe_lock.commitment(),
+            "Shoe reshuffled"
+        );
+        events.push(PublishTrigger::ShoeShuffled {
+            decks: shoe_lock.decks(),
+        });
+    }
+
+    if let Some((commitment, client_seeds)) = shoe_lock.take_commitment() {
+        events.push(PublishTrigger::ShoeCommitted {
+            shoe_id: shoe_lock.id(),
+            decks: shoe_lock.decks(),
+            commitment,
+            client_seeds,
+        });
+    }
+
+    events
+        .into_iter()
+        .map(|trigger| PublishRequest { trigger })
+        .collect()
+}
+
+pub async fn draw_cards_for_publish(
+    n: u16,
+    clients: &Clients,
+    client: &Client,
+    shoe: &SharedShoe,
+    history: &History,
+) -> PublishRequest {
     let mut clients_lock = clients.lock().await;
     let client_mut = clients_lock.iter_mut().find(|c| c.id == client.id).unwrap();
 
-    let drawn_cards = draw_cards(n);
+    let mut shoe_lock = shoe.lock().await;
+    let shoe_id = shoe_lock.id();
+    let drawn_cards = draw_cards(&mut shoe_lock, n);
     client_mut.player.hand.extend(drawn_cards.clone());
     client_mut.player.hand_value = get_hand_value(client_mut.player.hand.clone());
 
+    let mut history_lock = history.lock().await;
+    record_shuffle(&mut history_lock, &shoe_lock, shoe_id);
+    history_lock.record(RoundEventKind::CardsDealt {
+        user_name: client_mut.player.user_name.clone(),
+        cards: drawn_cards.clone(),
+    });
+    drop(history_lock);
+    drop(shoe_lock);
     drop(clients_lock);
 
     PublishRequest {
@@ -48,59 +196,105 @@ pub async fn draw_cards_for_publish(n: u16, clients: &Clients, client: &Client)
     }
 }
 
+/// Picks who acts first in a new round. At a tournament table the button decides, otherwise the
+/// first seat goes first. That's usually the player who dealt, unless they rejoined a saved table
+/// after someone in an earlier seat. Returns `None` once a tournament is over.
+pub async fn first_to_act(clients: &Clients, tournament: &SharedTournament) -> Option<Client> {
+    let seats = clients.lock().await.clone();
+    match &mut *tournament.lock().await {
+        Some(t) => {
+            let id = t.start_hand(&seats)?;
+            seats.into_iter().find(|c| c.id == id)
+        }
+        None => seats.into_iter().min_by_key(|c| c.position),
+    }
+}
+
 /// Ends the game turn. This will calcualte the end state for each player.
-/// It will also check of all players have run out of chips and return a bool indicating if the
-/// game should continue.
+/// It will also check whether the game is over and return a bool indicating if the game should
+/// continue, along with the requests to publish.
+#[allow(clippy::too_many_arguments)]
 pub async fn end_turn(
     client: &mut Client,
     clients: &Clients,
     player: &Player,
     dealer: &Dealer,
-) -> (PublishRequest, bool) {
+    shoe: &SharedShoe,
+    rules: &TableRules,
+    history: &History,
+    tournament: &SharedTournament,
+) -> (Vec<PublishRequest>, bool) {
     client.player = player.clone();
+    history.lock().await.record(RoundEventKind::Stand {
+        user_name: player.user_name.clone(),
+        hand_value: player.hand_value,
+    });
 
     let lock = clients.lock().await;
-    let next_client = lock
-        .iter()
-        // TODO: This will break if someone leaves. Probably ok for this.
-        .find(|c| c.position == client.position + 1);
+    // Anyone who has left the table since the round was dealt is skipped.
+    let next_client = match &*tournament.lock().await {
+        Some(t) => successors(t.next_after(&client.id), |id| t.next_after(id))
+            .find_map(|id| lock.iter().find(|c| c.id == id)),
+        None => lock
+            .iter()
+            .filter(|c| c.position > client.position)
+            .min_by_key(|c| c.position),
+    };
 
     if let Some(c) = next_client {
+        history.lock().await.record(RoundEventKind::TurnStarted {
+            user_name: c.player.user_name.clone(),
+        });
         return (
-            PublishRequest {
+            vec![PublishRequest {
                 trigger: PublishTrigger::StartTurn {
                     active_client_id: c.id.clone(),
                     user_name: c.player.user_name.clone(),
                     dealer_card: Some(dealer.lock().await.hand[0].clone()),
                 },
-            },
+            }],
             true,
         );
     } else {
         drop(lock);
         // If we can't find any more clients then all players have finished.
-        println!("No next client found, ending round.");
+        debug!("No next client found, ending round");
 
         // Play the dealer's turn.
         // TODO: Broadcase the dealer's turn to all clients.
-        take_dealers_turn(&dealer).await;
+        let shoe_id = shoe.lock().await.id();
+        take_dealers_turn(dealer, shoe, rules).await;
 
         // End the game.
         let mut results = vec![];
         let mut clients_lock = clients.lock().await;
-        let mut continue_playing = false;
+        let dealer = dealer.lock().await.clone();
+
+        let shoe_lock = shoe.lock().await;
+        let mut history_lock = history.lock().await;
+        record_shuffle(&mut history_lock, &shoe_lock, shoe_id);
+        drop(shoe_lock);
+        for card in dealer.hand.iter().skip(2) {
+            history_lock.record(RoundEventKind::DealerDrew { card: card.clone() });
+        }
 
         results.push(TurnResult {
-            player: dealer.lock().await.clone(),
+            player: dealer.clone(),
             end_state: EndState::Push, // Result for dealer is irrelevent.
         });
 
+        let mut tournament_lock = tournament.lock().await;
         for c in clients_lock.iter_mut() {
+            // Players knocked out of a tournament sit the hand out.
+            if tournament_lock.as_ref().is_some_and(|t| !t.in_hand(&c.id)) {
+                continue;
+            }
+
             // Calculate the end state for each player.
-            println!("Calculating end state for {}", c.id);
-            let end_state = calculate_end_state(&c.player, &dealer).await;
+            let end_state = calculate_end_state(&c.player, &dealer);
 
             handle_end_state(&mut c.player, end_state.clone());
+            history_lock.settle(&c.player, &end_state);
 
             c.player.hand = vec![];
             c.player.hand_value = 0;
@@ -110,40 +304,182 @@ pub async fn end_turn(
                 end_state: end_state.clone(),
             });
 
-            println!("Result: {:?}", end_state);
-            // Keep playing until everyone is out of chips.
-            if c.player.chips > 0 {
-                continue_playing = true;
-            }
+            debug!(client_id = %c.id, end_state = ?end_state, "Settled");
         }
 
-        drop(clients_lock);
-
-        let pub_req = PublishRequest {
+        let mut pub_reqs = vec![PublishRequest {
             trigger: PublishTrigger::RoundFinished(results),
+        }];
+
+        // A tournament runs for a fixed number of hands, otherwise keep playing until everyone
+        // is out of chips.
+        let continue_playing = match &mut *tournament_lock {
+            Some(t) => {
+                let (standings, continue_playing) = t.finish_hand(&clients_lock);
+                pub_reqs.extend(standings);
+                continue_playing
+            }
+            None => clients_lock.iter().any(|c| c.player.chips > 0),
         };
 
-        (pub_req, continue_playing)
+        drop(tournament_lock);
+        drop(clients_lock);
+        history_lock.finish_round(&dealer);
+        drop(history_lock);
+
+        (pub_reqs, continue_playing)
+    }
+}
+
+/// Ends the round being played before everyone has acted, such as when an operator steps in.
+/// Every bet is returned as a push and the round is left out of the hand history. A tournament
+/// still counts the hand. Returns `None` between rounds, otherwise the requests to publish and
+/// whether the game should continue.
+pub async fn void_round(
+    clients: &Clients,
+    dealer: &Dealer,
+    history: &History,
+    tournament: &SharedTournament,
+) -> Option<(Vec<PublishRequest>, bool)> {
+    let mut clients_lock = clients.lock().await;
+    let dealer = dealer.lock().await.clone();
+    if !history.lock().await.void_round() {
+        return None;
+    }
+
+    let mut results = vec![TurnResult {
+        player: dealer,
+        end_state: EndState::Push,
+    }];
+
+    let mut tournament_lock = tournament.lock().await;
+    for c in clients_lock.iter_mut() {
+        if tournament_lock.as_ref().is_some_and(|t| !t.in_hand(&c.id)) {
+            continue;
+        }
+
+        c.player.hand = vec![];
+        c.player.hand_value = 0;
+        results.push(TurnResult {
+            player: c.player.clone(),
+            end_state: EndState::Push,
+        });
+    }
+
+    let mut pub_reqs = vec![PublishRequest {
+        trigger: PublishTrigger::RoundFinished(results),
+    }];
+    let continue_playing = match &mut *tournament_lock {
+        Some(t) => {
+            let (standings, continue_playing) = t.finish_hand(&clients_lock);
+            pub_reqs.extend(standings);
+            continue_playing
+        }
+        None => clients_lock.iter().any(|c| c.player.chips > 0),
+    };
+
+    Some((pub_reqs, continue_playing))
+}
+
+/// Where the table is up to, for a player whose connection fell too far behind to be sent what
+/// happened. Hands and bets come from the round being played, and other players' bets are left
+/// out while they're secret.
+pub fn table_snapshot(
+    clients: &[Client],
+    history: &HandHistory,
+    id: &str,
+    secret_bets: bool,
+) -> PublishRequest {
+    let round = history.current();
+    let events = round.into_iter().flat_map(|r| &r.events);
+
+    let players = clients
+        .iter()
+        .map(|c| {
+            let name = &c.player.user_name;
+            let mut hand = vec![];
+            let mut bet = 0;
+            for event in events.clone() {
+                match &event.kind {
+                    RoundEventKind::CardsDealt { user_name, cards } if user_name == name => {
+                        hand.extend(cards.iter().cloned());
+                    }
+                    RoundEventKind::Bet { user_name, amount }
+                    | RoundEventKind::Double { user_name, amount }
+                        if user_name == name =>
+                    {
+                        bet = *amount;
+                    }
+                    _ => {}
+                }
+            }
+            Player {
+                user_name: name.clone(),
+                player_type: PlayerType::Human,
+                hand_value: get_hand_value(hand.clone()),
+                hand,
+                chips: c.player.chips,
+                current_bet: if secret_bets && c.id != id { 0 } else { bet },
+            }
+        })
+        .collect();
+
+    let dealer_card = events.clone().find_map(|e| match &e.kind {
+        RoundEventKind::DealerDealt { cards } => cards.first().cloned(),
+        _ => None,
+    });
+
+    PublishRequest {
+        trigger: PublishTrigger::TableSnapshot {
+            round: round.map(|r| r.round),
+            dealer_card,
+            active_player: history.active_player().map(str::to_owned),
+            players,
+        },
+    }
+}
+
+/// Records a reshuffle if the shoe has changed since it had the given id.
+fn record_shuffle(history: &mut HandHistory, shoe: &Shoe, shoe_id: u32) {
+    if shoe.id() != shoe_id {
+        history.record(RoundEventKind::ShoeShuffled {
+            shoe_id: shoe.id(),
+            commitment: shoe.commitment().to_owned(),
+        });
     }
 }
 
-pub async fn take_dealers_turn(dealer_arc: &Dealer) {
+pub async fn take_dealers_turn(dealer_arc: &Dealer, shoe: &SharedShoe, rules: &TableRules) {
     let mut dealer = dealer_arc.lock().await;
+    play_dealers_hand(&mut dealer, &mut *shoe.lock().await, rules);
+
+    debug!(
+        hand = %redacted(&dealer.hand),
+        hand_value = dealer.hand_value,
+        "Dealer's turn played"
+    );
+}
+
+/// Draws cards for the dealer until they stand. This is shared with the simulator so both play
+/// the dealer's hand in exactly the same way.
+pub fn play_dealers_hand(dealer: &mut Player, shoe: &mut Shoe, rules: &TableRules) {
     dealer.hand_value = get_hand_value(dealer.hand.to_owned());
 
-    // Aways stand on >= 17
-    while dealer.hand_value < 17 {
-        let card = draw_cards(1)[0].to_owned();
+    // Aways stand on >= 17, unless the table hits a soft 17.
+    while dealer.hand_value < 17 || (rules.dealer_hits_soft_17 && is_soft_17(&dealer.hand)) {
+        let card = draw_cards(shoe, 1)[0].to_owned();
         dealer.hand.push(card);
         dealer.hand_value = get_hand_value(dealer.hand.to_owned());
     }
+}
 
-    println!("Dealer's hand: {:?}", dealer.hand);
+fn is_soft_17(hand: &[Card]) -> bool {
+    hand_total(hand) == (17, true)
 }
 
-pub async fn calculate_end_state(player: &Player, dealer: &Dealer) -> EndState {
+pub fn calculate_end_state(player: &Player, dealer: &Player) -> EndState {
     let player_value = player.hand_value;
-    let dealer_value = dealer.lock().await.hand_value;
+    let dealer_value = dealer.hand_value;
 
     if dealer_value > 21 && player_value > 21 || dealer_value == player_value {
         EndState::Push
@@ -163,13 +499,15 @@ fn is_blackjack(player: &Player) -> bool {
 pub fn handle_end_state(player: &mut Player, end_state: EndState) {
     match end_state {
         EndState::Win => {
-            player.chips += player.current_bet;
+            player.chips = player.chips.saturating_add(player.current_bet);
         }
         EndState::Loss => {
-            player.chips -= player.current_bet;
+            player.chips = player.chips.saturating_sub(player.current_bet);
         }
         EndState::Blackjack => {
-            player.chips += player.current_bet * 3;
+            player.chips = player
+                .chips
+                .saturating_add(player.current_bet.saturating_mul(3));
         }
         EndState::Push => {} // Nothing to do on a push
     }
@@ -1,6 +1,8 @@
-use futures::{FutureExt, StreamExt};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
+use std::time::{Duration, Instant};
+
+use futures::{stream::SplitSink, SinkExt, StreamExt};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 use warp::{
     reject::Rejection,
@@ -8,8 +10,19 @@ use warp::{
     ws::{Message, WebSocket},
 };
 
-use crate::{client::Client, game::*, Clients, Dealer};
+use crate::{
+    bot::{add_bot, BOT_STRATEGIES},
+    client::{next_position, Client},
+    game::*,
+    history::now,
+    log::redacted,
+    metrics,
+    outbox::{Outbox, Outgoing},
+    Clients, Config, Dealer, History, Reserved, SharedAdmin, SharedShoe, SharedTournament,
+};
 use blackjack_shared::{
+    history::RoundEventKind,
+    leaderboard::{LeaderboardQuery, PlayerStatsQuery},
     player::{Player, PlayerType},
     web_socket::*,
 };
@@ -17,24 +30,103 @@ use blackjack_shared::{
 pub async fn register_handler(
     body: RegisterRequest,
     clients: Clients,
+    config: Config,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+    reserved: Reserved,
 ) -> Result<impl Reply, Rejection> {
-    println!("Registration from: {}", body.user_name);
+    info!(user_name = %body.user_name, "Registration");
+
+    {
+        let admin = admin.lock().await;
+        if admin.closed || admin.is_banned(&body.user_name) {
+            info!("Turned away {}", body.user_name);
+            return Ok(warp::http::StatusCode::FORBIDDEN.into_response());
+        }
+        if admin.draining || admin.shutting_down {
+            info!("Turned away {}, the server is draining", body.user_name);
+            return Ok(warp::http::StatusCode::SERVICE_UNAVAILABLE.into_response());
+        }
+    }
+
+    // Players seated when the server last stopped get their seat and chips back.
+    let saved = {
+        let mut reserved = reserved.lock().await;
+        reserved
+            .iter()
+            .position(|s| s.user_name == body.user_name)
+            .map(|i| reserved.remove(i))
+    };
+    if let Some(seat) = saved {
+        info!(user_name = %body.user_name, "Rejoined a saved seat");
+        let tournament = tournament.lock().await.as_ref().map(|t| t.config().clone());
+        let id = seat.id.clone();
+        let chips = seat.chips;
+
+        let mut clients_lock = clients.lock().await;
+        let is_host = !has_human(&clients_lock);
+        let index = clients_lock.partition_point(|c| c.position < seat.position);
+        clients_lock.insert(index, seat.into_client());
+        return Ok(json(&RegisterResponse {
+            url: config.ws_url(&id),
+            is_host,
+            id,
+            rules: config.rules.clone(),
+            chips,
+            tournament,
+        })
+        .into_response());
+    }
+
+    let tournament = match &*tournament.lock().await {
+        Some(t) if t.started() => {
+            info!("Turned away {}, the tournament has started", body.user_name);
+            return Ok(warp::http::StatusCode::FORBIDDEN.into_response());
+        }
+        t => t.as_ref().map(|t| t.config().clone()),
+    };
+    let chips = tournament
+        .as_ref()
+        .map_or(config.limits.starting_chips, |t| t.starting_chips);
+
+    if is_full(&clients, &config).await {
+        info!("Turned away {}, the table is full", body.user_name);
+        return Ok(warp::http::StatusCode::FORBIDDEN.into_response());
+    }
+
     let user_name = body.user_name;
     let uuid = Uuid::new_v4().simple().to_string();
 
-    let is_host = clients.lock().await.len() == 0;
+    let is_host = !has_human(&clients.lock().await);
 
-    register_client(uuid.clone(), user_name, clients).await;
+    register_client(uuid.clone(), user_name, chips, clients).await;
     Ok(json(&RegisterResponse {
-        url: format!("ws://127.0.0.1:8000/ws/{}", uuid),
+        url: config.ws_url(&uuid),
         is_host,
         id: uuid,
-    }))
+        rules: config.rules.clone(),
+        chips,
+        tournament,
+    })
+    .into_response())
+}
+
+/// Whether every seat at the table has been taken.
+async fn is_full(clients: &Clients, config: &Config) -> bool {
+    let seats = clients.lock().await.len();
+    config.limits.max_players.is_some_and(|max| seats >= max)
 }
 
-async fn register_client(id: String, user_name: String, clients: Clients) {
-    let position = clients.lock().await.len();
-    clients.lock().await.push(Client {
+/// Whether a player is seated. The first player to sit down hosts the game, even if bots have
+/// been seated from a saved table.
+fn has_human(clients: &[Client]) -> bool {
+    clients.iter().any(|c| c.bot.is_none())
+}
+
+async fn register_client(id: String, user_name: String, chips: u32, clients: Clients) {
+    let mut clients_lock = clients.lock().await;
+    let position = next_position(&clients_lock);
+    clients_lock.push(Client {
         id,
         sender: None,
         position,
@@ -43,9 +135,10 @@ async fn register_client(id: String, user_name: String, clients: Clients) {
             player_type: PlayerType::Human,
             hand: vec![],
             hand_value: 0,
-            chips: 500,
+            chips,
             current_bet: 0,
         },
+        bot: None,
     });
 }
 
@@ -54,30 +147,91 @@ pub async fn unregister_handler(id: String, clients: Clients) -> Result<impl Rep
     Ok(warp::http::StatusCode::OK)
 }
 
+pub async fn leaderboard_handler(
+    query: LeaderboardQuery,
+    history: History,
+) -> Result<impl Reply, Rejection> {
+    Ok(json(
+        &history.lock().await.results().leaderboard(&query, now()),
+    ))
+}
+
+pub async fn player_stats_handler(
+    user_name: String,
+    query: PlayerStatsQuery,
+    history: History,
+) -> Result<impl Reply, Rejection> {
+    match history
+        .lock()
+        .await
+        .results()
+        .player_stats(&user_name, query.window, now())
+    {
+        Some(stats) => Ok(json(&stats)),
+        None => Err(warp::reject::not_found()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn ws_handler(
     ws: warp::ws::Ws,
     id: String,
     clients: Clients,
     dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
 ) -> Result<impl Reply, Rejection> {
     let lock = clients.lock().await.clone();
     let client = lock.iter().find(|c| c.id == id);
     match client {
-        Some(_) => Ok(ws.on_upgrade(move |socket| client_connection(socket, id, clients, dealer))),
+        Some(client) => {
+            let room = history.lock().await.table_id().to_owned();
+            let span = info_span!(
+                "connection",
+                client_id = %id,
+                user_name = %client.player.user_name,
+                room = %room,
+            );
+            Ok(ws.on_upgrade(move |socket| {
+                client_connection(
+                    socket, id, clients, dealer, shoe, config, history, tournament, admin,
+                )
+                .instrument(span)
+            }))
+        }
         None => Err(warp::reject::not_found()),
     }
 }
 
-pub async fn client_connection(ws: WebSocket, id: String, clients: Clients, dealer: Dealer) {
+#[allow(clippy::too_many_arguments)]
+pub async fn client_connection(
+    ws: WebSocket,
+    id: String,
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+) {
     let (client_ws_sender, mut client_ws_rcv) = ws.split();
-    let (client_sender, client_rcv) = mpsc::unbounded_channel();
+    let outbox = Outbox::new(config.queue.capacity, config.queue.overflow);
 
-    let client_rcv = UnboundedReceiverStream::new(client_rcv);
-    tokio::task::spawn(client_rcv.forward(client_ws_sender).map(|result| {
-        if let Err(e) = result {
-            eprintln!("error sending websocket msg: {}", e);
-        }
-    }));
+    let mut writer = tokio::task::spawn(
+        write_messages(
+            client_ws_sender,
+            outbox.clone(),
+            id.clone(),
+            clients.clone(),
+            history.clone(),
+            tournament.clone(),
+        )
+        .in_current_span(),
+    );
 
     // Limit the scope of the lock and mut reference.
     {
@@ -86,11 +240,12 @@ pub async fn client_connection(ws: WebSocket, id: String, clients: Clients, deal
         let client = lock.iter_mut().find(|c| c.id == id);
 
         if let Some(c) = client {
-            c.sender = Some(client_sender);
+            c.sender = Some(outbox.clone());
         }
     }
 
-    println!("{} connected", id);
+    info!("Connected");
+    metrics::client_connected();
 
     let client = clients
         .lock()
@@ -100,37 +255,228 @@ pub async fn client_connection(ws: WebSocket, id: String, clients: Clients, deal
         .unwrap()
         .clone();
 
-    while let Some(result) = client_ws_rcv.next().await {
+    let interval = Duration::from_secs(config.heartbeat.interval_secs);
+    let timeout = Duration::from_secs(config.heartbeat.timeout_secs);
+    let mut pings = tokio::time::interval(interval);
+    pings.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut connection = Connection::new();
+
+    loop {
+        let result = tokio::select! {
+            result = client_ws_rcv.next() => result,
+            _ = outbox.closed() => {
+                if outbox.overflowed() {
+                    warn!("Fell too far behind, disconnecting");
+                }
+                // Give the close frame a moment to go out.
+                let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut writer).await;
+                break;
+            }
+            _ = pings.tick() => {
+                let silent = connection.last_heard.elapsed();
+                if silent >= timeout {
+                    warn!(silent_secs = silent.as_secs(), "Not heard from, disconnecting");
+                    metrics::connection_timed_out();
+                    break;
+                }
+                if silent >= interval * 2 {
+                    connection.report(ConnectionQuality::Unresponsive, &client, &clients).await;
+                }
+                let sent_at = now().to_be_bytes().to_vec();
+                if outbox.send(Message::ping(sent_at)).is_err() {
+                    metrics::send_error("closed");
+                }
+                continue;
+            }
+        };
         let msg = match result {
-            Ok(msg) => msg,
-            Err(e) => {
-                eprintln!("error receiving ws message for id: {}): {}", id.clone(), e);
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => {
+                error!(error = %e, "Couldn't receive a web socket message");
                 break;
             }
+            None => break,
         };
-        handle_client_msg(&id, msg, clients.clone(), client.clone(), dealer.clone()).await;
+
+        connection.last_heard = Instant::now();
+        if msg.is_pong() {
+            // The pong carries back the time its ping was sent.
+            if let Ok(sent_at) = <[u8; 8]>::try_from(msg.as_bytes()) {
+                let latency_ms = now().saturating_sub(u64::from_be_bytes(sent_at));
+                metrics::ping_answered(Duration::from_millis(latency_ms));
+                connection.latency_ms = Some(latency_ms);
+            }
+            let quality = if connection.latency_ms >= Some(SLOW_LATENCY_MS) {
+                ConnectionQuality::Slow
+            } else {
+                ConnectionQuality::Good
+            };
+            connection.report(quality, &client, &clients).await;
+            continue;
+        }
+
+        handle_client_msg(
+            &id,
+            msg,
+            clients.clone(),
+            client.clone(),
+            dealer.clone(),
+            shoe.clone(),
+            config.clone(),
+            history.clone(),
+            tournament.clone(),
+            admin.clone(),
+        )
+        .await;
     }
 
-    clients.lock().await.retain(|c| c.id != id);
-    println!("{} disconnected", id);
+    // The writer may be stuck on a connection that has stopped reading.
+    writer.abort();
+
+    // A seat that's still here left on its own rather than being removed by an operator.
+    let seat = {
+        let mut lock = clients.lock().await;
+        let index = lock.iter().position(|c| c.id == id);
+        index.map(|i| lock.remove(i))
+    };
+    metrics::client_disconnected();
+    info!("Disconnected");
+
+    if let Some(seat) = seat {
+        connection
+            .report(ConnectionQuality::Disconnected, &seat, &clients)
+            .await;
+        stand_for_leaver(
+            seat,
+            &clients,
+            &dealer,
+            &shoe,
+            &config,
+            &history,
+            &tournament,
+        )
+        .await;
+    }
 }
 
-pub async fn publish_handler(
-    body: PublishRequest,
+/// Pings slower than this mark a connection as slow.
+const SLOW_LATENCY_MS: u64 = 300;
+/// How long a closing connection is given to send what's left in its queue.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Writes a player's queued messages to their web socket. When the overflow policy has thrown
+/// messages away, a snapshot of the table is sent in their place.
+async fn write_messages(
+    mut socket: SplitSink<WebSocket, Message>,
+    outbox: Outbox,
+    id: String,
     clients: Clients,
-) -> Result<impl Reply, Rejection> {
-    publish(body, clients, None).await
+    history: History,
+    tournament: SharedTournament,
+) {
+    while let Some(outgoing) = outbox.next().await {
+        let message = match outgoing {
+            Outgoing::Message(message) => message,
+            Outgoing::Snapshot => {
+                warn!("Fell too far behind, sending a snapshot of the table");
+                let secret_bets = tournament
+                    .lock()
+                    .await
+                    .as_ref()
+                    .is_some_and(|t| t.secret_bets());
+                let clients = clients.lock().await;
+                let history = history.lock().await;
+                let pub_req = table_snapshot(&clients, &history, &id, secret_bets);
+                // Anything published from here on comes after the snapshot.
+                outbox.resynced();
+                Message::text(serde_json::to_string(&pub_req).unwrap())
+            }
+        };
+        if let Err(e) = socket.send(message).await {
+            metrics::send_error("socket");
+            error!(error = %e, "Couldn't send a web socket message");
+            return;
+        }
+    }
+    let _ = socket.close().await;
 }
 
-async fn publish(
+/// What the server knows about a player's connection from its pings.
+struct Connection {
+    last_heard: Instant,
+    latency_ms: Option<u64>,
+    /// `None` until the first ping comes back.
+    quality: Option<ConnectionQuality>,
+}
+
+impl Connection {
+    fn new() -> Self {
+        Connection {
+            last_heard: Instant::now(),
+            latency_ms: None,
+            quality: None,
+        }
+    }
+
+    /// Lets the table know when the quality of the connection changes, and how it is once the
+    /// first ping comes back.
+    async fn report(&mut self, quality: ConnectionQuality, client: &Client, clients: &Clients) {
+        if self.quality == Some(quality) {
+            return;
+        }
+        self.quality = Some(quality);
+        info!(quality = ?quality, latency_ms = self.latency_ms, "Connection changed");
+
+        let pub_req = PublishRequest {
+            trigger: PublishTrigger::ConnectionChanged {
+                user_name: client.player.user_name.clone(),
+                quality,
+                latency_ms: self.latency_ms,
+            },
+        };
+        let _ = publish(pub_req, clients.clone(), None).await;
+    }
+}
+
+/// Stands the hand of a player who has left the table in the middle of their turn, so the round
+/// can carry on without them.
+pub(crate) async fn stand_for_leaver(
+    mut seat: Client,
+    clients: &Clients,
+    dealer: &Dealer,
+    shoe: &SharedShoe,
+    config: &Config,
+    history: &History,
+    tournament: &SharedTournament,
+) {
+    let was_acting = history.lock().await.active_player() == Some(seat.player.user_name.as_str());
+    if !was_acting {
+        return;
+    }
+
+    info!(user_name = %seat.player.user_name, "Standing for a player who has left");
+    let player = seat.player.clone();
+    let (pub_reqs, continue_playing) = end_turn(
+        &mut seat,
+        clients,
+        &player,
+        dealer,
+        shoe,
+        &config.rules,
+        history,
+        tournament,
+    )
+    .await;
+    publish_shoe_events(shoe, history, clients).await;
+    publish_results(pub_reqs, continue_playing, shoe, history, clients).await;
+}
+
+pub(crate) async fn publish(
     body: PublishRequest,
     clients: Clients,
     filter_client_id: Option<String>,
 ) -> Result<impl Reply, Rejection> {
-    println!(
-        "Attempting to publish message {}",
-        serde_json::to_string(&body).unwrap()
-    );
+    debug!(request = %redacted(&body), "Publishing");
     clients.lock().await.iter_mut().for_each(|client| {
         if let Some(filter) = &filter_client_id {
             if client.id == *filter {
@@ -139,74 +485,309 @@ async fn publish(
         }
 
         if let Some(sender) = &client.sender {
-            println!("Sending message to {}", client.player.user_name);
-            let _ = sender.send(Ok(Message::text(serde_json::to_string(&body).unwrap())));
+            if sender
+                .send(Message::text(serde_json::to_string(&body).unwrap()))
+                .is_err()
+            {
+                metrics::send_error("closed");
+            }
         }
     });
 
-    println!("Message published");
     Ok(warp::http::StatusCode::OK)
 }
 
-async fn handle_client_msg(
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn handle_client_msg(
     id: &str,
     msg: Message,
     clients: Clients,
     mut client: Client,
     dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
 ) {
     // TODO: Try and limit cloning - change functions to borrow where possible.
     // TODO: Add testing.
-    println!("received message from {}: {:?}", id, msg);
     let message = match msg.to_str() {
         Ok(v) => v,
         Err(_) => return,
     };
 
-    if message == "ping" || message == "ping\n" {
-        return;
-    }
-
     let req: BlackjackRequest = match serde_json::from_str(message) {
         Ok(v) => v,
         Err(e) => {
-            eprintln!("error while parsing message to request: {}", e);
+            metrics::message_received("invalid");
+            warn!(error = %e, "Couldn't read a request");
             return;
         }
     };
+    let command = req.command.name();
+    metrics::message_received(command);
+    debug!(request = %redacted(&req), "Received");
 
-    if let Some(_sender) = &client.sender {
+    // Only the player whose turn it is can bet, draw, double or end their turn, and a new round
+    // can't be dealt over one being played.
+    let allowed = match &req.command {
+        RequestCommand::Start => !history.lock().await.in_round(),
+        RequestCommand::Bet(_)
+        | RequestCommand::DrawCards(_)
+        | RequestCommand::Hit
+        | RequestCommand::Double
+        | RequestCommand::EndTurn(_) => {
+            history.lock().await.active_player() == Some(client.player.user_name.as_str())
+        }
+        _ => true,
+    };
+    if !allowed {
+        metrics::message_ignored(command);
+        info!(command, "Ignored a command sent out of turn");
+        return;
+    }
+    let _timer = metrics::CommandTimer::start(command);
+
+    let (round, round_span) = {
+        let history = history.lock().await;
+        (history.current().map(|r| r.round), history.round_span())
+    };
+    let span = info_span!("command", command, round);
+    span.follows_from(&round_span);
+
+    async {
+        let Some(_sender) = &client.sender else {
+            return;
+        };
         match req.command {
             RequestCommand::Start => {
-                let pub_req = start_turn(&client, &dealer).await;
+                // No more rounds are dealt once the server is stopping, and a draining table
+                // ends the game instead of dealing another.
+                if admin.lock().await.shutting_down {
+                    return;
+                }
+                if admin.lock().await.draining {
+                    publish_results(vec![], false, &shoe, &history, &clients).await;
+                    return;
+                }
+
+                let Some(first) = first_to_act(&clients, &tournament).await else {
+                    return;
+                };
+                let pub_req = start_turn(&first, &clients, &dealer, &shoe, &history).await;
+                publish_shoe_events(&shoe, &history, &clients).await;
                 let _ = publish(pub_req, clients, None).await;
             }
             RequestCommand::Bet(amount) => {
-                bet(&clients, &client.id, amount).await;
-                // TODO: Publish the bet amount to all clients.
+                let Some(amount) = bet(&clients, &client.id, amount, &config.rules, &history).await
+                else {
+                    metrics::message_ignored(command);
+                    info!(amount, "Refused a bet placed after the cards were dealt");
+                    return;
+                };
+                publish_bet(&client, amount, &clients, &tournament).await;
             }
-            RequestCommand::EndTurn(player) => {
-                let (pub_req, continue_playing) =
-                    end_turn(&mut client, &clients, &player, &dealer).await;
+            RequestCommand::Double => {
+                let Some(amount) = double(&clients, &client.id, &history).await else {
+                    metrics::message_ignored(command);
+                    info!("Refused a double that isn't on the first two cards or can't be covered");
+                    return;
+                };
+                publish_bet(&client, amount, &clients, &tournament).await;
 
+                let pub_req = draw_cards_for_publish(1, &clients, &client, &shoe, &history).await;
+                publish_shoe_events(&shoe, &history, &clients).await;
                 let _ = publish(pub_req, clients.clone(), None).await;
 
-                if !continue_playing {
-                    let game_finished_req = PublishRequest {
-                        trigger: PublishTrigger::GameFinished,
-                    };
-
-                    let _ = publish(game_finished_req, clients.clone(), None).await;
-                }
+                // The one card ends the turn.
+                let Some(player) = clients
+                    .lock()
+                    .await
+                    .iter()
+                    .find(|c| c.id == client.id)
+                    .map(|c| c.player.clone())
+                else {
+                    return;
+                };
+                let (pub_reqs, continue_playing) = end_turn(
+                    &mut client,
+                    &clients,
+                    &player,
+                    &dealer,
+                    &shoe,
+                    &config.rules,
+                    &history,
+                    &tournament,
+                )
+                .await;
+                publish_shoe_events(&shoe, &history, &clients).await;
+                publish_results(pub_reqs, continue_playing, &shoe, &history, &clients).await;
+            }
+            RequestCommand::EndTurn(player) => {
+                let (pub_reqs, continue_playing) = end_turn(
+                    &mut client,
+                    &clients,
+                    &player,
+                    &dealer,
+                    &shoe,
+                    &config.rules,
+                    &history,
+                    &tournament,
+                )
+                .await;
+                publish_shoe_events(&shoe, &history, &clients).await;
+                publish_results(pub_reqs, continue_playing, &shoe, &history, &clients).await;
             }
             RequestCommand::DrawCards(n) => {
-                let pub_req = draw_cards_for_publish(n, &clients, &client).await;
+                let pub_req = draw_cards_for_publish(n, &clients, &client, &shoe, &history).await;
+                publish_shoe_events(&shoe, &history, &clients).await;
                 let _ = publish(pub_req, clients.clone(), None).await;
             }
             RequestCommand::Hit => {
-                let pub_req = draw_cards_for_publish(1, &clients, &client).await;
+                history.lock().await.record(RoundEventKind::Hit {
+                    user_name: client.player.user_name.clone(),
+                });
+                let pub_req = draw_cards_for_publish(1, &clients, &client, &shoe, &history).await;
+                publish_shoe_events(&shoe, &history, &clients).await;
+                let _ = publish(pub_req, clients.clone(), None).await;
+            }
+            RequestCommand::ClientSeed(seed) => {
+                if shoe.lock().await.add_client_seed(seed) {
+                    info!("Added a client seed from {} to the next shuffle", id);
+                }
+            }
+            RequestCommand::AddBot(strategy) => {
+                let is_host = clients.lock().await.first().is_some_and(|c| c.id == id);
+                if !is_host || history.lock().await.rounds() > 0 {
+                    info!("Ignored a request from {} to add a bot", id);
+                    return;
+                }
+
+                let chips = tournament
+                    .lock()
+                    .await
+                    .as_ref()
+                    .map_or(config.limits.starting_chips, |t| t.config().starting_chips);
+                let message = if is_full(&clients, &config).await {
+                    "The table is full.".to_owned()
+                } else {
+                    match add_bot(
+                        &strategy,
+                        chips,
+                        clients.clone(),
+                        dealer.clone(),
+                        shoe.clone(),
+                        config.clone(),
+                        history.clone(),
+                        tournament.clone(),
+                        admin.clone(),
+                    )
+                    .await
+                    {
+                        Some(name) => format!("{} has joined the table.", name),
+                        None => format!(
+                            "There's no '{}' bot. Try one of: {}.",
+                            strategy,
+                            BOT_STRATEGIES.join(", ")
+                        ),
+                    }
+                };
+
+                let pub_req = PublishRequest {
+                    trigger: PublishTrigger::ChatMessage {
+                        user_name: "Dealer".to_owned(),
+                        message,
+                    },
+                };
+                let _ = publish(pub_req, clients.clone(), None).await;
+            }
+            RequestCommand::Chat(message) => {
+                let pub_req = PublishRequest {
+                    trigger: PublishTrigger::ChatMessage {
+                        user_name: client.player.user_name.clone(),
+                        message,
+                    },
+                };
                 let _ = publish(pub_req, clients.clone(), None).await;
             }
         };
     }
+    .instrument(span)
+    .await;
+}
+
+/// Lets the table know the player's bet. Secret bets are only shown to the other players once the
+/// hand is settled.
+async fn publish_bet(
+    client: &Client,
+    amount: u32,
+    clients: &Clients,
+    tournament: &SharedTournament,
+) {
+    let pub_req = PublishRequest {
+        trigger: PublishTrigger::BetPlaced {
+            user_name: client.player.user_name.clone(),
+            amount,
+        },
+    };
+
+    if tournament
+        .lock()
+        .await
+        .as_ref()
+        .is_some_and(|t| t.secret_bets())
+    {
+        send_to(pub_req, clients, &client.id).await;
+    } else {
+        let _ = publish(pub_req, clients.clone(), None).await;
+    }
+}
+
+/// Sends the request to a single client.
+pub(crate) async fn send_to(body: PublishRequest, clients: &Clients, id: &str) {
+    let lock = clients.lock().await;
+    if let Some(sender) = lock
+        .iter()
+        .find(|c| c.id == id)
+        .and_then(|c| c.sender.as_ref())
+    {
+        if sender
+            .send(Message::text(serde_json::to_string(&body).unwrap()))
+            .is_err()
+        {
+            metrics::send_error("closed");
+        }
+    }
+}
+
+/// Publishes the end of a turn or round. Once the game is over a fresh shoe is started so the one
+/// that was played can be revealed, and everyone is told the game has finished.
+pub(crate) async fn publish_results(
+    pub_reqs: Vec<PublishRequest>,
+    continue_playing: bool,
+    shoe: &SharedShoe,
+    history: &History,
+    clients: &Clients,
+) {
+    for pub_req in pub_reqs {
+        let _ = publish(pub_req, clients.clone(), None).await;
+    }
+
+    if !continue_playing {
+        shoe.lock().await.shuffle();
+        publish_shoe_events(shoe, history, clients).await;
+
+        let game_finished_req = PublishRequest {
+            trigger: PublishTrigger::GameFinished,
+        };
+        let _ = publish(game_finished_req, clients.clone(), None).await;
+    }
+}
+
+/// Lets every client know about any reshuffles, reveals and commitments to a new shoe.
+pub(crate) async fn publish_shoe_events(shoe: &SharedShoe, history: &History, clients: &Clients) {
+    for pub_req in shoe_events(shoe, history).await {
+        let _ = publish(pub_req, clients.clone(), None).await;
+    }
 }
@@ -0,0 +1,256 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use blackjack_shared::{
+    card::Card,
+    history::{
+        rules_hash, RoundEvent, RoundEventKind, RoundRecord, SeatRecord, Settlement, ShoeReveal,
+    },
+    player::Player,
+    rules::TableRules,
+    web_socket::EndState,
+};
+use tracing::{error, info, info_span, Span};
+
+use crate::{
+    card::{FinishedShoe, Shoe},
+    client::Client,
+    leaderboard::PlayerResults,
+    metrics,
+};
+
+/// Builds up a record of the round being played and appends it to the hand history log as a
+/// line of JSON once the round is settled. The results of every round in the log are kept for
+/// the leaderboard. Without a log file the rounds are only kept in memory.
+#[derive(Debug)]
+pub struct HandHistory {
+    table_id: String,
+    rules: TableRules,
+    path: Option<PathBuf>,
+    rounds: u64,
+    current: Option<RoundRecord>,
+    /// Spans the round being played. Commands handled during the round follow from it.
+    span: Span,
+    results: PlayerResults,
+}
+
+impl HandHistory {
+    pub fn new(table_id: String, rules: TableRules, path: Option<PathBuf>) -> Self {
+        HandHistory {
+            table_id,
+            rules,
+            results: path.as_deref().map(PlayerResults::load).unwrap_or_default(),
+            path,
+            rounds: 0,
+            current: None,
+            span: Span::none(),
+        }
+    }
+
+    pub fn table_id(&self) -> &str {
+        &self.table_id
+    }
+
+    /// The round being played so far.
+    pub fn current(&self) -> Option<&RoundRecord> {
+        self.current.as_ref()
+    }
+
+    /// The number of rounds dealt at this table.
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    pub fn round_span(&self) -> Span {
+        self.span.clone()
+    }
+
+    /// Carries on the round numbers of a table saved when the server last stopped.
+    pub fn resume(&mut self, rounds: u64) {
+        self.rounds = rounds;
+    }
+
+    pub fn results(&self) -> &PlayerResults {
+        &self.results
+    }
+
+    /// Starts recording a round once the dealer has been dealt their cards.
+    pub fn start_round(&mut self, clients: &[Client], shoe: &Shoe, dealer_cards: &[Card]) {
+        self.rounds += 1;
+        let now = now();
+        self.span = info_span!(parent: None, "round", room = %self.table_id, round = self.rounds);
+        info!(parent: &self.span, seats = clients.len(), "Round dealt");
+
+        self.current = Some(RoundRecord {
+            table_id: self.table_id.clone(),
+            round: self.rounds,
+            rules: self.rules.clone(),
+            rules_hash: rules_hash(&self.rules),
+            shoe_id: shoe.id(),
+            commitment: shoe.commitment().to_owned(),
+            started_at: now,
+            finished_at: now,
+            seats: clients
+                .iter()
+                .map(|c| SeatRecord {
+                    position: c.position,
+                    user_name: c.player.user_name.clone(),
+                    chips: c.player.chips,
+                })
+                .collect(),
+            events: vec![RoundEvent {
+                at: now,
+                kind: RoundEventKind::DealerDealt {
+                    cards: dealer_cards.to_vec(),
+                },
+            }],
+            dealer_hand: vec![],
+            dealer_hand_value: 0,
+            settlements: vec![],
+        });
+    }
+
+    /// Adds an event to the round being played. Does nothing between rounds.
+    pub fn record(&mut self, kind: RoundEventKind) {
+        if let Some(round) = &mut self.current {
+            round.events.push(RoundEvent { at: now(), kind });
+        }
+    }
+
+    /// Whether the player has been dealt any cards this round.
+    pub fn has_cards(&self, user_name: &str) -> bool {
+        self.current.as_ref().is_some_and(|round| {
+            round.events.iter().any(|e| {
+                matches!(&e.kind, RoundEventKind::CardsDealt { user_name: name, .. } if name == user_name)
+            })
+        })
+    }
+
+    /// Whether the player has doubled their bet this round.
+    pub fn has_doubled(&self, user_name: &str) -> bool {
+        self.current.as_ref().is_some_and(|round| {
+            round.events.iter().any(|e| {
+                matches!(&e.kind, RoundEventKind::Double { user_name: name, .. } if name == user_name)
+            })
+        })
+    }
+
+    /// Whether the player has finished their turn this round.
+    pub fn has_stood(&self, user_name: &str) -> bool {
+        self.current.as_ref().is_some_and(|round| {
+            round.events.iter().any(|e| {
+                matches!(&e.kind, RoundEventKind::Stand { user_name: name, .. } if name == user_name)
+            })
+        })
+    }
+
+    /// Whether a round has been dealt and not yet settled.
+    pub fn in_round(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// The player whose turn it is, if a round is being played.
+    pub fn active_player(&self) -> Option<&str> {
+        self.current
+            .as_ref()?
+            .events
+            .iter()
+            .rev()
+            .find_map(|e| match &e.kind {
+                RoundEventKind::TurnStarted { user_name } => Some(user_name.as_str()),
+                _ => None,
+            })
+    }
+
+    pub fn settle(&mut self, player: &Player, end_state: &EndState) {
+        if let Some(round) = &mut self.current {
+            round.settlements.push(Settlement {
+                user_name: player.user_name.clone(),
+                hand: player.hand.clone(),
+                hand_value: player.hand_value,
+                bet: player.current_bet,
+                end_state: end_state.clone(),
+                chips: player.chips,
+            });
+        }
+    }
+
+    /// Throws away the round being played without logging it. Returns whether there was one.
+    pub fn void_round(&mut self) -> bool {
+        let voided = self.current.take().is_some();
+        if voided {
+            info!(parent: &self.span, "Round voided");
+            metrics::round_voided();
+        }
+        self.span = Span::none();
+        voided
+    }
+
+    /// Appends the finished round to the log.
+    pub fn finish_round(&mut self, dealer: &Player) {
+        let Some(mut round) = self.current.take() else {
+            return;
+        };
+        round.dealer_hand = dealer.hand.clone();
+        round.dealer_hand_value = dealer.hand_value;
+        round.finished_at = now();
+
+        self.append(&serde_json::to_string(&round).unwrap(), "round");
+        info!(
+            parent: &self.span,
+            dealer_hand_value = round.dealer_hand_value,
+            hands = round.settlements.len(),
+            "Round settled"
+        );
+        self.span = Span::none();
+        metrics::round_finished(&round);
+        self.results.add_round(&round);
+    }
+
+    /// Appends the seeds of a shoe that has just been revealed to the log. Rounds only hold the
+    /// commitment, so the log can't be used to work out the cards still to come.
+    pub fn reveal_shoe(&mut self, shoe: &FinishedShoe) {
+        let reveal = ShoeReveal {
+            table_id: self.table_id.clone(),
+            shoe_id: shoe.id,
+            server_seed: hex::encode(shoe.server_seed),
+            client_seeds: shoe.client_seeds.clone(),
+            dealt: shoe.dealt,
+            revealed_at: now(),
+        };
+        self.append(&serde_json::to_string(&reveal).unwrap(), "shoe reveal");
+    }
+
+    /// Appends a line to the log, if there is one.
+    fn append(&self, line: &str, what: &str) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", line));
+
+        if let Err(e) = result {
+            error!(
+                parent: &self.span,
+                error = %e,
+                path = %path.display(),
+                "Couldn't write the {} to the hand history",
+                what
+            );
+        }
+    }
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use blackjack_shared::{
+    history::{parse_history, RoundRecord},
+    leaderboard::*,
+    web_socket::EndState,
+};
+
+/// The most entries returned in one page of the leaderboard.
+pub const MAX_PER_PAGE: usize = 100;
+
+/// One settled hand, kept so results can be totalled over any window of time.
+#[derive(Debug, Clone)]
+struct HandResult {
+    user_name: String,
+    table_id: String,
+    finished_at: u64,
+    bet: u32,
+    end_state: EndState,
+    chips: u32,
+}
+
+/// The result of every hand in the hand history, across every table that writes to it.
+#[derive(Debug, Default)]
+pub struct PlayerResults {
+    /// In the order the hands finished.
+    hands: Vec<HandResult>,
+}
+
+/// A player's results added up over a window.
+#[derive(Debug, Default)]
+struct Totals {
+    user_name: String,
+    hands: u32,
+    wins: u32,
+    losses: u32,
+    pushes: u32,
+    blackjacks: u32,
+    net_chips: i64,
+    bankroll: u32,
+    biggest_win: u32,
+    biggest_loss: u32,
+    tables: Vec<String>,
+}
+
+impl PlayerResults {
+    /// Reads the results of every round already in the hand history log. A missing log just
+    /// means nothing has been played yet.
+    pub fn load(path: &Path) -> Self {
+        let mut results = PlayerResults::default();
+        if let Ok(log) = fs::read_to_string(path) {
+            parse_history(&log)
+                .iter()
+                .for_each(|round| results.add_round(round));
+        }
+        results
+    }
+
+    pub fn add_round(&mut self, round: &RoundRecord) {
+        self.hands
+            .extend(round.settlements.iter().map(|s| HandResult {
+                user_name: s.user_name.clone(),
+                table_id: round.table_id.clone(),
+                finished_at: round.finished_at,
+                bet: s.bet,
+                end_state: s.end_state.clone(),
+                chips: s.chips,
+            }));
+    }
+
+    /// Ranks every player with results in the window by the chips they've won, then returns the
+    /// requested page.
+    pub fn leaderboard(&self, query: &LeaderboardQuery, now: u64) -> LeaderboardResponse {
+        let page = query.page.max(1);
+        let per_page = query.per_page.clamp(1, MAX_PER_PAGE);
+
+        let mut players: Vec<Totals> = self.totals(query.window, now).into_values().collect();
+        players.sort_by(|a, b| {
+            b.net_chips
+                .cmp(&a.net_chips)
+                .then(b.hands.cmp(&a.hands))
+                .then(a.user_name.cmp(&b.user_name))
+        });
+
+        let entries = players
+            .iter()
+            .enumerate()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .map(|(i, t)| LeaderboardEntry {
+                rank: i + 1,
+                user_name: t.user_name.clone(),
+                net_chips: t.net_chips,
+                bankroll: t.bankroll,
+                biggest_win: t.biggest_win,
+                hands: t.hands,
+            })
+            .collect();
+
+        LeaderboardResponse {
+            window: query.window,
+            page,
+            per_page,
+            total: players.len(),
+            entries,
+        }
+    }
+
+    /// Returns `None` if the player has never finished a hand. Names are matched ignoring case.
+    pub fn player_stats(
+        &self,
+        user_name: &str,
+        window: TimeWindow,
+        now: u64,
+    ) -> Option<PlayerStatsResponse> {
+        let key = user_name.to_lowercase();
+        let user_name = self
+            .hands
+            .iter()
+            .rev()
+            .find(|h| h.user_name.to_lowercase() == key)?
+            .user_name
+            .clone();
+
+        let totals = self.totals(window, now).remove(&key).unwrap_or_default();
+        Some(PlayerStatsResponse {
+            user_name,
+            window,
+            hands: totals.hands,
+            wins: totals.wins,
+            losses: totals.losses,
+            pushes: totals.pushes,
+            blackjacks: totals.blackjacks,
+            net_chips: totals.net_chips,
+            bankroll: totals.bankroll,
+            biggest_win: totals.biggest_win,
+            biggest_loss: totals.biggest_loss,
+            tables: totals.tables,
+        })
+    }
+
+    /// Adds up each player's hands in the window, keyed by their lowercase name.
+    fn totals(&self, window: TimeWindow, now: u64) -> HashMap<String, Totals> {
+        let start = window.start(now);
+        let mut totals: HashMap<String, Totals> = HashMap::new();
+
+        for hand in self.hands.iter().filter(|h| h.finished_at >= start) {
+            let t = totals
+                .entry(hand.user_name.to_lowercase())
+                .or_insert_with(|| Totals {
+                    user_name: hand.user_name.clone(),
+                    ..Default::default()
+                });
+
+            t.hands += 1;
+            match hand.end_state {
+                EndState::Win => t.wins += 1,
+                EndState::Loss => t.losses += 1,
+                EndState::Push => t.pushes += 1,
+                EndState::Blackjack => t.blackjacks += 1,
+            }
+
+            let won = hand.end_state.net_chips(hand.bet);
+            t.net_chips += won;
+            if won > 0 {
+                t.biggest_win = t.biggest_win.max(won as u32);
+            } else {
+                t.biggest_loss = t.biggest_loss.max(won.unsigned_abs() as u32);
+            }
+
+            // Hands are kept in order so the last one seen is the latest.
+            t.bankroll = hand.chips;
+            if !t.tables.contains(&hand.table_id) {
+                t.tables.push(hand.table_id.clone());
+            }
+        }
+
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blackjack_shared::{
+        history::{RoundRecord, Settlement},
+        rules::TableRules,
+    };
+
+    use super::*;
+
+    const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+    /// Noon on the tenth day after the epoch.
+    const NOW: u64 = 10 * DAY_MS + DAY_MS / 2;
+
+    /// A round at the table finishing at the given time, where each player bet ten chips.
+    fn round(finished_at: u64, results: &[(&str, EndState)]) -> RoundRecord {
+        RoundRecord {
+            table_id: "table".to_owned(),
+            round: 1,
+            rules: TableRules::default(),
+            rules_hash: String::new(),
+            shoe_id: 1,
+            commitment: String::new(),
+            started_at: finished_at,
+            finished_at,
+            seats: vec![],
+            events: vec![],
+            dealer_hand: vec![],
+            dealer_hand_value: 0,
+            settlements: results
+                .iter()
+                .map(|(user_name, end_state)| Settlement {
+                    user_name: user_name.to_string(),
+                    hand: vec![],
+                    hand_value: 0,
+                    bet: 10,
+                    end_state: end_state.clone(),
+                    chips: 500,
+                })
+                .collect(),
+        }
+    }
+
+    fn query(window: TimeWindow, page: usize, per_page: usize) -> LeaderboardQuery {
+        LeaderboardQuery {
+            window,
+            page,
+            per_page,
+        }
+    }
+
+    fn names(response: &LeaderboardResponse) -> Vec<(usize, &str)> {
+        response
+            .entries
+            .iter()
+            .map(|e| (e.rank, e.user_name.as_str()))
+            .collect()
+    }
+
+    /// Five players who each won a different number of chips in one round.
+    fn five_players() -> PlayerResults {
+        let mut results = PlayerResults::default();
+        results.add_round(&round(
+            NOW,
+            &[
+                ("Al", EndState::Loss),
+                ("Bo", EndState::Blackjack),
+                ("Cy", EndState::Push),
+                ("Di", EndState::Win),
+                ("Ed", EndState::Loss),
+            ],
+        ));
+        results
+    }
+
+    #[test]
+    fn pages_count_from_one() {
+        let results = five_players();
+
+        let first = results.leaderboard(&query(TimeWindow::AllTime, 1, 2), NOW);
+        assert_eq!(names(&first), [(1, "Bo"), (2, "Di")]);
+        assert_eq!(first.total, 5);
+
+        let last = results.leaderboard(&query(TimeWindow::AllTime, 3, 2), NOW);
+        assert_eq!(names(&last), [(5, "Ed")]);
+
+        // Page zero is read as the first page.
+        let zero = results.leaderboard(&query(TimeWindow::AllTime, 0, 2), NOW);
+        assert_eq!(zero.page, 1);
+        assert_eq!(names(&zero), names(&first));
+    }
+
+    #[test]
+    fn pages_past_the_end_are_empty() {
+        let results = five_players();
+
+        let past = results.leaderboard(&query(TimeWindow::AllTime, 4, 2), NOW);
+        assert!(past.entries.is_empty());
+        assert_eq!(past.total, 5);
+
+        let huge = results.leaderboard(&query(TimeWindow::AllTime, usize::MAX, MAX_PER_PAGE), NOW);
+        assert!(huge.entries.is_empty());
+    }
+
+    #[test]
+    fn page_size_is_kept_within_the_limits() {
+        let results = five_players();
+
+        assert_eq!(
+            results
+                .leaderboard(&query(TimeWindow::AllTime, 1, 0), NOW)
+                .per_page,
+            1
+        );
+        assert_eq!(
+            results
+                .leaderboard(&query(TimeWindow::AllTime, 1, 10_000), NOW)
+                .per_page,
+            MAX_PER_PAGE
+        );
+    }
+
+    #[test]
+    fn windows_only_count_hands_since_their_start() {
+        let mut results = PlayerResults::default();
+        let midnight = NOW - DAY_MS / 2;
+        results.add_round(&round(midnight - 1, &[("Yesterday", EndState::Win)]));
+        results.add_round(&round(midnight, &[("Today", EndState::Win)]));
+        results.add_round(&round(NOW - 7 * DAY_MS - 1, &[("Old", EndState::Win)]));
+        results.add_round(&round(NOW - 7 * DAY_MS, &[("Week", EndState::Win)]));
+
+        let players = |window| {
+            let mut names: Vec<String> = results
+                .leaderboard(&query(window, 1, MAX_PER_PAGE), NOW)
+                .entries
+                .into_iter()
+                .map(|e| e.user_name)
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(players(TimeWindow::Today), ["Today"]);
+        assert_eq!(players(TimeWindow::Week), ["Today", "Week", "Yesterday"]);
+        assert_eq!(
+            players(TimeWindow::AllTime),
+            ["Old", "Today", "Week", "Yesterday"]
+        );
+    }
+}
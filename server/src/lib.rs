@@ -0,0 +1,41 @@
+pub mod admin;
+pub mod bot;
+pub mod card;
+pub mod client;
+pub mod config;
+pub mod game;
+pub mod handlers;
+pub mod history;
+pub mod leaderboard;
+pub mod log;
+pub mod metrics;
+pub mod outbox;
+pub mod shutdown;
+pub mod tournament;
+
+use std::sync::Arc;
+
+use blackjack_shared::player::Player;
+use tokio::sync::Mutex;
+
+use crate::{
+    admin::{AdminState, AuditLog},
+    card::Shoe,
+    client::Client,
+    config::ServerConfig,
+    history::HandHistory,
+    shutdown::SavedSeat,
+    tournament::Tournament,
+};
+
+pub type Clients = Arc<Mutex<Vec<Client>>>;
+pub type Dealer = Arc<Mutex<Player>>;
+pub type SharedShoe = Arc<Mutex<Shoe>>;
+pub type Config = Arc<ServerConfig>;
+pub type History = Arc<Mutex<HandHistory>>;
+pub type Audit = Arc<Mutex<AuditLog>>;
+pub type SharedAdmin = Arc<Mutex<AdminState>>;
+/// Seats saved when the server last stopped, held until their players register again.
+pub type Reserved = Arc<Mutex<Vec<SavedSeat>>>;
+/// `None` unless the table is running a tournament.
+pub type SharedTournament = Arc<Mutex<Option<Tournament>>>;
@@ -0,0 +1,93 @@
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing_subscriber::{prelude::*, reload, EnvFilter, Registry};
+
+/// How much the server logs. Each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    /// Also logs every message sent and received, with the cards redacted.
+    Debug,
+}
+
+impl LogLevel {
+    /// The filter for the level. Other crates only log their warnings and errors.
+    fn filter(self) -> EnvFilter {
+        let level = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        };
+        let dependencies = if self > LogLevel::Warn { "warn" } else { level };
+        EnvFilter::new(format!("{},blackjack_server={}", dependencies, level))
+    }
+}
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Readable lines of text, coloured on a terminal.
+    Pretty,
+    /// A JSON object per line with the fields of every span, for collecting logs.
+    Json,
+}
+
+static RELOAD: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Starts logging to stdout. Only the first call has any effect.
+pub fn init(level: LogLevel, format: LogFormat) {
+    let (filter, handle) = reload::Layer::new(level.filter());
+    if RELOAD.set(handle).is_err() {
+        return;
+    }
+
+    let registry = tracing_subscriber::registry().with(filter);
+    match format {
+        LogFormat::Pretty => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .init(),
+    }
+}
+
+/// Changes the level while the server is running.
+pub fn set_level(level: LogLevel) -> Result<(), String> {
+    let handle = RELOAD.get().ok_or("logging hasn't been started")?;
+    handle
+        .reload(level.filter())
+        .map_err(|e| format!("couldn't change the log level: {}", e))
+}
+
+/// Writes a message as JSON for the logs with every card replaced by `**`, so the dealer's hole
+/// card and the cards still to come never end up in a log file.
+pub fn redacted(message: &impl Serialize) -> String {
+    let mut value = serde_json::to_value(message).unwrap_or(Value::Null);
+    redact_cards(&mut value);
+    value.to_string()
+}
+
+fn redact_cards(value: &mut Value) {
+    match value {
+        Value::Object(fields)
+            if fields.len() == 2 && fields.contains_key("suit") && fields.contains_key("rank") =>
+        {
+            *value = Value::String("**".to_owned());
+        }
+        Value::Object(fields) => fields.values_mut().for_each(redact_cards),
+        Value::Array(items) => items.iter_mut().for_each(redact_cards),
+        _ => {}
+    }
+}
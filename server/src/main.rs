@@ -1,23 +1,202 @@
-mod card;
-mod client;
-mod game;
-mod handlers;
+use std::{convert::Infallible, fs, net::SocketAddr, path::PathBuf, sync::Arc};
 
-use std::{convert::Infallible, sync::Arc};
-
-use blackjack_shared::player::{Player, PlayerType};
+use blackjack_server::{
+    admin::{self, AdminState, AuditLog, Caller},
+    bot::restore_bot,
+    card::Shoe,
+    config::{RoundPolicy, ServerConfig, StorageBackend, TlsConfig},
+    handlers,
+    history::HandHistory,
+    log::{self, LogFormat, LogLevel},
+    metrics,
+    shutdown::{self, TableSnapshot},
+    tournament::Tournament,
+    Audit, Clients, Config, Dealer, History, Reserved, SharedAdmin, SharedShoe, SharedTournament,
+};
+use blackjack_shared::{
+    card::Card,
+    leaderboard::{LeaderboardQuery, PlayerStatsQuery},
+    player::{Player, PlayerType},
+    tournament::{Elimination, TournamentConfig},
+};
+use clap::Parser;
 use color_eyre::eyre::*;
+use rand::{thread_rng, Rng};
 use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use uuid::Uuid;
 use warp::Filter;
 
-use crate::client::Client;
+#[derive(Debug, Parser)]
+#[command(about = "The blackjack game server")]
+struct Args {
+    /// Read the server settings from this TOML file. The options below override it.
+    #[arg(long, value_name = "FILE", env = "BLACKJACK_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// The address to listen on, such as `0.0.0.0:8000`.
+    #[arg(long, env = "BLACKJACK_BIND")]
+    bind: Option<SocketAddr>,
+
+    /// The address players reach the server at, used to build the web socket urls handed out at
+    /// registration.
+    #[arg(long, value_name = "URL", env = "BLACKJACK_PUBLIC_URL")]
+    public_url: Option<String>,
+
+    #[arg(long, env = "BLACKJACK_LOG_LEVEL")]
+    log_level: Option<LogLevel>,
+
+    /// Write logs as readable text or as a JSON object per line.
+    #[arg(long, env = "BLACKJACK_LOG_FORMAT")]
+    log_format: Option<LogFormat>,
+
+    /// The number of decks in the shoe.
+    #[arg(long, env = "BLACKJACK_DECKS")]
+    decks: Option<u8>,
+
+    /// Whether the dealer hits a soft 17, `true` or `false`.
+    #[arg(long, value_name = "BOOL", env = "BLACKJACK_DEALER_HITS_SOFT_17")]
+    dealer_hits_soft_17: Option<bool>,
+
+    /// The smallest opening bet.
+    #[arg(long, value_name = "CHIPS", env = "BLACKJACK_MIN_BET")]
+    min_bet: Option<u32>,
+
+    /// The largest opening bet.
+    #[arg(long, value_name = "CHIPS", env = "BLACKJACK_MAX_BET")]
+    max_bet: Option<u32>,
+
+    /// The most seats at the table, bots included.
+    #[arg(long, value_name = "SEATS", env = "BLACKJACK_MAX_PLAYERS")]
+    max_players: Option<usize>,
+
+    /// Where to keep the hand history.
+    #[arg(long, env = "BLACKJACK_STORAGE")]
+    storage: Option<StorageBackend>,
+
+    /// Serve over TLS with this PEM certificate chain.
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "BLACKJACK_TLS_CERT",
+        requires = "tls_key"
+    )]
+    tls_cert: Option<PathBuf>,
+
+    /// The PEM private key for `--tls-cert`.
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "BLACKJACK_TLS_KEY",
+        requires = "tls_cert"
+    )]
+    tls_key: Option<PathBuf>,
+
+    /// Turn on the admin API, which expects this bearer token.
+    #[arg(
+        long,
+        value_name = "TOKEN",
+        env = "BLACKJACK_ADMIN_TOKEN",
+        hide_env_values = true
+    )]
+    admin_token: Option<String>,
+
+    /// What to do with the round being played when the server is stopped.
+    #[arg(long, env = "BLACKJACK_SHUTDOWN_POLICY")]
+    shutdown_policy: Option<RoundPolicy>,
+
+    /// Where the table is saved when the server stops, and restored from when it starts.
+    #[arg(long, value_name = "FILE", env = "BLACKJACK_STATE_FILE")]
+    state_file: Option<PathBuf>,
+
+    /// Where to append the record of admin requests.
+    #[arg(long, value_name = "FILE", env = "BLACKJACK_AUDIT_LOG")]
+    audit_log: Option<PathBuf>,
+
+    /// Shuffle the shoe from this seed so every game deals the same cards. A random seed is
+    /// used and logged when this is left out.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Deal these cards in order before shuffling as normal. The file lists cards such as `AS`
+    /// or `10h`, separated by whitespace or commas.
+    #[arg(long, value_name = "FILE")]
+    deck_file: Option<PathBuf>,
+
+    /// Identifies this table in the hand history. A random id is used when this is left out.
+    #[arg(long)]
+    table_id: Option<String>,
 
-type Clients = Arc<Mutex<Vec<Client>>>;
-type Dealer = Arc<Mutex<Player>>;
+    /// Every round is appended to this file as a line of JSON when the storage is `file`.
+    #[arg(long, value_name = "FILE", env = "BLACKJACK_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+
+    /// Run a tournament of this many hands instead of playing until everyone is out of chips.
+    #[arg(long, value_name = "HANDS")]
+    tournament_hands: Option<u32>,
+
+    /// The chips every tournament player starts with.
+    #[arg(long, default_value_t = 1000, requires = "tournament_hands")]
+    starting_chips: u32,
+
+    /// Knock out everyone outside the top KEEP chip counts after the given hand, written as
+    /// `HAND:KEEP`. Can be given more than once.
+    #[arg(long, value_name = "HAND:KEEP", requires = "tournament_hands")]
+    elimination: Vec<Elimination>,
+
+    /// Show bets on the final tournament hand as they're placed rather than keeping them secret
+    /// until the hand is settled.
+    #[arg(long, requires = "tournament_hands")]
+    open_final_bet: bool,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
+    let args = Args::parse();
+
+    let mut config = match &args.config {
+        Some(path) => ServerConfig::load(path).map_err(|e| eyre!(e))?,
+        None => ServerConfig::default(),
+    };
+    config.bind = args.bind.unwrap_or(config.bind);
+    config.public_url = args.public_url.or(config.public_url);
+    config.log_level = args.log_level.unwrap_or(config.log_level);
+    config.log_format = args.log_format.unwrap_or(config.log_format);
+    config.rules.decks = args.decks.unwrap_or(config.rules.decks);
+    config.rules.dealer_hits_soft_17 = args
+        .dealer_hits_soft_17
+        .unwrap_or(config.rules.dealer_hits_soft_17);
+    config.rules.min_bet = args.min_bet.unwrap_or(config.rules.min_bet);
+    config.rules.max_bet = args.max_bet.or(config.rules.max_bet);
+    config.limits.max_players = args.max_players.or(config.limits.max_players);
+    config.storage.backend = args.storage.unwrap_or(config.storage.backend);
+    config.storage.path = args.history_file.unwrap_or(config.storage.path);
+    if let (Some(cert), Some(key)) = (args.tls_cert, args.tls_key) {
+        config.tls = Some(TlsConfig { cert, key });
+    }
+    config.admin.token = args.admin_token.or(config.admin.token);
+    config.admin.audit_log = args.audit_log.unwrap_or(config.admin.audit_log);
+    config.shutdown.round_policy = args.shutdown_policy.unwrap_or(config.shutdown.round_policy);
+    config.shutdown.state_file = args.state_file.unwrap_or(config.shutdown.state_file);
+    config.validate().map_err(|e| eyre!(e))?;
+
+    log::init(config.log_level, config.log_format);
+
+    let mut snapshot = TableSnapshot::load(&config.shutdown.state_file).map_err(|e| eyre!(e))?;
+    if let (Some(saved), Some(table_id)) = (&snapshot, &args.table_id) {
+        if saved.table_id != *table_id {
+            warn!(
+                "Not restoring table {} from {} into table {}",
+                saved.table_id,
+                config.shutdown.state_file.display(),
+                table_id
+            );
+            snapshot = None;
+        }
+    }
+    let config: Config = Arc::new(config);
+    let metrics_handle = metrics::install();
 
     let clients: Clients = Arc::new(Mutex::new(vec![]));
     let dealer: Dealer = Arc::new(Mutex::new(Player {
@@ -29,11 +208,105 @@ async fn main() -> Result<()> {
         current_bet: 0,
     }));
 
+    // The seed gives away every shoe the server will deal, so a random one is never logged.
+    if let Some(seed) = args.seed {
+        info!("Using shoe seed {}", seed);
+    }
+    let seed = args.seed.unwrap_or_else(|| thread_rng().gen());
+    let shoe = match &args.deck_file {
+        Some(path) => Shoe::stacked(config.rules.decks, seed, read_deck_file(path)?),
+        None => Shoe::seeded(config.rules.decks, seed),
+    };
+    let shoe: SharedShoe = Arc::new(Mutex::new(shoe));
+
+    let table_id = args
+        .table_id
+        .or_else(|| snapshot.as_ref().map(|s| s.table_id.clone()))
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+    let history_path = config.history_path().map(|p| p.to_path_buf());
+    match &history_path {
+        Some(path) => info!("Recording table {} to {}", table_id, path.display()),
+        None => info!("Keeping the history of table {} in memory", table_id),
+    }
+    let mut hand_history = HandHistory::new(table_id, config.rules.clone(), history_path);
+    if let Some(snapshot) = &snapshot {
+        hand_history.resume(snapshot.rounds);
+    }
+    let history: History = Arc::new(Mutex::new(hand_history));
+
+    let saved_tournament = snapshot.as_mut().and_then(|s| s.tournament.take());
+    let tournament: SharedTournament = Arc::new(Mutex::new(saved_tournament.or_else(|| {
+        args.tournament_hands.map(|hands| {
+            info!("Running a tournament of {} hands", hands);
+            Tournament::new(TournamentConfig {
+                hands,
+                starting_chips: args.starting_chips,
+                eliminations: args.elimination,
+                secret_final_bet: !args.open_final_bet,
+            })
+        })
+    })));
+
+    let bans = snapshot
+        .as_mut()
+        .map(|s| std::mem::take(&mut s.bans))
+        .unwrap_or_default();
+    let admin_state: SharedAdmin = Arc::new(Mutex::new(AdminState::with_bans(bans)));
+    let audit: Audit = Arc::new(Mutex::new(AuditLog::new(config.admin.audit_log.clone())));
+    if config.admin.token.is_some() {
+        info!(
+            "Admin API enabled, auditing to {}",
+            config.admin.audit_log.display()
+        );
+    }
+
+    // Bots take their seats again straight away. Everyone else's seat is held until they
+    // register under the same name.
+    let reserved: Reserved = Arc::new(Mutex::new(vec![]));
+    if let Some(snapshot) = snapshot {
+        info!(
+            "Restoring table {} with {} seats after {} rounds",
+            snapshot.table_id,
+            snapshot.seats.len(),
+            snapshot.rounds
+        );
+        for seat in snapshot.seats {
+            if seat.bot.is_none() {
+                reserved.lock().await.push(seat);
+                continue;
+            }
+            let user_name = seat.user_name.clone();
+            let restored = restore_bot(
+                seat,
+                clients.clone(),
+                dealer.clone(),
+                shoe.clone(),
+                config.clone(),
+                history.clone(),
+                tournament.clone(),
+                admin_state.clone(),
+            )
+            .await;
+            if restored.is_none() {
+                warn!("Couldn't restore the bot {}", user_name);
+            }
+        }
+        // The table is saved again when the server next stops. Until then a crash shouldn't bring
+        // back the chips as they were.
+        if let Err(e) = fs::remove_file(&config.shutdown.state_file) {
+            error!(error = %e, "Couldn't remove the saved table");
+        }
+    }
+
     let register = warp::path("register");
     let register_routes = register
         .and(warp::post())
         .and(warp::body::json())
         .and(with_clients(clients.clone()))
+        .and(with_config(config.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and(with_reserved(reserved.clone()))
         .and_then(handlers::register_handler)
         .or(register
             .and(warp::delete())
@@ -41,28 +314,222 @@ async fn main() -> Result<()> {
             .and(with_clients(clients.clone()))
             .and_then(handlers::unregister_handler));
 
-    let publish = warp::path!("publish")
+    let announcements = warp::path!("admin" / "announcements")
+        .and(admin_caller())
+        .and(warp::post())
         .and(warp::body::json())
         .and(with_clients(clients.clone()))
-        .and_then(handlers::publish_handler);
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and_then(admin::announcement_handler);
+    let end_round = warp::path!("admin" / "rounds" / "end")
+        .and(admin_caller())
+        .and(warp::post())
+        .and(with_clients(clients.clone()))
+        .and(with_dealer(dealer.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and_then(admin::end_round_handler);
+    let tables = warp::path!("admin" / "tables")
+        .and(admin_caller())
+        .and(warp::get())
+        .and(with_clients(clients.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::tables_handler);
+    let table = warp::path!("admin" / "tables" / String)
+        .and(admin_caller())
+        .and(warp::get())
+        .and(with_clients(clients.clone()))
+        .and(with_dealer(dealer.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::table_handler);
+    let close = warp::path!("admin" / "tables" / String / "close")
+        .and(admin_caller())
+        .and(warp::post())
+        .and(with_clients(clients.clone()))
+        .and(with_dealer(dealer.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::close_handler);
+    let kick = warp::path!("admin" / "tables" / String / "seats" / String / "kick")
+        .and(admin_caller())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_clients(clients.clone()))
+        .and(with_dealer(dealer.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::kick_handler);
+    let chips = warp::path!("admin" / "tables" / String / "seats" / String / "chips")
+        .and(admin_caller())
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_clients(clients.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and_then(admin::chips_handler);
+    let drain = warp::path!("admin" / "drain")
+        .and(admin_caller())
+        .and(warp::post())
+        .and(with_clients(clients.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::drain_handler);
+    let bans = warp::path!("admin" / "bans")
+        .and(admin_caller())
+        .and(warp::get())
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(admin::bans_handler)
+        .or(warp::path!("admin" / "bans")
+            .and(admin_caller())
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(with_config(config.clone()))
+            .and(with_audit(audit.clone()))
+            .and(with_admin(admin_state.clone()))
+            .and_then(admin::ban_handler))
+        .or(warp::path!("admin" / "bans" / String)
+            .and(admin_caller())
+            .and(warp::delete())
+            .and(with_config(config.clone()))
+            .and(with_audit(audit.clone()))
+            .and(with_admin(admin_state.clone()))
+            .and_then(admin::unban_handler));
+    let log_level = warp::path!("admin" / "log-level")
+        .and(admin_caller())
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(with_config(config.clone()))
+        .and(with_audit(audit.clone()))
+        .and_then(admin::log_level_handler);
+    let admin_routes = announcements
+        .or(end_round)
+        .or(tables)
+        .or(table)
+        .or(close)
+        .or(kick)
+        .or(chips)
+        .or(drain)
+        .or(bans)
+        .or(log_level);
 
     let ws_route = warp::path("ws")
         .and(warp::ws())
         .and(warp::path::param())
         .and(with_clients(clients.clone()))
         .and(with_dealer(dealer.clone()))
+        .and(with_shoe(shoe.clone()))
+        .and(with_config(config.clone()))
+        .and(with_history(history.clone()))
+        .and(with_tournament(tournament.clone()))
+        .and(with_admin(admin_state.clone()))
         .and_then(handlers::ws_handler);
 
+    let leaderboard = warp::path!("leaderboard")
+        .and(warp::get())
+        .and(warp::query::<LeaderboardQuery>())
+        .and(with_history(history.clone()))
+        .and_then(handlers::leaderboard_handler);
+
+    let player_stats = warp::path!("players" / String / "stats")
+        .and(warp::get())
+        .and(warp::query::<PlayerStatsQuery>())
+        .and(with_history(history.clone()))
+        .and_then(handlers::player_stats_handler);
+
+    let metrics_route = warp::path!("metrics")
+        .and(warp::get())
+        .and(warp::any().map(move || metrics_handle.clone()))
+        .and(with_clients(clients.clone()))
+        .and(with_history(history.clone()))
+        .and(with_admin(admin_state.clone()))
+        .and_then(metrics::metrics_handler);
+
     let routes = ws_route
-        .or(publish)
+        .or(admin_routes)
+        .or(metrics_route)
+        .or(leaderboard)
+        .or(player_stats)
         .or(register_routes)
-        .with(warp::cors().allow_any_origin());
+        .with(warp::cors().allow_any_origin())
+        .with(warp::log::custom(metrics::http_request))
+        .with(warp::trace::request());
 
-    warp::serve(routes).run(([127, 0, 0, 1], 8000)).await;
+    let stop = {
+        let config = config.clone();
+        async move {
+            shutdown::signal().await;
+            shutdown::shut_down(
+                clients,
+                dealer,
+                shoe,
+                config,
+                history,
+                tournament,
+                admin_state,
+                reserved,
+            )
+            .await;
+        }
+    };
+
+    info!("Listening on {} as {}", config.bind, config.public_url());
+    match &config.tls {
+        Some(tls) => {
+            let (_, server) = warp::serve(routes)
+                .tls()
+                .cert_path(&tls.cert)
+                .key_path(&tls.key)
+                .bind_with_graceful_shutdown(config.bind, stop);
+            server.await
+        }
+        None => {
+            let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(config.bind, stop);
+            server.await
+        }
+    }
+    info!("Stopped");
 
     Ok(())
 }
 
+/// Reads a pre-arranged sequence of cards to deal.
+fn read_deck_file(path: &PathBuf) -> Result<Vec<Card>> {
+    fs::read_to_string(path)?
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<Card>().map_err(|e| eyre!(e)))
+        .collect()
+}
+
 fn with_clients(clients: Clients) -> impl Filter<Extract = (Clients,), Error = Infallible> + Clone {
     warp::any().map(move || clients.clone())
 }
@@ -70,3 +537,49 @@ fn with_clients(clients: Clients) -> impl Filter<Extract = (Clients,), Error = I
 fn with_dealer(dealer: Dealer) -> impl Filter<Extract = (Dealer,), Error = Infallible> + Clone {
     warp::any().map(move || dealer.clone())
 }
+
+fn with_shoe(shoe: SharedShoe) -> impl Filter<Extract = (SharedShoe,), Error = Infallible> + Clone {
+    warp::any().map(move || shoe.clone())
+}
+
+fn with_config(config: Config) -> impl Filter<Extract = (Config,), Error = Infallible> + Clone {
+    warp::any().map(move || config.clone())
+}
+
+fn with_history(history: History) -> impl Filter<Extract = (History,), Error = Infallible> + Clone {
+    warp::any().map(move || history.clone())
+}
+
+fn with_tournament(
+    tournament: SharedTournament,
+) -> impl Filter<Extract = (SharedTournament,), Error = Infallible> + Clone {
+    warp::any().map(move || tournament.clone())
+}
+
+fn with_audit(audit: Audit) -> impl Filter<Extract = (Audit,), Error = Infallible> + Clone {
+    warp::any().map(move || audit.clone())
+}
+
+fn with_reserved(
+    reserved: Reserved,
+) -> impl Filter<Extract = (Reserved,), Error = Infallible> + Clone {
+    warp::any().map(move || reserved.clone())
+}
+
+fn with_admin(
+    admin: SharedAdmin,
+) -> impl Filter<Extract = (SharedAdmin,), Error = Infallible> + Clone {
+    warp::any().map(move || admin.clone())
+}
+
+/// Who sent an admin request, for checking the token and the audit log.
+fn admin_caller() -> impl Filter<Extract = (Caller,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("authorization")
+        .or(warp::any().map(|| None))
+        .unify()
+        .and(warp::addr::remote())
+        .map(|authorization, remote| Caller {
+            authorization,
+            remote,
+        })
+}
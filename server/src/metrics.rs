@@ -0,0 +1,297 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex as StdMutex,
+    time::{Duration, Instant},
+};
+
+use ::metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use blackjack_shared::{history::RoundRecord, web_socket::EndState};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use warp::{
+    http::header::CONTENT_TYPE,
+    reject::Rejection,
+    reply::{with_header, Reply},
+};
+
+use crate::{Clients, History, SharedAdmin};
+
+/// Latency buckets in seconds, from half a millisecond up to a couple of seconds.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Queue depth buckets in messages, up to the default queue capacity.
+const DEPTH_BUCKETS: [f64; 9] = [1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0];
+
+/// When each of the rounds finished in the last minute, for the rounds per minute gauge.
+static RECENT_ROUNDS: StdMutex<VecDeque<Instant>> = StdMutex::new(VecDeque::new());
+
+/// Installs the Prometheus recorder and describes the metrics. Until this is called, as in the
+/// simulator, recording a metric does nothing.
+pub fn install() -> PrometheusHandle {
+    let handle = PrometheusBuilder::new()
+        .set_buckets(&LATENCY_BUCKETS)
+        .unwrap()
+        .set_buckets_for_metric(
+            Matcher::Full("blackjack_ws_queue_depth".to_owned()),
+            &DEPTH_BUCKETS,
+        )
+        .unwrap()
+        .install_recorder()
+        .expect("the metrics recorder is only installed once");
+
+    describe_gauge!(
+        "blackjack_connected_clients",
+        "Players with a web socket open."
+    );
+    describe_gauge!("blackjack_bots", "Bots seated by the server.");
+    describe_gauge!(
+        "blackjack_active_rooms",
+        "Open tables with at least one seat taken."
+    );
+    describe_gauge!(
+        "blackjack_rounds_in_progress",
+        "Rounds dealt and not yet settled."
+    );
+    describe_counter!("blackjack_rounds_total", "Rounds settled.");
+    describe_counter!(
+        "blackjack_rounds_voided_total",
+        "Rounds called off by an operator."
+    );
+    describe_gauge!(
+        "blackjack_rounds_per_minute",
+        "Rounds settled in the last minute."
+    );
+    describe_counter!("blackjack_hands_total", "Hands settled, by outcome.");
+    describe_gauge!(
+        "blackjack_house_profit_chips",
+        "Chips won by the house less chips paid out, since the server started."
+    );
+    describe_counter!(
+        "blackjack_messages_total",
+        "Messages received over web sockets, by command."
+    );
+    describe_counter!(
+        "blackjack_messages_ignored_total",
+        "Commands ignored because they were sent out of turn."
+    );
+    describe_counter!(
+        "blackjack_ws_send_errors_total",
+        "Messages that couldn't be sent to a client."
+    );
+    describe_counter!(
+        "blackjack_ws_timeouts_total",
+        "Connections dropped for not answering pings."
+    );
+    describe_gauge!(
+        "blackjack_ws_queued_messages",
+        "Messages waiting to be sent to clients."
+    );
+    describe_histogram!(
+        "blackjack_ws_queue_depth",
+        "Messages waiting for a client, measured each time one is queued."
+    );
+    describe_counter!(
+        "blackjack_ws_queue_overflows_total",
+        "Clients whose queue filled up, by what was done about it."
+    );
+    describe_counter!(
+        "blackjack_ws_messages_dropped_total",
+        "Messages thrown away because a client's queue was full."
+    );
+    describe_histogram!(
+        "blackjack_ws_ping_seconds",
+        "Time taken for a ping to a client to come back."
+    );
+    describe_histogram!(
+        "blackjack_command_duration_seconds",
+        "Time taken to handle a web socket command."
+    );
+    describe_histogram!(
+        "blackjack_http_request_duration_seconds",
+        "Time taken to answer an http request."
+    );
+
+    // Start these at zero so they can be graphed before anything has happened.
+    gauge!("blackjack_connected_clients").set(0.0);
+    gauge!("blackjack_bots").set(0.0);
+    gauge!("blackjack_house_profit_chips").set(0.0);
+    counter!("blackjack_rounds_total").absolute(0);
+    counter!("blackjack_rounds_voided_total").absolute(0);
+    counter!("blackjack_ws_timeouts_total").absolute(0);
+    gauge!("blackjack_ws_queued_messages").set(0.0);
+    counter!("blackjack_ws_messages_dropped_total").absolute(0);
+    for action in ["disconnect", "snapshot"] {
+        counter!("blackjack_ws_queue_overflows_total", "action" => action).absolute(0);
+    }
+    for reason in ["socket", "closed"] {
+        counter!("blackjack_ws_send_errors_total", "reason" => reason).absolute(0);
+    }
+
+    handle
+}
+
+pub fn client_connected() {
+    gauge!("blackjack_connected_clients").increment(1.0);
+}
+
+pub fn client_disconnected() {
+    gauge!("blackjack_connected_clients").decrement(1.0);
+}
+
+pub fn bot_joined() {
+    gauge!("blackjack_bots").increment(1.0);
+}
+
+pub fn bot_left() {
+    gauge!("blackjack_bots").decrement(1.0);
+}
+
+/// A message that couldn't be read as a command is counted as `invalid`.
+pub fn message_received(command: &'static str) {
+    counter!("blackjack_messages_total", "command" => command).increment(1);
+}
+
+pub fn message_ignored(command: &'static str) {
+    counter!("blackjack_messages_ignored_total", "command" => command).increment(1);
+}
+
+/// Counts a failed send. `socket` is the web socket itself failing, `closed` is a message
+/// published to a client whose connection has already gone.
+pub fn send_error(reason: &'static str) {
+    counter!("blackjack_ws_send_errors_total", "reason" => reason).increment(1);
+}
+
+pub fn ping_answered(latency: Duration) {
+    histogram!("blackjack_ws_ping_seconds").record(latency);
+}
+
+pub fn connection_timed_out() {
+    counter!("blackjack_ws_timeouts_total").increment(1);
+}
+
+/// Counts a message queued for a client, whose queue is now `depth` messages long.
+pub fn message_queued(depth: usize) {
+    gauge!("blackjack_ws_queued_messages").increment(1.0);
+    histogram!("blackjack_ws_queue_depth").record(depth as f64);
+}
+
+pub fn messages_dequeued(count: usize) {
+    gauge!("blackjack_ws_queued_messages").decrement(count as f64);
+}
+
+pub fn messages_dropped(count: usize) {
+    counter!("blackjack_ws_messages_dropped_total").increment(count as u64);
+}
+
+/// Counts a client's queue filling up. The action is the overflow policy: `disconnect` or
+/// `snapshot`.
+pub fn queue_overflowed(action: &'static str) {
+    counter!("blackjack_ws_queue_overflows_total", "action" => action).increment(1);
+}
+
+/// Records how long a command took to handle once it's dropped, however the handler returns.
+pub struct CommandTimer {
+    command: &'static str,
+    started: Instant,
+}
+
+impl CommandTimer {
+    pub fn start(command: &'static str) -> Self {
+        CommandTimer {
+            command,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for CommandTimer {
+    fn drop(&mut self) {
+        histogram!("blackjack_command_duration_seconds", "command" => self.command)
+            .record(self.started.elapsed());
+    }
+}
+
+/// Records an http request. Requests are grouped by the first part of their path so ids and
+/// names don't each get a series of their own.
+pub fn http_request(info: warp::log::Info) {
+    let route = match info.path().trim_start_matches('/').split('/').next() {
+        Some("register") => "register",
+        Some("ws") => "ws",
+        Some("leaderboard") => "leaderboard",
+        Some("players") => "players",
+        Some("admin") => "admin",
+        Some("metrics") => "metrics",
+        _ => "other",
+    };
+    histogram!(
+        "blackjack_http_request_duration_seconds",
+        "route" => route,
+        "method" => info.method().to_string(),
+        "status" => info.status().as_u16().to_string(),
+    )
+    .record(info.elapsed());
+}
+
+/// Counts a settled round, its hands and what the house made from them.
+pub fn round_finished(round: &RoundRecord) {
+    counter!("blackjack_rounds_total").increment(1);
+    let mut recent = RECENT_ROUNDS.lock().unwrap();
+    forget_old_rounds(&mut recent);
+    recent.push_back(Instant::now());
+    drop(recent);
+
+    for settlement in &round.settlements {
+        let outcome = match settlement.end_state {
+            EndState::Win => "win",
+            EndState::Loss => "loss",
+            EndState::Blackjack => "blackjack",
+            EndState::Push => "push",
+        };
+        counter!("blackjack_hands_total", "outcome" => outcome).increment(1);
+        gauge!("blackjack_house_profit_chips")
+            .decrement(settlement.end_state.net_chips(settlement.bet) as f64);
+    }
+}
+
+fn forget_old_rounds(recent: &mut VecDeque<Instant>) {
+    while recent
+        .front()
+        .is_some_and(|at| at.elapsed() > Duration::from_secs(60))
+    {
+        recent.pop_front();
+    }
+}
+
+pub fn round_voided() {
+    counter!("blackjack_rounds_voided_total").increment(1);
+}
+
+/// Serves the metrics in the Prometheus text format. The table gauges are brought up to date
+/// first.
+pub async fn metrics_handler(
+    handle: PrometheusHandle,
+    clients: Clients,
+    history: History,
+    admin: SharedAdmin,
+) -> Result<impl Reply, Rejection> {
+    let closed = admin.lock().await.closed;
+    let seated = !clients.lock().await.is_empty();
+    let in_round = history.lock().await.in_round();
+    gauge!("blackjack_active_rooms").set(if seated && !closed { 1.0 } else { 0.0 });
+    gauge!("blackjack_rounds_in_progress").set(if in_round { 1.0 } else { 0.0 });
+
+    let rounds_per_minute = {
+        let mut recent = RECENT_ROUNDS.lock().unwrap();
+        forget_old_rounds(&mut recent);
+        recent.len()
+    };
+    gauge!("blackjack_rounds_per_minute").set(rounds_per_minute as f64);
+
+    handle.run_upkeep();
+    Ok(with_header(
+        handle.render(),
+        CONTENT_TYPE,
+        "text/plain; version=0.0.4",
+    ))
+}
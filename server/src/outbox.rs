@@ -0,0 +1,265 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use tokio::sync::Notify;
+use warp::ws::Message;
+
+use crate::{config::OverflowPolicy, metrics};
+
+/// The messages waiting to be sent to a player. A connection that stops reading can only fall
+/// `capacity` messages behind before the overflow policy kicks in, so it can't grow the server's
+/// memory without limit.
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    queue: StdMutex<Queue>,
+    /// Woken whenever a message is queued or the outbox is closed.
+    changed: Notify,
+    capacity: usize,
+    overflow: OverflowPolicy,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    /// The queue overflowed and a snapshot of the table is owed in place of what was dropped.
+    resync: bool,
+    closed: bool,
+    /// Closed by the `disconnect` policy rather than by [`Outbox::close`].
+    overflowed: bool,
+}
+
+/// What to send next.
+#[derive(Debug)]
+pub enum Outgoing {
+    Message(Message),
+    /// A snapshot of the table, built once it's time to send it. Call [`Outbox::resynced`] when
+    /// it's been built.
+    Snapshot,
+}
+
+/// The player's connection has gone, or is going, so the message won't be sent.
+#[derive(Debug, PartialEq)]
+pub struct Closed;
+
+impl Outbox {
+    pub fn new(capacity: usize, overflow: OverflowPolicy) -> Self {
+        Outbox {
+            inner: Arc::new(Inner {
+                queue: StdMutex::new(Queue::default()),
+                changed: Notify::new(),
+                capacity,
+                overflow,
+            }),
+        }
+    }
+
+    /// Bots read their messages as soon as they're sent, so their outboxes never fill up.
+    pub fn unbounded() -> Self {
+        Outbox::new(usize::MAX, OverflowPolicy::Disconnect)
+    }
+
+    /// Queues a message. A full queue is dealt with by the overflow policy.
+    pub fn send(&self, message: Message) -> Result<(), Closed> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        if queue.closed {
+            return Err(Closed);
+        }
+        // The snapshot that's owed will show whatever this message would have.
+        if queue.resync {
+            metrics::messages_dropped(1);
+            return Ok(());
+        }
+
+        if queue.messages.len() < self.inner.capacity {
+            queue.messages.push_back(message);
+            metrics::message_queued(queue.messages.len());
+            drop(queue);
+            self.inner.changed.notify_waiters();
+            return Ok(());
+        }
+
+        let dropped = queue.messages.len() + 1;
+        queue.messages.clear();
+        metrics::messages_dequeued(dropped - 1);
+        metrics::messages_dropped(dropped);
+        let result = match self.inner.overflow {
+            OverflowPolicy::Disconnect => {
+                metrics::queue_overflowed("disconnect");
+                queue.closed = true;
+                queue.overflowed = true;
+                Err(Closed)
+            }
+            OverflowPolicy::Snapshot => {
+                metrics::queue_overflowed("snapshot");
+                queue.resync = true;
+                Ok(())
+            }
+        };
+        drop(queue);
+        self.inner.changed.notify_waiters();
+        result
+    }
+
+    /// Sends a close frame after whatever is already queued, and nothing more.
+    pub fn close(&self) {
+        let mut queue = self.inner.queue.lock().unwrap();
+        if queue.closed {
+            return;
+        }
+        queue.messages.push_back(Message::close());
+        metrics::message_queued(queue.messages.len());
+        queue.closed = true;
+        drop(queue);
+        self.inner.changed.notify_waiters();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.queue.lock().unwrap().closed
+    }
+
+    /// Whether the `disconnect` policy closed the outbox.
+    pub fn overflowed(&self) -> bool {
+        self.inner.queue.lock().unwrap().overflowed
+    }
+
+    /// Waits until the outbox is closed, by [`Outbox::close`] or by overflowing.
+    pub async fn closed(&self) {
+        loop {
+            let changed = self.inner.changed.notified();
+            if self.is_closed() {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    /// Waits for the next thing to send. Returns `None` once the outbox is closed and everything
+    /// queued before that has been taken.
+    pub async fn next(&self) -> Option<Outgoing> {
+        loop {
+            let changed = self.inner.changed.notified();
+            {
+                let mut queue = self.inner.queue.lock().unwrap();
+                if let Some(message) = queue.messages.pop_front() {
+                    metrics::messages_dequeued(1);
+                    return Some(Outgoing::Message(message));
+                }
+                if queue.closed {
+                    return None;
+                }
+                if queue.resync {
+                    return Some(Outgoing::Snapshot);
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// Messages are queued again once the snapshot that's owed has been built. Call this while
+    /// still holding the locks the snapshot was built under, so nothing published after it was
+    /// taken is lost.
+    pub fn resynced(&self) {
+        self.inner.queue.lock().unwrap().resync = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.queue.lock().unwrap().messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        let queue = self.queue.get_mut().unwrap();
+        metrics::messages_dequeued(queue.messages.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blackjack_shared::{
+        player::{Player, PlayerType},
+        web_socket::{PublishRequest, PublishTrigger},
+    };
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::{client::Client, handlers::publish, Clients};
+
+    const CAPACITY: usize = 8;
+
+    /// A table with one player whose connection never reads anything it's sent.
+    fn table(overflow: OverflowPolicy) -> (Clients, Outbox) {
+        let outbox = Outbox::new(CAPACITY, overflow);
+        let client = Client {
+            id: "stalled".to_owned(),
+            sender: Some(outbox.clone()),
+            position: 0,
+            player: Player {
+                user_name: "Stalled".to_owned(),
+                player_type: PlayerType::Human,
+                hand: vec![],
+                hand_value: 0,
+                chips: 100,
+                current_bet: 0,
+            },
+            bot: None,
+        };
+        (Arc::new(Mutex::new(vec![client])), outbox)
+    }
+
+    async fn chat(clients: &Clients, count: usize) {
+        for i in 0..count {
+            let pub_req = PublishRequest {
+                trigger: PublishTrigger::ChatMessage {
+                    user_name: "Al".to_owned(),
+                    message: i.to_string(),
+                },
+            };
+            let _ = publish(pub_req, clients.clone(), None).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn a_client_that_never_reads_is_disconnected() {
+        let (clients, outbox) = table(OverflowPolicy::Disconnect);
+
+        chat(&clients, CAPACITY).await;
+        assert_eq!(outbox.len(), CAPACITY);
+        assert!(!outbox.is_closed());
+
+        chat(&clients, 1000).await;
+        assert!(outbox.is_closed());
+        assert!(outbox.overflowed());
+        assert!(outbox.is_empty());
+        assert!(outbox.next().await.is_none());
+        assert_eq!(outbox.send(Message::text("late")), Err(Closed));
+    }
+
+    #[tokio::test]
+    async fn a_client_that_never_reads_is_sent_a_snapshot() {
+        let (clients, outbox) = table(OverflowPolicy::Snapshot);
+
+        chat(&clients, 1000).await;
+        assert!(!outbox.is_closed());
+        assert!(outbox.is_empty());
+        assert!(matches!(outbox.next().await, Some(Outgoing::Snapshot)));
+
+        // Nothing is queued until the snapshot has been built, and then the queue carries on.
+        chat(&clients, 10).await;
+        assert!(outbox.is_empty());
+        outbox.resynced();
+        chat(&clients, 1).await;
+        assert!(matches!(outbox.next().await, Some(Outgoing::Message(m)) if m.is_text()));
+    }
+}
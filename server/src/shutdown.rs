@@ -0,0 +1,219 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use blackjack_shared::{
+    admin::Ban,
+    player::{Player, PlayerType},
+    web_socket::{PublishRequest, PublishTrigger},
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    client::Client,
+    config::RoundPolicy,
+    game::void_round,
+    handlers::{publish, publish_results, publish_shoe_events},
+    history::now,
+    tournament::Tournament,
+    Clients, Config, Dealer, History, Reserved, SharedAdmin, SharedShoe, SharedTournament,
+};
+
+/// How often the `finish` policy checks whether the round is over.
+const ROUND_POLL: Duration = Duration::from_millis(100);
+/// How long to wait for the players' connections to close once they've been told to.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A table saved when the server stopped, so the players can carry on once it's back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableSnapshot {
+    pub saved_at: u64,
+    pub table_id: String,
+    /// The rounds dealt so far, so the hand history carries on numbering from there.
+    pub rounds: u64,
+    pub seats: Vec<SavedSeat>,
+    pub tournament: Option<Tournament>,
+    pub bans: Vec<Ban>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSeat {
+    /// The client id, which a tournament uses to keep track of the seat.
+    pub id: String,
+    pub position: usize,
+    pub user_name: String,
+    pub chips: u32,
+    /// The strategy of a seat played by the server.
+    pub bot: Option<String>,
+}
+
+impl SavedSeat {
+    fn new(client: &Client) -> Self {
+        SavedSeat {
+            id: client.id.clone(),
+            position: client.position,
+            user_name: client.player.user_name.clone(),
+            chips: client.player.chips,
+            bot: client.bot.clone(),
+        }
+    }
+
+    /// The seat as it was, waiting for its player to connect.
+    pub fn into_client(self) -> Client {
+        Client {
+            id: self.id,
+            sender: None,
+            position: self.position,
+            player: Player {
+                user_name: self.user_name,
+                player_type: PlayerType::Human,
+                hand: vec![],
+                hand_value: 0,
+                chips: self.chips,
+                current_bet: 0,
+            },
+            bot: self.bot,
+        }
+    }
+}
+
+impl TableSnapshot {
+    /// Reads the table saved when the server last stopped, if there is one.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&text)
+            .map(Some)
+            .map_err(|e| format!("Invalid saved table {}: {}", path.display(), e))
+    }
+
+    /// Writes the snapshot to a temporary file first so a failed write can't leave half a table
+    /// behind.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, serde_json::to_string_pretty(self).unwrap())
+            .and_then(|_| fs::rename(&temp, path))
+            .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))
+    }
+}
+
+/// Waits for Ctrl-C, or SIGTERM on Unix.
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!(error = %e, "Couldn't listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                error!(error = %e, "Couldn't listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+/// Stops the table. Everyone is told the server is going, the round being played is finished or
+/// called off by the policy, the shoe is revealed and the table is saved before every connection
+/// is closed.
+#[allow(clippy::too_many_arguments)]
+pub async fn shut_down(
+    clients: Clients,
+    dealer: Dealer,
+    shoe: SharedShoe,
+    config: Config,
+    history: History,
+    tournament: SharedTournament,
+    admin: SharedAdmin,
+    reserved: Reserved,
+) {
+    admin.lock().await.shutting_down = true;
+    let policy = config.shutdown.round_policy;
+    let finish_round = policy == RoundPolicy::Finish && history.lock().await.in_round();
+    info!(policy = ?policy, "Shutting down");
+    let pub_req = PublishRequest {
+        trigger: PublishTrigger::ServerShuttingDown { finish_round },
+    };
+    let _ = publish(pub_req, clients.clone(), None).await;
+
+    if finish_round {
+        let deadline = Instant::now() + Duration::from_secs(config.shutdown.grace_secs);
+        while history.lock().await.in_round() && Instant::now() < deadline {
+            tokio::time::sleep(ROUND_POLL).await;
+        }
+    }
+
+    let continue_playing = match void_round(&clients, &dealer, &history, &tournament).await {
+        Some((pub_reqs, continue_playing)) => {
+            warn!("Called off the round being played, every bet has been returned");
+            publish_shoe_events(&shoe, &history, &clients).await;
+            publish_results(pub_reqs, continue_playing, &shoe, &history, &clients).await;
+            continue_playing
+        }
+        None => true,
+    };
+    // Reveal the shoe so it can be checked. The restarted server deals from a new one.
+    if continue_playing {
+        shoe.lock().await.shuffle();
+        publish_shoe_events(&shoe, &history, &clients).await;
+    }
+
+    // Once the game is over there's nothing to come back to but the bans.
+    let snapshot = {
+        let clients = clients.lock().await;
+        let history = history.lock().await;
+        let mut seats = vec![];
+        if continue_playing {
+            seats.extend(clients.iter().map(SavedSeat::new));
+            seats.extend(reserved.lock().await.iter().cloned());
+            seats.sort_by_key(|s| s.position);
+        }
+        TableSnapshot {
+            saved_at: now(),
+            table_id: history.table_id().to_owned(),
+            rounds: history.rounds(),
+            seats,
+            tournament: tournament.lock().await.take().filter(|t| !t.finished()),
+            bans: admin.lock().await.bans().to_vec(),
+        }
+    };
+    let path = &config.shutdown.state_file;
+    match snapshot.save(path) {
+        Ok(()) => info!(
+            seats = snapshot.seats.len(),
+            path = %path.display(),
+            "Saved the table"
+        ),
+        Err(e) => error!(error = %e, "Couldn't save the table"),
+    }
+
+    for client in clients.lock().await.iter() {
+        if let Some(sender) = &client.sender {
+            sender.close();
+        }
+    }
+    let deadline = Instant::now() + CLOSE_TIMEOUT;
+    while clients.lock().await.iter().any(|c| c.bot.is_none()) && Instant::now() < deadline {
+        tokio::time::sleep(ROUND_POLL).await;
+    }
+}
@@ -0,0 +1,213 @@
+use std::cmp::Reverse;
+
+use blackjack_shared::{
+    tournament::{Standing, TournamentConfig},
+    web_socket::{PublishRequest, PublishTrigger},
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::client::Client;
+
+/// Runs a tournament table: a fixed number of hands from equal stacks, with players knocked out
+/// at checkpoints or when they run out of chips.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tournament {
+    config: TournamentConfig,
+    /// Hands dealt so far, including the one being played.
+    hand: u32,
+    /// The button moves to the first seat still in at or after this position. It starts on the
+    /// last seat so the first hand is played in seat order.
+    next_button: Option<usize>,
+    /// The position of the seat acting last in the hand being played.
+    button: usize,
+    /// The client ids of the players knocked out.
+    eliminated: Vec<String>,
+    /// The client ids of the players in the hand being played, in the order they act.
+    order: Vec<String>,
+    finished: bool,
+}
+
+impl Tournament {
+    pub fn new(config: TournamentConfig) -> Self {
+        Tournament {
+            config,
+            hand: 0,
+            next_button: None,
+            button: 0,
+            eliminated: vec![],
+            order: vec![],
+            finished: false,
+        }
+    }
+
+    pub fn config(&self) -> &TournamentConfig {
+        &self.config
+    }
+
+    /// Players can only join before the first hand is dealt.
+    pub fn started(&self) -> bool {
+        self.hand > 0
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Whether bets should be kept from the other players until the hand is settled.
+    pub fn secret_bets(&self) -> bool {
+        self.config.secret_final_bet && self.hand == self.config.hands
+    }
+
+    /// Deals the next hand in, moving the button along. Returns the id of the first player to
+    /// act, or `None` once the tournament is over.
+    pub fn start_hand(&mut self, clients: &[Client]) -> Option<String> {
+        if self.finished {
+            return None;
+        }
+
+        let mut seats: Vec<&Client> = clients
+            .iter()
+            .filter(|c| !self.eliminated.contains(&c.id))
+            .collect();
+        seats.sort_by_key(|c| c.position);
+        let last = seats.last()?;
+
+        let button = match self.next_button {
+            Some(next) => seats
+                .iter()
+                .find(|c| c.position >= next)
+                .unwrap_or(&seats[0]),
+            None => last,
+        };
+        self.button = button.position;
+
+        // The seat after the button acts first and the button acts last.
+        let split = seats.iter().position(|c| c.position > self.button);
+        let (before, after) = seats.split_at(split.unwrap_or(seats.len()));
+        self.order = after.iter().chain(before).map(|c| c.id.clone()).collect();
+
+        self.hand += 1;
+        info!(
+            "Dealing tournament hand {} of {} with {} players",
+            self.hand,
+            self.config.hands,
+            self.order.len()
+        );
+        self.order.first().cloned()
+    }
+
+    pub fn is_eliminated(&self, id: &str) -> bool {
+        self.eliminated.iter().any(|e| e == id)
+    }
+
+    /// Whether the player was dealt into the hand being played.
+    pub fn in_hand(&self, id: &str) -> bool {
+        self.order.iter().any(|o| o == id)
+    }
+
+    /// The id of the player acting after the given one, if anyone is left to act.
+    pub fn next_after(&self, id: &str) -> Option<&str> {
+        let index = self.order.iter().position(|o| o == id)?;
+        self.order.get(index + 1).map(|o| o.as_str())
+    }
+
+    /// Knocks out anyone who has run out of chips or missed a checkpoint and returns the
+    /// standings to announce, along with whether the tournament carries on.
+    pub fn finish_hand(&mut self, clients: &[Client]) -> (Vec<PublishRequest>, bool) {
+        let mut remaining: Vec<&Client> = clients
+            .iter()
+            .filter(|c| !self.eliminated.contains(&c.id))
+            .collect();
+        remaining.sort_by_key(|c| Reverse(c.player.chips));
+
+        // The chips needed to survive this hand's checkpoints, if there are any.
+        let cut = self
+            .config
+            .eliminations
+            .iter()
+            .filter(|e| e.after_hand == self.hand)
+            .filter_map(|e| {
+                remaining
+                    .get(e.keep.saturating_sub(1))
+                    .map(|c| c.player.chips)
+            })
+            .max();
+        let mut knocked_out: Vec<&Client> = remaining
+            .iter()
+            .copied()
+            .filter(|c| c.player.chips == 0 || cut.is_some_and(|cut| c.player.chips < cut))
+            .collect();
+
+        // Everyone going out at once would leave no winner, so the biggest stacks survive.
+        if knocked_out.len() == remaining.len() {
+            let best = remaining.first().map_or(0, |c| c.player.chips);
+            knocked_out.retain(|c| c.player.chips < best);
+        }
+
+        let mut eliminated = vec![];
+        for client in knocked_out {
+            info!("{} has been knocked out", client.player.user_name);
+            self.eliminated.push(client.id.clone());
+            eliminated.push(client.player.user_name.clone());
+        }
+        self.next_button = Some(self.button + 1);
+        self.order.clear();
+
+        let standings = self.standings(clients);
+        let mut events = vec![PublishRequest {
+            trigger: PublishTrigger::TournamentStandings {
+                hand: self.hand,
+                hands: self.config.hands,
+                standings: standings.clone(),
+                eliminated,
+            },
+        }];
+
+        // The tournament ends early once knockouts leave a single player.
+        let players_left = standings.iter().filter(|s| !s.eliminated).count();
+        let last_one_standing = players_left <= 1 && standings.len() > 1;
+        if self.hand >= self.config.hands || last_one_standing {
+            self.finished = true;
+            let winner = standings
+                .first()
+                .map(|s| s.user_name.clone())
+                .unwrap_or_default();
+            info!("The tournament has finished. {} wins", winner);
+            events.push(PublishRequest {
+                trigger: PublishTrigger::TournamentFinished { winner, standings },
+            });
+        }
+
+        (events, !self.finished)
+    }
+
+    /// Players still in are ranked by their chips, followed by everyone knocked out with the
+    /// last to go out first.
+    fn standings(&self, clients: &[Client]) -> Vec<Standing> {
+        let mut playing: Vec<&Client> = clients
+            .iter()
+            .filter(|c| !self.eliminated.contains(&c.id))
+            .collect();
+        playing.sort_by_key(|c| Reverse(c.player.chips));
+
+        let knocked_out = self
+            .eliminated
+            .iter()
+            .rev()
+            .filter_map(|id| clients.iter().find(|c| &c.id == id));
+
+        playing
+            .into_iter()
+            .map(|c| (c, false))
+            .chain(knocked_out.map(|c| (c, true)))
+            .enumerate()
+            .map(|(i, (c, eliminated))| Standing {
+                rank: i + 1,
+                user_name: c.player.user_name.clone(),
+                chips: c.player.chips,
+                eliminated,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    history::RoundRecord, player::Player, rules::TableRules, tournament::TournamentConfig,
+};
+
+/// The body of `POST /admin/announcements`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementRequest {
+    pub message: String,
+}
+
+/// The body of `PUT /admin/log-level`. The level is `error`, `warn`, `info` or `debug`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevelRequest {
+    pub level: String,
+}
+
+/// What a table is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TablePhase {
+    /// No round has been dealt yet.
+    Waiting,
+    Playing,
+    BetweenRounds,
+    /// The round being played will be the last.
+    Draining,
+    /// The last round has finished and the server can be restarted.
+    Drained,
+    Closed,
+}
+
+/// What a seat is doing in the round being played.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeatPhase {
+    /// Waiting for their turn or the next round.
+    Waiting,
+    Acting,
+    /// Finished their turn this round.
+    Done,
+    /// Knocked out of the tournament.
+    SittingOut,
+    /// Registered but without a web socket open.
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeatSummary {
+    pub id: String,
+    pub user_name: String,
+    pub position: usize,
+    pub bot: bool,
+    pub phase: SeatPhase,
+    pub chips: u32,
+    pub bet: u32,
+}
+
+/// A row of `GET /admin/tables`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSummary {
+    pub table_id: String,
+    pub phase: TablePhase,
+    /// The number of rounds dealt so far.
+    pub rounds: u64,
+    pub seats: Vec<SeatSummary>,
+}
+
+/// Where the shoe is up to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShoeStatus {
+    pub shoe_id: u32,
+    pub decks: u8,
+    pub dealt: usize,
+    /// Whether the cut card has been reached, so the next round starts a new shoe.
+    pub needs_shuffle: bool,
+}
+
+/// Everything about a table, from `GET /admin/tables/{table_id}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableDetail {
+    #[serde(flatten)]
+    pub summary: TableSummary,
+    pub rules: TableRules,
+    pub tournament: Option<TournamentConfig>,
+    /// Each seat's hand and bet, in seat order.
+    pub players: Vec<Player>,
+    /// The dealer's hand, hole card included.
+    pub dealer: Player,
+    pub shoe: ShoeStatus,
+    /// The round being played so far.
+    pub round: Option<RoundRecord>,
+    pub bans: Vec<Ban>,
+}
+
+/// The body of `POST /admin/tables/{table_id}/seats/{seat_id}/kick`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KickRequest {
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Also ban the player's name from registering again.
+    #[serde(default)]
+    pub ban: bool,
+}
+
+/// The body of `POST /admin/tables/{table_id}/seats/{seat_id}/chips`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChipsAdjustment {
+    /// The chips to add, or take away when negative.
+    pub change: i64,
+    /// Why, for the audit log and the players.
+    pub reason: String,
+}
+
+/// The body of `POST /admin/bans`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanRequest {
+    pub user_name: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A name that can't register.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub user_name: String,
+    pub reason: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub at: u64,
+}
@@ -1,5 +1,8 @@
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{fmt, str::FromStr};
+
+use crate::helpers::{rank_from_int, suit_from_int};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Suit {
@@ -50,19 +53,6 @@ impl fmt::Display for Suit {
 impl fmt::Display for Card {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}{}", self.rank, self.suit)
-        /*)writedoc!(
-            f,
-            "
-               /-----\\
-               |{}    |
-               |  {}  |
-               |    {}|
-               \\-----/
-               ",
-            self.rank,
-            self.suit,
-            self.rank
-        )*/
     }
 }
 
@@ -89,3 +79,55 @@ impl fmt::Display for Rank {
         )
     }
 }
+
+impl FromStr for Card {
+    type Err = String;
+
+    /// Parses a card written as its rank followed by its suit, such as `AS`, `10h` or `Q♦`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        let suit = match chars.next_back().map(|c| c.to_ascii_uppercase()) {
+            Some('S' | '♠') => Suit::Spades,
+            Some('H' | '♥') => Suit::Hearts,
+            Some('D' | '♦') => Suit::Diamonds,
+            Some('C' | '♣') => Suit::Clubs,
+            _ => return Err(format!("Invalid suit in card '{}'", s)),
+        };
+        let rank = match chars.as_str().to_uppercase().as_str() {
+            "2" => Rank::Two,
+            "3" => Rank::Three,
+            "4" => Rank::Four,
+            "5" => Rank::Five,
+            "6" => Rank::Six,
+            "7" => Rank::Seven,
+            "8" => Rank::Eight,
+            "9" => Rank::Nine,
+            "T" | "10" => Rank::Ten,
+            "J" => Rank::Jack,
+            "Q" => Rank::Queen,
+            "K" => Rank::King,
+            "A" => Rank::Ace,
+            _ => return Err(format!("Invalid rank in card '{}'", s)),
+        };
+
+        Ok(Card { suit, rank })
+    }
+}
+
+/// Creates the given number of decks shuffled together. Cards are dealt from the back.
+pub fn create_playing_deck(num_of_decks: u8, rng: &mut impl Rng) -> Vec<Card> {
+    let mut deck: Vec<Card> = (0..num_of_decks)
+        .flat_map(|_d| {
+            (0..4).flat_map(|s| {
+                (0..13).map(move |r| Card {
+                    rank: rank_from_int(r),
+                    suit: suit_from_int(s),
+                })
+            })
+        })
+        .collect();
+
+    deck.shuffle(rng);
+    deck
+}
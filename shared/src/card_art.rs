@@ -0,0 +1,157 @@
+use std::env;
+
+use crate::card::{Card, Suit};
+
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Controls which characters are used when drawing cards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtStyle {
+    /// Use box drawing characters and suit symbols. When false only plain ASCII is used.
+    pub unicode: bool,
+    /// Colour the hearts and diamonds red using ANSI escape codes.
+    pub colour: bool,
+}
+
+impl ArtStyle {
+    /// Plain ASCII with no colour. Safe to write to files and any terminal.
+    pub const PLAIN: ArtStyle = ArtStyle {
+        unicode: false,
+        colour: false,
+    };
+
+    /// Picks a style based on the environment. Unicode is used when the locale is UTF-8 and
+    /// colour is used unless `NO_COLOR` is set or the terminal is `dumb`.
+    pub fn detect() -> Self {
+        let unicode = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|v| env::var(v).ok())
+            .find(|v| !v.is_empty())
+            .map(|v| {
+                let v = v.to_lowercase();
+                v.contains("utf-8") || v.contains("utf8")
+            })
+            .unwrap_or(false);
+
+        let colour = env::var_os("NO_COLOR").is_none()
+            && env::var("TERM").map(|t| t != "dumb").unwrap_or(true);
+
+        ArtStyle { unicode, colour }
+    }
+
+    fn border(&self) -> Border {
+        if self.unicode {
+            Border {
+                top: "┌─────┐",
+                bottom: "└─────┘",
+                side: "│",
+                back: "░░░░░",
+            }
+        } else {
+            Border {
+                top: "+-----+",
+                bottom: "+-----+",
+                side: "|",
+                back: "#####",
+            }
+        }
+    }
+}
+
+struct Border {
+    top: &'static str,
+    bottom: &'static str,
+    side: &'static str,
+    back: &'static str,
+}
+
+/// The symbol used for the suit. The ASCII fallback uses the suit's initial.
+pub fn suit_symbol(suit: &Suit, unicode: bool) -> String {
+    if unicode {
+        suit.to_string()
+    } else {
+        match suit {
+            Suit::Spades => "S",
+            Suit::Hearts => "H",
+            Suit::Diamonds => "D",
+            Suit::Clubs => "C",
+        }
+        .to_string()
+    }
+}
+
+/// Draws a single face up card. Every line has the same visible width.
+pub fn render_card(card: &Card, style: ArtStyle) -> Vec<String> {
+    let border = style.border();
+    let paint = |text: String| {
+        if style.colour && matches!(card.suit, Suit::Hearts | Suit::Diamonds) {
+            format!("{}{}{}", RED, text, RESET)
+        } else {
+            text
+        }
+    };
+
+    let rank = card.rank.to_string();
+    let suit = suit_symbol(&card.suit, style.unicode);
+
+    vec![
+        border.top.to_string(),
+        format!(
+            "{}{}{}",
+            border.side,
+            paint(format!("{:<5}", rank)),
+            border.side
+        ),
+        format!(
+            "{}{}{}",
+            border.side,
+            paint(format!("  {}  ", suit)),
+            border.side
+        ),
+        format!(
+            "{}{}{}",
+            border.side,
+            paint(format!("{:>5}", rank)),
+            border.side
+        ),
+        border.bottom.to_string(),
+    ]
+}
+
+/// Draws the back of a card, used for the dealer's hole card.
+pub fn render_card_back(style: ArtStyle) -> Vec<String> {
+    let border = style.border();
+    let middle = format!("{}{}{}", border.side, border.back, border.side);
+
+    vec![
+        border.top.to_string(),
+        middle.clone(),
+        middle.clone(),
+        middle,
+        border.bottom.to_string(),
+    ]
+}
+
+/// Draws a hand of cards side by side followed by `face_down` card backs.
+pub fn render_hand(cards: &[Card], face_down: usize, style: ArtStyle) -> Vec<String> {
+    let rendered: Vec<Vec<String>> = cards
+        .iter()
+        .map(|c| render_card(c, style))
+        .chain((0..face_down).map(|_| render_card_back(style)))
+        .collect();
+
+    if rendered.is_empty() {
+        return vec![];
+    }
+
+    (0..rendered[0].len())
+        .map(|line| {
+            rendered
+                .iter()
+                .map(|card| card[line].as_str())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .collect()
+}
@@ -0,0 +1,335 @@
+use std::{fmt, str::FromStr};
+
+use crate::card::{Card, Rank};
+
+/// A card counting system. Each card seen adds its tag to the running count.
+pub trait CountingSystem {
+    fn name(&self) -> &'static str;
+
+    /// The amount added to the running count when the card is seen.
+    fn tag(&self, card: &Card) -> i32;
+
+    /// The running count at the start of a fresh shoe.
+    fn initial_count(&self, _decks: u8) -> i32 {
+        0
+    }
+
+    /// Balanced systems sum to zero over a full deck, so the running count can be converted to a
+    /// true count.
+    fn is_balanced(&self) -> bool {
+        true
+    }
+}
+
+/// The high-low system. Low cards are +1, tens and aces are -1.
+pub struct HiLo;
+
+impl CountingSystem for HiLo {
+    fn name(&self) -> &'static str {
+        "Hi-Lo"
+    }
+
+    fn tag(&self, card: &Card) -> i32 {
+        match card.rank {
+            Rank::Two | Rank::Three | Rank::Four | Rank::Five | Rank::Six => 1,
+            Rank::Seven | Rank::Eight | Rank::Nine => 0,
+            _ => -1,
+        }
+    }
+}
+
+/// The Knock-Out system. Like Hi-Lo but sevens are +1, so the count is unbalanced and starts
+/// below zero for multiple decks.
+pub struct KnockOut;
+
+impl CountingSystem for KnockOut {
+    fn name(&self) -> &'static str {
+        "KO"
+    }
+
+    fn tag(&self, card: &Card) -> i32 {
+        match card.rank {
+            Rank::Two | Rank::Three | Rank::Four | Rank::Five | Rank::Six | Rank::Seven => 1,
+            Rank::Eight | Rank::Nine => 0,
+            _ => -1,
+        }
+    }
+
+    fn initial_count(&self, decks: u8) -> i32 {
+        -4 * (decks as i32 - 1)
+    }
+
+    fn is_balanced(&self) -> bool {
+        false
+    }
+}
+
+/// The Omega II system. A level two count that ignores aces.
+pub struct OmegaII;
+
+impl CountingSystem for OmegaII {
+    fn name(&self) -> &'static str {
+        "Omega II"
+    }
+
+    fn tag(&self, card: &Card) -> i32 {
+        match card.rank {
+            Rank::Two | Rank::Three | Rank::Seven => 1,
+            Rank::Four | Rank::Five | Rank::Six => 2,
+            Rank::Eight | Rank::Ace => 0,
+            Rank::Nine => -1,
+            _ => -2,
+        }
+    }
+}
+
+/// Looks up a counting system by name, such as `hi-lo`, `ko` or `omega-ii`.
+pub fn counting_system_from_name(name: &str) -> Option<Box<dyn CountingSystem + Send>> {
+    match name.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "hilo" => Some(Box::new(HiLo)),
+        "ko" | "knockout" => Some(Box::new(KnockOut)),
+        "omegaii" | "omega2" => Some(Box::new(OmegaII)),
+        _ => None,
+    }
+}
+
+/// Keeps the running count for a shoe.
+pub struct Counter {
+    system: Box<dyn CountingSystem + Send>,
+    decks: u8,
+    running_count: i32,
+    cards_seen: u32,
+}
+
+impl Counter {
+    pub fn new(system: Box<dyn CountingSystem + Send>, decks: u8) -> Self {
+        let running_count = system.initial_count(decks);
+        Counter {
+            system,
+            decks,
+            running_count,
+            cards_seen: 0,
+        }
+    }
+
+    pub fn system(&self) -> &dyn CountingSystem {
+        self.system.as_ref()
+    }
+
+    /// Starts counting a fresh shoe.
+    pub fn reset(&mut self) {
+        self.running_count = self.system.initial_count(self.decks);
+        self.cards_seen = 0;
+    }
+
+    pub fn observe(&mut self, cards: &[Card]) {
+        for card in cards {
+            self.running_count += self.system.tag(card);
+            self.cards_seen += 1;
+        }
+    }
+
+    pub fn running_count(&self) -> i32 {
+        self.running_count
+    }
+
+    /// The number of decks that haven't been seen yet, never less than a quarter of a deck.
+    pub fn decks_remaining(&self) -> f64 {
+        let cards_left = (self.decks as f64 * 52.0 - self.cards_seen as f64).max(13.0);
+        cards_left / 52.0
+    }
+
+    /// The running count divided by the decks remaining. Unbalanced systems don't use a true
+    /// count so `None` is returned for them.
+    pub fn true_count(&self) -> Option<f64> {
+        self.system
+            .is_balanced()
+            .then(|| self.running_count as f64 / self.decks_remaining())
+    }
+}
+
+/// How many betting units to wager at each true count. The first entry is bet at a true count of
+/// one or less, the next at two and so on. Counts past the end use the last entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetSpread {
+    units: Vec<u32>,
+}
+
+impl BetSpread {
+    /// Bets one unit whatever the count.
+    pub fn flat() -> Self {
+        BetSpread { units: vec![1] }
+    }
+
+    pub fn units(&self, true_count: f64) -> u32 {
+        let index = (true_count.floor() as i64 - 1).clamp(0, self.units.len() as i64 - 1);
+        self.units[index as usize]
+    }
+}
+
+impl FromStr for BetSpread {
+    type Err = String;
+
+    /// Parses a comma separated list of units, such as `1,2,4,8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let units = s
+            .split(',')
+            .map(|u| u.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid bet spread '{}': {}", s, e))?;
+
+        if units.is_empty() || units.contains(&0) {
+            return Err(format!(
+                "Invalid bet spread '{}': units must be above zero",
+                s
+            ));
+        }
+
+        Ok(BetSpread { units })
+    }
+}
+
+impl fmt::Display for BetSpread {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units: Vec<String> = self.units.iter().map(|u| u.to_string()).collect();
+        write!(f, "{}", units.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{create_playing_deck, Suit};
+
+    const RANKS: [Rank; 13] = [
+        Rank::Two,
+        Rank::Three,
+        Rank::Four,
+        Rank::Five,
+        Rank::Six,
+        Rank::Seven,
+        Rank::Eight,
+        Rank::Nine,
+        Rank::Ten,
+        Rank::Jack,
+        Rank::Queen,
+        Rank::King,
+        Rank::Ace,
+    ];
+
+    /// The tag of each rank, from two up to ace.
+    fn tags(system: &dyn CountingSystem) -> Vec<i32> {
+        RANKS
+            .into_iter()
+            .map(|rank| {
+                system.tag(&Card {
+                    suit: Suit::Spades,
+                    rank,
+                })
+            })
+            .collect()
+    }
+
+    fn shoe(decks: u8) -> Vec<Card> {
+        create_playing_deck(decks, &mut rand::thread_rng())
+    }
+
+    #[test]
+    fn hi_lo_tags() {
+        assert_eq!(tags(&HiLo), [1, 1, 1, 1, 1, 0, 0, 0, -1, -1, -1, -1, -1]);
+        assert!(HiLo.is_balanced());
+        assert_eq!(HiLo.initial_count(6), 0);
+    }
+
+    #[test]
+    fn knock_out_tags() {
+        assert_eq!(
+            tags(&KnockOut),
+            [1, 1, 1, 1, 1, 1, 0, 0, -1, -1, -1, -1, -1]
+        );
+        assert!(!KnockOut.is_balanced());
+    }
+
+    #[test]
+    fn omega_ii_tags() {
+        assert_eq!(tags(&OmegaII), [1, 1, 2, 2, 2, 1, 0, -1, -2, -2, -2, -2, 0]);
+        assert!(OmegaII.is_balanced());
+        assert_eq!(OmegaII.initial_count(6), 0);
+    }
+
+    #[test]
+    fn knock_out_starts_below_zero_for_multiple_decks() {
+        assert_eq!(KnockOut.initial_count(1), 0);
+        assert_eq!(KnockOut.initial_count(2), -4);
+        assert_eq!(KnockOut.initial_count(6), -20);
+        assert_eq!(KnockOut.initial_count(8), -28);
+    }
+
+    #[test]
+    fn balanced_counts_end_a_shoe_at_zero() {
+        for decks in [1, 2, 6] {
+            for system in [
+                Box::new(HiLo) as Box<dyn CountingSystem + Send>,
+                Box::new(OmegaII),
+            ] {
+                let mut counter = Counter::new(system, decks);
+                counter.observe(&shoe(decks));
+                assert_eq!(counter.running_count(), 0, "{}", counter.system().name());
+            }
+        }
+    }
+
+    #[test]
+    fn knock_out_ends_a_shoe_at_four() {
+        for decks in [1, 2, 6, 8] {
+            let mut counter = Counter::new(Box::new(KnockOut), decks);
+            counter.observe(&shoe(decks));
+            assert_eq!(counter.running_count(), 4);
+            assert_eq!(counter.true_count(), None);
+
+            counter.reset();
+            assert_eq!(counter.running_count(), KnockOut.initial_count(decks));
+        }
+    }
+
+    #[test]
+    fn true_count_divides_by_the_decks_remaining() {
+        let mut counter = Counter::new(Box::new(HiLo), 6);
+        let low_cards: Vec<Card> = shoe(1).into_iter().filter(|c| HiLo.tag(c) == 1).collect();
+        counter.observe(&low_cards);
+
+        assert_eq!(counter.running_count(), 20);
+        assert!((counter.decks_remaining() - (312.0 - 20.0) / 52.0).abs() < 1e-9);
+        assert!((counter.true_count().unwrap() - 20.0 * 52.0 / 292.0).abs() < 1e-9);
+
+        counter.reset();
+        assert_eq!(counter.running_count(), 0);
+        assert_eq!(counter.decks_remaining(), 6.0);
+    }
+
+    #[test]
+    fn systems_are_found_by_name() {
+        assert_eq!(counting_system_from_name("Hi-Lo").unwrap().name(), "Hi-Lo");
+        assert_eq!(counting_system_from_name("knock_out").unwrap().name(), "KO");
+        assert_eq!(
+            counting_system_from_name("omega 2").unwrap().name(),
+            "Omega II"
+        );
+        assert!(counting_system_from_name("zen").is_none());
+    }
+
+    #[test]
+    fn bet_spreads() {
+        let spread: BetSpread = "1, 2,4,8".parse().unwrap();
+        assert_eq!(spread.units(-3.0), 1);
+        assert_eq!(spread.units(1.9), 1);
+        assert_eq!(spread.units(2.0), 2);
+        assert_eq!(spread.units(3.5), 4);
+        assert_eq!(spread.units(12.0), 8);
+        assert_eq!(spread.to_string(), "1,2,4,8");
+
+        assert!("1,0".parse::<BetSpread>().is_err());
+        assert!("one".parse::<BetSpread>().is_err());
+        assert_eq!(BetSpread::flat().units(10.0), 1);
+    }
+}
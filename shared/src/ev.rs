@@ -0,0 +1,437 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{card::Card, helpers::get_rank_value, rules::TableRules, strategy::StrategyAction};
+
+/// The memo tables are cleared once they grow past this many entries to keep memory in check.
+const MAX_MEMO_ENTRIES: usize = 2_000_000;
+
+/// How many of each card value are left in the shoe. Aces are held at index 0 and every ten
+/// valued card at index 9.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShoeComposition {
+    counts: [u16; 10],
+}
+
+impl ShoeComposition {
+    /// A freshly shuffled shoe.
+    pub fn new(decks: u8) -> Self {
+        let decks = decks as u16;
+        let mut counts = [4 * decks; 10];
+        counts[9] = 16 * decks;
+        ShoeComposition { counts }
+    }
+
+    /// A fresh shoe with the given cards taken out.
+    pub fn without(decks: u8, cards: &[Card]) -> Self {
+        let mut shoe = ShoeComposition::new(decks);
+        shoe.remove_all(cards);
+        shoe
+    }
+
+    /// Takes a card out of the shoe. Returns false if there were none of that value left.
+    pub fn remove(&mut self, card: &Card) -> bool {
+        let count = &mut self.counts[value_index(card)];
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        true
+    }
+
+    pub fn remove_all(&mut self, cards: &[Card]) {
+        cards.iter().for_each(|c| {
+            self.remove(c);
+        });
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.counts.iter().map(|&c| c as u32).sum()
+    }
+
+    /// Each card value with the chance of it being drawn next, leaving out values that are gone.
+    fn draws(&self) -> impl Iterator<Item = (u32, f64, ShoeComposition)> + '_ {
+        let total = self.remaining() as f64;
+        (0..10).filter(|&i| self.counts[i] > 0).map(move |i| {
+            let mut rest = *self;
+            rest.counts[i] -= 1;
+            (i as u32 + 1, self.counts[i] as f64 / total, rest)
+        })
+    }
+}
+
+fn value_index(card: &Card) -> usize {
+    match get_rank_value(card.rank.clone()) {
+        11 => 0,
+        value => value as usize - 1,
+    }
+}
+
+/// A hand's total with every ace counted as one, and whether it holds an ace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+struct Total {
+    hard: u32,
+    has_ace: bool,
+}
+
+impl Total {
+    fn of(cards: &[Card]) -> Self {
+        cards
+            .iter()
+            .fold(Total::default(), |t, c| t.add(value_index(c) as u32 + 1))
+    }
+
+    /// Adds a card with aces counted as one.
+    fn add(self, value: u32) -> Self {
+        Total {
+            hard: self.hard + value,
+            has_ace: self.has_ace || value == 1,
+        }
+    }
+
+    fn best(&self) -> u32 {
+        if self.is_soft() {
+            self.hard + 10
+        } else {
+            self.hard
+        }
+    }
+
+    fn is_soft(&self) -> bool {
+        self.has_ace && self.hard + 10 <= 21
+    }
+
+    fn is_bust(&self) -> bool {
+        self.hard > 21
+    }
+}
+
+/// The chance of the dealer finishing on 17, 18, 19, 20 and 21, followed by the chance of busting.
+type DealerOutcomes = [f64; 6];
+
+/// The expected value of each action, as a fraction of the bet placed before the action. Actions
+/// that aren't allowed for the hand are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActionValues {
+    pub stand: f64,
+    pub hit: f64,
+    pub double: Option<f64>,
+    pub split: Option<f64>,
+    pub surrender: Option<f64>,
+}
+
+impl ActionValues {
+    /// The action with the highest expected value.
+    pub fn best(&self) -> (StrategyAction, f64) {
+        [
+            (StrategyAction::Stand, Some(self.stand)),
+            (StrategyAction::Hit, Some(self.hit)),
+            (StrategyAction::Double, self.double),
+            (StrategyAction::Split, self.split),
+            (StrategyAction::Surrender, self.surrender),
+        ]
+        .into_iter()
+        .filter_map(|(action, ev)| ev.map(|ev| (action, ev)))
+        .fold((StrategyAction::Stand, f64::MIN), |best, next| {
+            if next.1 > best.1 {
+                next
+            } else {
+                best
+            }
+        })
+    }
+}
+
+impl fmt::Display for ActionValues {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stand {:+.3}, hit {:+.3}", self.stand, self.hit)?;
+        for (name, ev) in [
+            ("double", self.double),
+            ("split", self.split),
+            ("surrender", self.surrender),
+        ] {
+            if let Some(ev) = ev {
+                write!(f, ", {} {:+.3}", name, ev)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Works out the exact expected value of each action from the cards left in the shoe.
+///
+/// Hands are settled the way the server settles them: the dealer doesn't peek for blackjack, a
+/// player who busts only loses if the dealer doesn't bust too, and a two card 21 pays three to
+/// one unless the dealer also makes 21. Split hands are played once each with no resplitting,
+/// split aces get one card each and both hands are worked out from the same shoe.
+pub struct EvCalculator {
+    rules: TableRules,
+    dealer_memo: HashMap<(Total, ShoeComposition), DealerOutcomes>,
+    hit_memo: HashMap<(u32, Total, ShoeComposition), f64>,
+}
+
+impl EvCalculator {
+    pub fn new(rules: &TableRules) -> Self {
+        EvCalculator {
+            rules: rules.clone(),
+            dealer_memo: HashMap::new(),
+            hit_memo: HashMap::new(),
+        }
+    }
+
+    /// Returns the expected value of every action for the hand. The shoe should hold the cards
+    /// that haven't been seen yet, so it shouldn't include the hand or the dealer's up card.
+    pub fn evaluate(
+        &mut self,
+        hand: &[Card],
+        dealer_up: &Card,
+        shoe: &ShoeComposition,
+    ) -> ActionValues {
+        if self.dealer_memo.len() + self.hit_memo.len() > MAX_MEMO_ENTRIES {
+            self.dealer_memo.clear();
+            self.hit_memo.clear();
+        }
+
+        let up = value_index(dealer_up) as u32 + 1;
+        let total = Total::of(hand);
+        let first_two = hand.len() == 2;
+        let natural = first_two && total.best() == 21;
+
+        let stand = self.stand_ev(up, total, natural, shoe);
+        let hit = self.hit_ev(up, total, shoe);
+        let double = first_two.then(|| self.double_ev(up, total, shoe));
+        let surrender = (first_two && self.rules.surrender).then_some(-0.5);
+
+        let split =
+            (self.rules.splitting && first_two && value_index(&hand[0]) == value_index(&hand[1]))
+                .then(|| self.split_ev(up, value_index(&hand[0]) as u32 + 1, shoe));
+
+        ActionValues {
+            stand,
+            hit,
+            double,
+            split,
+            surrender,
+        }
+    }
+
+    /// The chance of each final dealer total, drawing the hole card and any hits from the shoe.
+    fn dealer_outcomes(&mut self, dealer: Total, shoe: &ShoeComposition) -> DealerOutcomes {
+        let mut outcomes = [0.0; 6];
+        if dealer.is_bust() {
+            outcomes[5] = 1.0;
+            return outcomes;
+        }
+
+        let best = dealer.best();
+        let hits_soft_17 = self.rules.dealer_hits_soft_17 && best == 17 && dealer.is_soft();
+        if best >= 17 && !hits_soft_17 {
+            outcomes[best as usize - 17] = 1.0;
+            return outcomes;
+        }
+
+        if let Some(memo) = self.dealer_memo.get(&(dealer, *shoe)) {
+            return *memo;
+        }
+
+        for (value, chance, rest) in shoe.draws() {
+            let next = self.dealer_outcomes(dealer.add(value), &rest);
+            outcomes
+                .iter_mut()
+                .zip(next)
+                .for_each(|(o, n)| *o += chance * n);
+        }
+
+        self.dealer_memo.insert((dealer, *shoe), outcomes);
+        outcomes
+    }
+
+    fn stand_ev(&mut self, up: u32, player: Total, natural: bool, shoe: &ShoeComposition) -> f64 {
+        let dealer = self.dealer_outcomes(Total::default().add(up), shoe);
+        let dealer_bust = dealer[5];
+
+        if player.is_bust() {
+            // Both busting is a push.
+            return -(1.0 - dealer_bust);
+        }
+
+        if natural {
+            return 3.0 * (1.0 - dealer[4]);
+        }
+
+        let player_total = player.best();
+        dealer_bust
+            + dealer[..5]
+                .iter()
+                .enumerate()
+                .map(|(i, chance)| match (i as u32 + 17).cmp(&player_total) {
+                    std::cmp::Ordering::Less => *chance,
+                    std::cmp::Ordering::Equal => 0.0,
+                    std::cmp::Ordering::Greater => -chance,
+                })
+                .sum::<f64>()
+    }
+
+    /// Takes a card and then plays on as well as possible.
+    fn hit_ev(&mut self, up: u32, player: Total, shoe: &ShoeComposition) -> f64 {
+        if let Some(memo) = self.hit_memo.get(&(up, player, *shoe)) {
+            return *memo;
+        }
+
+        let mut ev = 0.0;
+        for (value, chance, rest) in shoe.draws() {
+            let next = player.add(value);
+            let stand = self.stand_ev(up, next, false, &rest);
+            ev += chance
+                * if next.is_bust() || next.best() == 21 {
+                    stand
+                } else {
+                    stand.max(self.hit_ev(up, next, &rest))
+                };
+        }
+
+        self.hit_memo.insert((up, player, *shoe), ev);
+        ev
+    }
+
+    /// Doubles the bet and takes exactly one more card.
+    fn double_ev(&mut self, up: u32, player: Total, shoe: &ShoeComposition) -> f64 {
+        let mut ev = 0.0;
+        for (value, chance, rest) in shoe.draws() {
+            ev += chance * 2.0 * self.stand_ev(up, player.add(value), false, &rest);
+        }
+        ev
+    }
+
+    /// Splits the pair into two hands that each get one more card and are then played on.
+    fn split_ev(&mut self, up: u32, value: u32, shoe: &ShoeComposition) -> f64 {
+        let one_card = Total::default().add(value);
+        let mut ev = 0.0;
+
+        for (drawn, chance, rest) in shoe.draws() {
+            let hand = one_card.add(drawn);
+            let stand = self.stand_ev(up, hand, false, &rest);
+            let hand_ev = if value == 1 {
+                stand
+            } else {
+                let mut best = stand.max(self.hit_ev(up, hand, &rest));
+                if self.rules.double_after_split {
+                    best = best.max(self.double_ev(up, hand, &rest));
+                }
+                best
+            };
+            ev += chance * hand_ev;
+        }
+
+        2.0 * ev
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(cards: &str) -> Vec<Card> {
+        cards.split(' ').map(|c| c.parse().unwrap()).collect()
+    }
+
+    fn card(card: &str) -> Card {
+        card.parse().unwrap()
+    }
+
+    /// A shoe holding nothing but sixes, so every draw is known in advance.
+    fn sixes() -> ShoeComposition {
+        let mut counts = [0; 10];
+        counts[5] = 8;
+        ShoeComposition { counts }
+    }
+
+    #[test]
+    fn a_fresh_shoe_holds_every_card() {
+        assert_eq!(ShoeComposition::new(1).remaining(), 52);
+        assert_eq!(ShoeComposition::new(6).remaining(), 312);
+        assert_eq!(
+            ShoeComposition::without(1, &cards("AS KH 6D")).remaining(),
+            49
+        );
+    }
+
+    #[test]
+    fn standing_on_20_against_a_dealer_6() {
+        let mut calculator = EvCalculator::new(&TableRules::default());
+        let hand = cards("KS QH");
+        let up = card("6D");
+        let shoe = ShoeComposition::without(1, &[hand.clone(), vec![up.clone()]].concat());
+
+        let values = calculator.evaluate(&hand, &up, &shoe);
+        assert!((0.68..0.73).contains(&values.stand), "{}", values);
+        assert!(values.hit < 0.0, "{}", values);
+        assert_eq!(values.best().0, StrategyAction::Stand);
+        assert_eq!(values.split, None);
+        assert_eq!(values.surrender, None);
+    }
+
+    #[test]
+    fn a_natural_pays_three_to_one() {
+        let mut calculator = EvCalculator::new(&TableRules::default());
+
+        // The dealer draws to 16 and busts on the next six.
+        let values = calculator.evaluate(&cards("AS KH"), &card("TD"), &sixes());
+        assert_eq!(values.stand, 3.0);
+
+        // The dealer draws to 21, which pushes.
+        let values = calculator.evaluate(&cards("AS KH"), &card("9D"), &sixes());
+        assert_eq!(values.stand, 0.0);
+
+        // Three cards to 21 are only paid even money.
+        let values = calculator.evaluate(&cards("5S 5H AC"), &card("TD"), &sixes());
+        assert_eq!(values.stand, 1.0);
+    }
+
+    #[test]
+    fn both_busting_is_a_push() {
+        let mut calculator = EvCalculator::new(&TableRules::default());
+
+        let values = calculator.evaluate(&cards("TS TH 5C"), &card("TD"), &sixes());
+        assert_eq!(values.stand, 0.0);
+
+        // The dealer makes 17 from a 5 and two sixes.
+        let values = calculator.evaluate(&cards("TS TH 5C"), &card("5D"), &sixes());
+        assert_eq!(values.stand, -1.0);
+    }
+
+    #[test]
+    fn the_dealer_hits_soft_17_only_when_the_rules_say_so() {
+        let soft_17 = Total::default().add(1).add(6);
+        let shoe = ShoeComposition::new(1);
+
+        let outcomes = EvCalculator::new(&TableRules::default()).dealer_outcomes(soft_17, &shoe);
+        assert_eq!(outcomes, [1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+
+        let rules = TableRules {
+            dealer_hits_soft_17: true,
+            ..TableRules::default()
+        };
+        let outcomes = EvCalculator::new(&rules).dealer_outcomes(soft_17, &shoe);
+        assert!(outcomes[0] < 1.0);
+        assert!((outcomes.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn memoized_dealer_outcomes_match_a_fresh_calculation() {
+        let rules = TableRules::default();
+        let hand = cards("9S 7H");
+        let up = card("TD");
+        let shoe = ShoeComposition::without(2, &[hand.clone(), vec![up.clone()]].concat());
+
+        let mut calculator = EvCalculator::new(&rules);
+        let first = calculator.evaluate(&hand, &up, &shoe);
+        assert!(!calculator.dealer_memo.is_empty());
+        assert!(!calculator.hit_memo.is_empty());
+
+        let outcomes = calculator.dealer_outcomes(Total::default().add(10), &shoe);
+        assert!((outcomes.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        assert_eq!(calculator.evaluate(&hand, &up, &shoe), first);
+        assert_eq!(EvCalculator::new(&rules).evaluate(&hand, &up, &shoe), first);
+    }
+}
@@ -0,0 +1,150 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sha2::{Digest, Sha256};
+
+use crate::card::{create_playing_deck, Card};
+
+/// The secret the server shuffles each shoe from. It's only revealed once the shoe is finished.
+pub type ServerSeed = [u8; 32];
+
+/// Combines the server's seed with the seeds the clients sent in to get the key the shoe is
+/// shuffled with, so neither side can pick the order of the cards on its own.
+pub fn shuffle_key(server_seed: &ServerSeed, client_seeds: &[String]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(server_seed);
+    for seed in client_seeds {
+        hasher.update((seed.len() as u32).to_be_bytes());
+        hasher.update(seed.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Shuffles a shoe from the seeds. Like `create_playing_deck` the cards are dealt from the back.
+pub fn shuffled_shoe(decks: u8, server_seed: &ServerSeed, client_seeds: &[String]) -> Vec<Card> {
+    let mut rng = ChaCha8Rng::from_seed(shuffle_key(server_seed, client_seeds));
+    create_playing_deck(decks, &mut rng)
+}
+
+/// The cards of a shoe in the order they'll be dealt.
+pub fn deal_order(decks: u8, server_seed: &ServerSeed, client_seeds: &[String]) -> Vec<Card> {
+    let mut cards = shuffled_shoe(decks, server_seed, client_seeds);
+    cards.reverse();
+    cards
+}
+
+/// The hash published before a shoe is dealt. It covers the server seed and every card in the
+/// order it will be dealt, so the shoe can't be changed once the clients have it.
+pub fn commitment(server_seed: &ServerSeed, deal_order: &[Card]) -> String {
+    let cards: Vec<String> = deal_order.iter().map(|c| c.to_string()).collect();
+    let mut hasher = Sha256::new();
+    hasher.update(hex::encode(server_seed));
+    hasher.update(":");
+    hasher.update(cards.join(" "));
+    hex::encode(hasher.finalize())
+}
+
+pub fn parse_server_seed(hex_seed: &str) -> Result<ServerSeed, String> {
+    let bytes = hex::decode(hex_seed).map_err(|e| format!("Invalid server seed: {}", e))?;
+    bytes
+        .try_into()
+        .map_err(|_| "Invalid server seed: expected 32 bytes".to_string())
+}
+
+/// Rebuilds a finished shoe from its revealed seeds and checks it against the commitment and the
+/// cards that were seen being dealt from it. Cards that weren't seen are `None` and are skipped.
+/// Returns the number of cards checked.
+pub fn verify_shoe(
+    decks: u8,
+    commitment_hash: &str,
+    server_seed: &str,
+    client_seeds: &[String],
+    dealt: &[Option<Card>],
+) -> Result<usize, String> {
+    let server_seed = parse_server_seed(server_seed)?;
+    let order = deal_order(decks, &server_seed, client_seeds);
+
+    if commitment(&server_seed, &order) != commitment_hash {
+        return Err("The revealed seed doesn't match the commitment".to_string());
+    }
+    if dealt.len() > order.len() {
+        return Err(format!(
+            "{} cards were dealt from a shoe of {}",
+            dealt.len(),
+            order.len()
+        ));
+    }
+
+    let mut checked = 0;
+    for (i, (seen, expected)) in dealt.iter().zip(&order).enumerate() {
+        if let Some(seen) = seen {
+            if seen != expected {
+                return Err(format!(
+                    "Card {} was {} but the shuffle dealt {}",
+                    i + 1,
+                    seen,
+                    expected
+                ));
+            }
+            checked += 1;
+        }
+    }
+
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: ServerSeed = [7; 32];
+
+    fn client_seeds() -> Vec<String> {
+        vec!["alice".to_string(), "bob".to_string()]
+    }
+
+    /// Commits to a two deck shoe and deals the first `n` cards from it, the way the server does.
+    fn dealt_shoe(n: usize) -> (String, Vec<Option<Card>>) {
+        let order = deal_order(2, &SEED, &client_seeds());
+        let dealt = order.iter().take(n).cloned().map(Some).collect();
+        (commitment(&SEED, &order), dealt)
+    }
+
+    #[test]
+    fn shoe_round_trips() {
+        let (hash, dealt) = dealt_shoe(20);
+        let checked = verify_shoe(2, &hash, &hex::encode(SEED), &client_seeds(), &dealt);
+        assert_eq!(checked, Ok(20));
+    }
+
+    #[test]
+    fn unseen_cards_are_skipped() {
+        let (hash, mut dealt) = dealt_shoe(10);
+        dealt[3] = None;
+        let checked = verify_shoe(2, &hash, &hex::encode(SEED), &client_seeds(), &dealt);
+        assert_eq!(checked, Ok(9));
+    }
+
+    #[test]
+    fn wrong_seed_is_rejected() {
+        let (hash, dealt) = dealt_shoe(10);
+        let other = hex::encode([8u8; 32]);
+        assert!(verify_shoe(2, &hash, &other, &client_seeds(), &dealt).is_err());
+        assert!(verify_shoe(2, &hash, "not hex", &client_seeds(), &dealt).is_err());
+    }
+
+    #[test]
+    fn swapped_card_is_rejected() {
+        let (hash, mut dealt) = dealt_shoe(10);
+        let swap = dealt.iter().position(|card| *card != dealt[0]).unwrap();
+        dealt.swap(0, swap);
+        let checked = verify_shoe(2, &hash, &hex::encode(SEED), &client_seeds(), &dealt);
+        assert!(checked.unwrap_err().starts_with("Card 1 was"));
+    }
+
+    #[test]
+    fn changed_client_seed_is_rejected() {
+        let (hash, dealt) = dealt_shoe(10);
+        let seeds = vec!["alice".to_string(), "mallory".to_string()];
+        assert!(verify_shoe(2, &hash, &hex::encode(SEED), &seeds, &dealt).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{card::Card, rules::TableRules, web_socket::EndState};
+
+/// One round of play, written as a single line of the hand history log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundRecord {
+    pub table_id: String,
+    /// Counts up from one for each round dealt at the table.
+    pub round: u64,
+    pub rules: TableRules,
+    /// See `rules_hash`. Lets tools group rounds played under the same rules.
+    pub rules_hash: String,
+    pub shoe_id: u32,
+    /// The commitment to the shoe the round was dealt from. Its seed is logged in a
+    /// [`ShoeReveal`] once the shoe is finished.
+    #[serde(default)]
+    pub commitment: String,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub seats: Vec<SeatRecord>,
+    /// Everything that happened in the round in order, including every card dealt.
+    pub events: Vec<RoundEvent>,
+    pub dealer_hand: Vec<Card>,
+    pub dealer_hand_value: u32,
+    pub settlements: Vec<Settlement>,
+}
+
+/// A player seated at the table when the round started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeatRecord {
+    pub position: usize,
+    pub user_name: String,
+    pub chips: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundEvent {
+    /// Milliseconds since the Unix epoch.
+    pub at: u64,
+    pub kind: RoundEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RoundEventKind {
+    /// The dealer's up card followed by the hole card.
+    DealerDealt {
+        cards: Vec<Card>,
+    },
+    TurnStarted {
+        user_name: String,
+    },
+    Bet {
+        user_name: String,
+        amount: u32,
+    },
+    /// A bet raised after the cards were dealt.
+    Double {
+        user_name: String,
+        amount: u32,
+    },
+    Hit {
+        user_name: String,
+    },
+    CardsDealt {
+        user_name: String,
+        cards: Vec<Card>,
+    },
+    /// The player ended their turn with this hand value.
+    Stand {
+        user_name: String,
+        hand_value: u32,
+    },
+    DealerDrew {
+        card: Card,
+    },
+    ShoeShuffled {
+        shoe_id: u32,
+        #[serde(default)]
+        commitment: String,
+    },
+}
+
+/// The seeds of a shoe that has been dealt, written to the hand history log as a line of its own
+/// once the shoe is revealed to the clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShoeReveal {
+    pub table_id: String,
+    pub shoe_id: u32,
+    /// As hex.
+    pub server_seed: String,
+    pub client_seeds: Vec<String>,
+    /// The number of cards dealt from the shoe.
+    pub dealt: usize,
+    /// Milliseconds since the Unix epoch.
+    pub revealed_at: u64,
+}
+
+/// How a player's hand was settled against the dealer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settlement {
+    pub user_name: String,
+    pub hand: Vec<Card>,
+    pub hand_value: u32,
+    pub bet: u32,
+    pub end_state: EndState,
+    /// The player's chips once the bet was paid out or taken.
+    pub chips: u32,
+}
+
+/// A short hash of the rules.
+pub fn rules_hash(rules: &TableRules) -> String {
+    let json = serde_json::to_string(rules).unwrap();
+    hex::encode(&Sha256::digest(json)[..8])
+}
+
+/// Reads every round from the contents of a hand history log, skipping shoe reveals and lines
+/// that can't be parsed.
+pub fn parse_history(log: &str) -> Vec<RoundRecord> {
+    log.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Reads every shoe revealed in the contents of a hand history log.
+pub fn parse_reveals(log: &str) -> Vec<ShoeReveal> {
+    log.lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// The stretch of time results are counted over.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeWindow {
+    /// Since midnight UTC.
+    Today,
+    /// The last seven days.
+    Week,
+    #[default]
+    #[serde(alias = "all")]
+    AllTime,
+}
+
+impl TimeWindow {
+    /// The earliest time in the window, given the current time. Both are milliseconds since the
+    /// Unix epoch.
+    pub fn start(&self, now: u64) -> u64 {
+        match self {
+            TimeWindow::Today => now - now % DAY_MS,
+            TimeWindow::Week => now.saturating_sub(7 * DAY_MS),
+            TimeWindow::AllTime => 0,
+        }
+    }
+}
+
+/// The query string for `GET /leaderboard`. Pages count from one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardQuery {
+    pub window: TimeWindow,
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl Default for LeaderboardQuery {
+    fn default() -> Self {
+        LeaderboardQuery {
+            window: TimeWindow::AllTime,
+            page: 1,
+            per_page: 20,
+        }
+    }
+}
+
+/// The query string for `GET /players/{name}/stats`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerStatsQuery {
+    pub window: TimeWindow,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardResponse {
+    pub window: TimeWindow,
+    pub page: usize,
+    pub per_page: usize,
+    /// The number of players with results in the window.
+    pub total: usize,
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+/// A player's results in the window. Players are ranked by the chips they've won or lost.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub user_name: String,
+    /// Chips won less chips lost.
+    pub net_chips: i64,
+    /// The player's chips after their latest hand.
+    pub bankroll: u32,
+    pub biggest_win: u32,
+    pub hands: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatsResponse {
+    pub user_name: String,
+    pub window: TimeWindow,
+    pub hands: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub pushes: u32,
+    pub blackjacks: u32,
+    pub net_chips: i64,
+    /// The player's chips after their latest hand in the window.
+    pub bankroll: u32,
+    pub biggest_win: u32,
+    pub biggest_loss: u32,
+    /// Every table the player has results at in the window.
+    pub tables: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Noon on the tenth day after the epoch.
+    const NOW: u64 = 10 * DAY_MS + DAY_MS / 2;
+
+    #[test]
+    fn today_starts_at_midnight_utc() {
+        assert_eq!(TimeWindow::Today.start(NOW), 10 * DAY_MS);
+        assert_eq!(TimeWindow::Today.start(10 * DAY_MS), 10 * DAY_MS);
+        assert_eq!(TimeWindow::Today.start(11 * DAY_MS - 1), 10 * DAY_MS);
+    }
+
+    #[test]
+    fn week_starts_seven_days_ago() {
+        assert_eq!(TimeWindow::Week.start(NOW), NOW - 7 * DAY_MS);
+        assert_eq!(TimeWindow::Week.start(DAY_MS), 0);
+    }
+
+    #[test]
+    fn all_time_starts_at_the_epoch() {
+        assert_eq!(TimeWindow::AllTime.start(NOW), 0);
+    }
+
+    #[test]
+    fn windows_are_read_from_the_query_string() {
+        let window = |s: &str| serde_json::from_str::<TimeWindow>(&format!("\"{}\"", s)).unwrap();
+        assert_eq!(window("today"), TimeWindow::Today);
+        assert_eq!(window("week"), TimeWindow::Week);
+        assert_eq!(window("all-time"), TimeWindow::AllTime);
+        assert_eq!(window("all"), TimeWindow::AllTime);
+    }
+}
@@ -1,4 +1,14 @@
+pub mod admin;
 pub mod card;
+pub mod card_art;
+pub mod counting;
+pub mod ev;
+pub mod fairness;
 pub mod helpers;
+pub mod history;
+pub mod leaderboard;
 pub mod player;
+pub mod rules;
+pub mod strategy;
+pub mod tournament;
 pub mod web_socket;
@@ -21,7 +21,7 @@ pub struct Player {
     pub current_bet: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PlayerAction {
     Hit,
     Stand,
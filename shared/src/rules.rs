@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+/// The rules a table is played with. The defaults describe how the server deals today.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableRules {
+    /// The number of decks shuffled together into the shoe.
+    pub decks: u8,
+    /// Whether the dealer hits a soft 17. When false the dealer stands on every 17.
+    pub dealer_hits_soft_17: bool,
+    /// Whether pairs can be split into two hands.
+    pub splitting: bool,
+    /// Whether a hand can be doubled after a split.
+    pub double_after_split: bool,
+    /// Whether late surrender is offered on the first two cards.
+    pub surrender: bool,
+    /// The smallest opening bet.
+    pub min_bet: u32,
+    /// The largest opening bet, if there is one. Doubling down can take the bet past it.
+    pub max_bet: Option<u32>,
+}
+
+impl Default for TableRules {
+    fn default() -> Self {
+        TableRules {
+            decks: 1,
+            dealer_hits_soft_17: false,
+            splitting: false,
+            double_after_split: false,
+            surrender: false,
+            min_bet: 1,
+            max_bet: None,
+        }
+    }
+}
+
+impl TableRules {
+    /// Checks an opening bet against the table limits and the player's chips.
+    pub fn check_bet(&self, amount: u32, chips: u32) -> Result<(), String> {
+        if amount > chips {
+            return Err("You don't have enough chips to cover that bet!".to_owned());
+        }
+        // A player short of the minimum can still go all in.
+        if amount < self.min_bet.min(chips) {
+            return Err(format!("The minimum bet is {} chips.", self.min_bet));
+        }
+        match self.max_bet {
+            Some(max_bet) if amount > max_bet => {
+                Err(format!("The maximum bet is {} chips.", max_bet))
+            }
+            _ => Ok(()),
+        }
+    }
+}
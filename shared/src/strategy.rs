@@ -0,0 +1,639 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{card::Card, helpers::get_rank_value, player::PlayerAction, rules::TableRules};
+
+/// The move basic strategy recommends for a hand.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StrategyAction {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+}
+
+impl StrategyAction {
+    /// Whether taking the player's action follows this recommendation.
+    pub fn matches(&self, action: &PlayerAction) -> bool {
+        matches!(
+            (self, action),
+            (StrategyAction::Hit, PlayerAction::Hit)
+                | (StrategyAction::Stand, PlayerAction::Stand)
+                | (StrategyAction::Double, PlayerAction::Double)
+        )
+    }
+}
+
+impl fmt::Display for StrategyAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                StrategyAction::Hit => "Hit",
+                StrategyAction::Stand => "Stand",
+                StrategyAction::Double => "Double",
+                StrategyAction::Split => "Split",
+                StrategyAction::Surrender => "Surrender",
+            }
+        )
+    }
+}
+
+/// How a hand is looked up in the strategy tables.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandKind {
+    /// A total where every ace counts as one.
+    Hard(u32),
+    /// A total where one ace counts as eleven.
+    Soft(u32),
+    /// Two cards of the same value. Holds the value of one card, with aces as eleven.
+    Pair(u32),
+}
+
+/// An entry in a strategy table. Doubling, splitting and surrendering aren't always allowed so
+/// some entries say what to do instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Entry {
+    /// Hit.
+    H,
+    /// Stand.
+    S,
+    /// Double, otherwise hit.
+    Dh,
+    /// Double, otherwise stand.
+    Ds,
+    /// Split.
+    P,
+    /// Surrender, otherwise hit.
+    Rh,
+    /// Surrender, otherwise stand.
+    Rs,
+    /// Surrender, otherwise split.
+    Rp,
+}
+
+impl Entry {
+    fn from_code(code: &str) -> Self {
+        match code {
+            "H" => Entry::H,
+            "S" => Entry::S,
+            "Dh" => Entry::Dh,
+            "Ds" => Entry::Ds,
+            "P" => Entry::P,
+            "Rh" => Entry::Rh,
+            "Rs" => Entry::Rs,
+            "Rp" => Entry::Rp,
+            _ => panic!("Unknown strategy table entry: {}", code),
+        }
+    }
+}
+
+// The base tables are for four or more decks where the dealer stands on soft 17 and doubling
+// after a split is allowed. `StrategyTable::new` adjusts them for other rules.
+//
+// Dealer up card:    2  3  4  5  6  7  8  9  T  A
+const HARD: [(u32, &str); 8] = [
+    (9, " H  Dh Dh Dh Dh H  H  H  H  H"),
+    (10, "Dh Dh Dh Dh Dh Dh Dh Dh H  H"),
+    (11, "Dh Dh Dh Dh Dh Dh Dh Dh Dh H"),
+    (12, "H  H  S  S  S  H  H  H  H  H"),
+    (13, "S  S  S  S  S  H  H  H  H  H"),
+    (14, "S  S  S  S  S  H  H  H  H  H"),
+    (15, "S  S  S  S  S  H  H  H  Rh H"),
+    (16, "S  S  S  S  S  H  H  Rh Rh Rh"),
+];
+
+const SOFT: [(u32, &str); 7] = [
+    (13, "H  H  H  Dh Dh H  H  H  H  H"),
+    (14, "H  H  H  Dh Dh H  H  H  H  H"),
+    (15, "H  H  Dh Dh Dh H  H  H  H  H"),
+    (16, "H  H  Dh Dh Dh H  H  H  H  H"),
+    (17, "H  Dh Dh Dh Dh H  H  H  H  H"),
+    (18, "S  Ds Ds Ds Ds S  S  H  H  H"),
+    (19, "S  S  S  S  S  S  S  S  S  S"),
+];
+
+const PAIRS: [(u32, &str); 10] = [
+    (2, "P  P  P  P  P  P  H  H  H  H"),
+    (3, "P  P  P  P  P  P  H  H  H  H"),
+    (4, "H  H  H  P  P  H  H  H  H  H"),
+    (5, "Dh Dh Dh Dh Dh Dh Dh Dh H  H"),
+    (6, "P  P  P  P  P  H  H  H  H  H"),
+    (7, "P  P  P  P  P  P  H  H  H  H"),
+    (8, "P  P  P  P  P  P  P  P  P  P"),
+    (9, "P  P  P  P  P  S  P  P  S  S"),
+    (10, "S  S  S  S  S  S  S  S  S  S"),
+    (11, "P  P  P  P  P  P  P  P  P  P"),
+];
+
+/// Basic strategy for one set of table rules.
+#[derive(Debug, Clone)]
+pub struct StrategyTable {
+    rules: TableRules,
+    /// Indexed by the hand total and then the dealer's up card.
+    hard: [[Entry; 10]; 22],
+    soft: [[Entry; 10]; 22],
+    /// Indexed by the value of one of the paired cards.
+    pairs: [[Entry; 10]; 12],
+}
+
+impl StrategyTable {
+    /// Builds the tables for the given rules.
+    pub fn new(rules: &TableRules) -> Self {
+        let mut table = StrategyTable {
+            rules: rules.clone(),
+            hard: [[Entry::H; 10]; 22],
+            soft: [[Entry::H; 10]; 22],
+            pairs: [[Entry::H; 10]; 12],
+        };
+
+        // Always stand on a hard 17 or more.
+        (17..22).for_each(|t| table.hard[t] = [Entry::S; 10]);
+        (20..22).for_each(|t| table.soft[t] = [Entry::S; 10]);
+
+        fill(&mut table.hard, &HARD);
+        fill(&mut table.soft, &SOFT);
+        fill(&mut table.pairs, &PAIRS);
+
+        if rules.decks <= 2 {
+            table.hard[9][0] = Entry::Dh;
+            table.hard[11][9] = Entry::Dh;
+        }
+
+        if rules.decks == 1 {
+            table.hard[8][3] = Entry::Dh;
+            table.hard[8][4] = Entry::Dh;
+            table.soft[13][2] = Entry::Dh;
+            table.soft[14][2] = Entry::Dh;
+            table.soft[17][0] = Entry::Dh;
+            table.soft[19][4] = Entry::Ds;
+            if !rules.dealer_hits_soft_17 {
+                table.soft[18][9] = Entry::S;
+            }
+        }
+
+        if rules.dealer_hits_soft_17 {
+            table.hard[11][9] = Entry::Dh;
+            table.hard[15][9] = Entry::Rh;
+            table.hard[17][9] = Entry::Rs;
+            table.soft[18][0] = Entry::Ds;
+            table.soft[19][4] = Entry::Ds;
+            table.pairs[8][9] = Entry::Rp;
+        }
+
+        // Small pairs are only worth splitting against strong up cards when the new hands can
+        // be doubled.
+        if !rules.double_after_split {
+            table.pairs[2][0] = Entry::H;
+            table.pairs[2][1] = Entry::H;
+            table.pairs[3][0] = Entry::H;
+            table.pairs[3][1] = Entry::H;
+            table.pairs[4] = table.hard[8];
+            table.pairs[6][0] = Entry::H;
+        }
+
+        table
+    }
+
+    /// Returns the basic strategy action for the hand against the dealer's up card.
+    pub fn action(&self, hand: &[Card], dealer_up: &Card) -> StrategyAction {
+        self.lookup(hand, dealer_up, self.rules.splitting, self.rules.surrender)
+    }
+
+    /// Returns the basic strategy action among the moves the server deals: hit, stand or
+    /// double. A pair is played on its total and surrender falls back to hitting or standing.
+    pub fn playable_action(&self, hand: &[Card], dealer_up: &Card) -> StrategyAction {
+        self.lookup(hand, dealer_up, false, false)
+    }
+
+    fn lookup(
+        &self,
+        hand: &[Card],
+        dealer_up: &Card,
+        splitting: bool,
+        surrender: bool,
+    ) -> StrategyAction {
+        let up = (get_rank_value(dealer_up.rank.clone()) - 2) as usize;
+        let first_two = hand.len() == 2;
+
+        let entry = match classify_hand(hand) {
+            HandKind::Pair(value) if splitting => self.pairs[value as usize][up],
+            _ => {
+                let (total, soft) = hand_total(hand);
+                if total > 21 {
+                    return StrategyAction::Stand;
+                } else if soft {
+                    self.soft[total as usize][up]
+                } else {
+                    self.hard[total as usize][up]
+                }
+            }
+        };
+
+        let surrender = surrender && first_two;
+        match entry {
+            Entry::H => StrategyAction::Hit,
+            Entry::S => StrategyAction::Stand,
+            Entry::Dh if first_two => StrategyAction::Double,
+            Entry::Dh => StrategyAction::Hit,
+            Entry::Ds if first_two => StrategyAction::Double,
+            Entry::Ds => StrategyAction::Stand,
+            Entry::P => StrategyAction::Split,
+            Entry::Rh | Entry::Rs | Entry::Rp if surrender => StrategyAction::Surrender,
+            Entry::Rh => StrategyAction::Hit,
+            Entry::Rs => StrategyAction::Stand,
+            Entry::Rp => StrategyAction::Split,
+        }
+    }
+}
+
+fn fill<const N: usize>(table: &mut [[Entry; 10]; N], rows: &[(u32, &str)]) {
+    for (total, row) in rows {
+        row.split_whitespace()
+            .enumerate()
+            .for_each(|(up, code)| table[*total as usize][up] = Entry::from_code(code));
+    }
+}
+
+/// Returns the best total for the hand and whether an ace is being counted as eleven.
+pub fn hand_total(hand: &[Card]) -> (u32, bool) {
+    let mut total = 0;
+    let mut has_ace = false;
+
+    for card in hand {
+        match get_rank_value(card.rank.clone()) {
+            11 => {
+                has_ace = true;
+                total += 1;
+            }
+            value => total += value,
+        }
+    }
+
+    if has_ace && total + 10 <= 21 {
+        (total + 10, true)
+    } else {
+        (total, false)
+    }
+}
+
+pub fn classify_hand(hand: &[Card]) -> HandKind {
+    if hand.len() == 2 {
+        let first = get_rank_value(hand[0].rank.clone());
+        if first == get_rank_value(hand[1].rank.clone()) {
+            return HandKind::Pair(first);
+        }
+    }
+
+    match hand_total(hand) {
+        (total, true) => HandKind::Soft(total),
+        (total, false) => HandKind::Hard(total),
+    }
+}
+
+/// Returns the basic strategy action for the hand. Builds the tables each call so hold on to a
+/// `StrategyTable` when looking up many hands.
+pub fn basic_strategy(hand: &[Card], dealer_up: &Card, rules: &TableRules) -> StrategyAction {
+    StrategyTable::new(rules).action(hand, dealer_up)
+}
+
+/// Decides how to play a hand without anyone at the keyboard, such as in the simulator.
+pub trait PlayerStrategy {
+    fn name(&self) -> &'static str;
+
+    fn action(&self, hand: &[Card], dealer_up: &Card) -> PlayerAction;
+}
+
+impl PlayerStrategy for StrategyTable {
+    fn name(&self) -> &'static str {
+        "basic"
+    }
+
+    fn action(&self, hand: &[Card], dealer_up: &Card) -> PlayerAction {
+        match self.playable_action(hand, dealer_up) {
+            StrategyAction::Stand => PlayerAction::Stand,
+            StrategyAction::Double => PlayerAction::Double,
+            // Never recommended, as the server doesn't support splitting or surrender yet.
+            StrategyAction::Hit | StrategyAction::Split | StrategyAction::Surrender => {
+                PlayerAction::Hit
+            }
+        }
+    }
+}
+
+/// Plays like the dealer, hitting anything below 17.
+pub struct MimicDealer;
+
+impl PlayerStrategy for MimicDealer {
+    fn name(&self) -> &'static str {
+        "mimic-dealer"
+    }
+
+    fn action(&self, hand: &[Card], _dealer_up: &Card) -> PlayerAction {
+        match hand_total(hand).0 {
+            0..=16 => PlayerAction::Hit,
+            _ => PlayerAction::Stand,
+        }
+    }
+}
+
+/// Never takes a card that could bust the hand.
+pub struct NeverBust;
+
+impl PlayerStrategy for NeverBust {
+    fn name(&self) -> &'static str {
+        "never-bust"
+    }
+
+    fn action(&self, hand: &[Card], _dealer_up: &Card) -> PlayerAction {
+        match hand_total(hand) {
+            (total, false) if total >= 12 => PlayerAction::Stand,
+            (total, _) if total >= 17 => PlayerAction::Stand,
+            _ => PlayerAction::Hit,
+        }
+    }
+}
+
+/// Stands on every hand, leaving the dealer to bust.
+pub struct AlwaysStand;
+
+impl PlayerStrategy for AlwaysStand {
+    fn name(&self) -> &'static str {
+        "always-stand"
+    }
+
+    fn action(&self, _hand: &[Card], _dealer_up: &Card) -> PlayerAction {
+        PlayerAction::Stand
+    }
+}
+
+/// Looks up a strategy by name, such as `basic`, `mimic-dealer`, `never-bust` or
+/// `always-stand`.
+pub fn strategy_from_name(
+    name: &str,
+    rules: &TableRules,
+) -> Option<Box<dyn PlayerStrategy + Send + Sync>> {
+    match name.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "basic" => Some(Box::new(StrategyTable::new(rules))),
+        "mimicdealer" | "dealer" => Some(Box::new(MimicDealer)),
+        "neverbust" => Some(Box::new(NeverBust)),
+        "alwaysstand" | "stand" => Some(Box::new(AlwaysStand)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use StrategyAction::*;
+
+    fn cards(cards: &str) -> Vec<Card> {
+        cards.split(' ').map(|c| c.parse().unwrap()).collect()
+    }
+
+    /// A six deck shoe where the dealer stands on soft 17 and every move is allowed.
+    fn rules() -> TableRules {
+        TableRules {
+            decks: 6,
+            splitting: true,
+            double_after_split: true,
+            surrender: true,
+            ..TableRules::default()
+        }
+    }
+
+    /// Checks the recommendation for each hand and dealer up card.
+    fn assert_actions(rules: &TableRules, expected: &[(&str, &str, StrategyAction)]) {
+        let table = StrategyTable::new(rules);
+        for (hand, up, action) in expected {
+            let up = up.parse().unwrap();
+            assert_eq!(
+                table.action(&cards(hand), &up),
+                *action,
+                "{} against {}",
+                hand,
+                up
+            );
+        }
+    }
+
+    #[test]
+    fn hands_are_classified() {
+        assert_eq!(classify_hand(&cards("AS AH")), HandKind::Pair(11));
+        assert_eq!(classify_hand(&cards("KS QH")), HandKind::Pair(10));
+        assert_eq!(classify_hand(&cards("AS 6H")), HandKind::Soft(17));
+        assert_eq!(classify_hand(&cards("AS 6H TD")), HandKind::Hard(17));
+        assert_eq!(classify_hand(&cards("8S 8H 2D")), HandKind::Hard(18));
+        assert_eq!(hand_total(&cards("AS AH 9D")), (21, true));
+    }
+
+    #[test]
+    fn the_base_tables() {
+        assert_actions(
+            &rules(),
+            &[
+                ("TS 2H", "2D", Hit),
+                ("TS 2H", "4D", Stand),
+                ("TS 6H", "7D", Hit),
+                ("TS 7H", "AD", Stand),
+                ("5S 4H", "2D", Hit),
+                ("5S 4H", "3D", Double),
+                ("6S 5H", "TD", Double),
+                ("6S 5H", "AD", Hit),
+                ("AS 7H", "2D", Stand),
+                ("AS 7H", "3D", Double),
+                ("AS 7H", "9D", Hit),
+                ("AS 8H", "6D", Stand),
+                ("AS 2H", "5D", Double),
+                ("AS 2H", "4D", Hit),
+                ("8S 8H", "AD", Split),
+                ("5S 5H", "6D", Double),
+                ("9S 9H", "7D", Stand),
+                ("9S 9H", "8D", Split),
+                ("2S 2H", "2D", Split),
+                ("AS AH", "TD", Split),
+                ("TS TH", "6D", Stand),
+            ],
+        );
+    }
+
+    #[test]
+    fn surrender_on_the_first_two_cards() {
+        assert_actions(
+            &rules(),
+            &[
+                ("TS 6H", "9D", Surrender),
+                ("TS 6H", "TD", Surrender),
+                ("TS 5H", "TD", Surrender),
+                ("TS 5H", "AD", Hit),
+                ("TS 4H 2D", "TD", Hit),
+            ],
+        );
+
+        let rules = TableRules {
+            surrender: false,
+            ..rules()
+        };
+        assert_actions(&rules, &[("TS 6H", "TD", Hit), ("TS 5H", "TD", Hit)]);
+    }
+
+    #[test]
+    fn doubles_fall_back_after_the_first_two_cards() {
+        assert_actions(
+            &rules(),
+            &[
+                ("5S 4H 2D", "6D", Hit),
+                ("AS 3H 4D", "4D", Stand),
+                ("AS 2H 4D", "3D", Hit),
+            ],
+        );
+    }
+
+    #[test]
+    fn two_deck_adjustments() {
+        let rules = TableRules {
+            decks: 2,
+            ..rules()
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("5S 4H", "2D", Double),
+                ("6S 5H", "AD", Double),
+                ("5S 3H", "5D", Hit),
+            ],
+        );
+    }
+
+    #[test]
+    fn one_deck_adjustments() {
+        let rules = TableRules {
+            decks: 1,
+            ..rules()
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("5S 4H", "2D", Double),
+                ("6S 5H", "AD", Double),
+                ("5S 3H", "5D", Double),
+                ("5S 3H", "6D", Double),
+                ("5S 3H", "4D", Hit),
+                ("AS 2H", "4D", Double),
+                ("AS 3H", "4D", Double),
+                ("AS 6H", "2D", Double),
+                ("AS 8H", "6D", Double),
+                ("AS 8H", "5D", Stand),
+                ("AS 7H", "AD", Stand),
+            ],
+        );
+    }
+
+    #[test]
+    fn dealer_hits_soft_17_adjustments() {
+        let rules = TableRules {
+            dealer_hits_soft_17: true,
+            ..rules()
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("6S 5H", "AD", Double),
+                ("TS 5H", "AD", Surrender),
+                ("TS 7H", "AD", Surrender),
+                ("TS 7H", "TD", Stand),
+                ("AS 7H", "2D", Double),
+                ("AS 8H", "6D", Double),
+                ("8S 8H", "AD", Surrender),
+            ],
+        );
+
+        let rules = TableRules {
+            surrender: false,
+            ..rules
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("TS 5H", "AD", Hit),
+                ("TS 7H", "AD", Stand),
+                ("8S 8H", "AD", Split),
+            ],
+        );
+    }
+
+    #[test]
+    fn one_deck_soft_18_against_an_ace_depends_on_soft_17() {
+        let s17 = TableRules {
+            decks: 1,
+            ..rules()
+        };
+        let h17 = TableRules {
+            dealer_hits_soft_17: true,
+            ..s17.clone()
+        };
+        assert_actions(&s17, &[("AS 7H", "AD", Stand)]);
+        assert_actions(&h17, &[("AS 7H", "AD", Hit), ("AS 7H", "2D", Double)]);
+    }
+
+    #[test]
+    fn no_double_after_split_adjustments() {
+        let rules = TableRules {
+            double_after_split: false,
+            ..rules()
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("2S 2H", "2D", Hit),
+                ("2S 2H", "3D", Hit),
+                ("2S 2H", "4D", Split),
+                ("3S 3H", "2D", Hit),
+                ("3S 3H", "4D", Split),
+                ("4S 4H", "5D", Hit),
+                ("6S 6H", "2D", Hit),
+                ("6S 6H", "3D", Split),
+            ],
+        );
+
+        // A pair of fours is played as a hard 8, which is doubled against a 5 or 6 from one deck.
+        let rules = TableRules { decks: 1, ..rules };
+        assert_actions(&rules, &[("4S 4H", "5D", Double), ("4S 4H", "4D", Hit)]);
+    }
+
+    #[test]
+    fn pairs_are_played_on_their_total_without_splitting() {
+        let rules = TableRules {
+            splitting: false,
+            surrender: false,
+            ..rules()
+        };
+        assert_actions(
+            &rules,
+            &[
+                ("8S 8H", "6D", Stand),
+                ("8S 8H", "TD", Hit),
+                ("AS AH", "6D", Hit),
+                ("5S 5H", "9D", Double),
+            ],
+        );
+    }
+
+    #[test]
+    fn playable_actions_never_split_or_surrender() {
+        let table = StrategyTable::new(&rules());
+        let up = |c: &str| -> Card { c.parse().unwrap() };
+
+        assert_eq!(table.action(&cards("8S 8H"), &up("6D")), Split);
+        assert_eq!(table.playable_action(&cards("8S 8H"), &up("6D")), Stand);
+        assert_eq!(table.action(&cards("TS 6H"), &up("TD")), Surrender);
+        assert_eq!(table.playable_action(&cards("TS 6H"), &up("TD")), Hit);
+        assert_eq!(
+            PlayerStrategy::action(&table, &cards("AS AH"), &up("6D")),
+            PlayerAction::Hit
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// How a tournament table is run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TournamentConfig {
+    /// The number of hands played before a winner is declared.
+    pub hands: u32,
+    /// Every player starts with the same chips.
+    pub starting_chips: u32,
+    pub eliminations: Vec<Elimination>,
+    /// Bets on the final hand are only shown once the hand is settled.
+    pub secret_final_bet: bool,
+}
+
+/// A checkpoint where everyone outside the top few chip counts is knocked out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Elimination {
+    pub after_hand: u32,
+    /// Players tied with the last place kept are kept too.
+    pub keep: usize,
+}
+
+/// Parses a checkpoint written as `HAND:KEEP`, such as `10:4` to keep the top four players
+/// after the tenth hand.
+impl FromStr for Elimination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hand, keep) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected HAND:KEEP, got '{}'", s))?;
+        let after_hand = hand
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' isn't a hand number", hand))?;
+        let keep = keep
+            .trim()
+            .parse()
+            .map_err(|_| format!("'{}' isn't a number of players", keep))?;
+
+        if after_hand == 0 || keep == 0 {
+            return Err("the hand and the number of players kept must be at least 1".to_owned());
+        }
+        Ok(Elimination { after_hand, keep })
+    }
+}
+
+/// A player's place in the tournament.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    pub rank: usize,
+    pub user_name: String,
+    pub chips: u32,
+    pub eliminated: bool,
+}
+
+impl fmt::Display for Standing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}. {} - {} chips",
+            self.rank, self.user_name, self.chips
+        )?;
+        if self.eliminated {
+            write!(f, " (out)")?;
+        }
+        Ok(())
+    }
+}
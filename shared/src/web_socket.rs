@@ -1,17 +1,34 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{card::Card, player::Player};
+use crate::{
+    card::Card,
+    player::Player,
+    rules::TableRules,
+    tournament::{Standing, TournamentConfig},
+};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct RegisterRequest {
     pub user_name: String,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct RegisterResponse {
     pub url: String,
     pub is_host: bool,
     pub id: String,
+    #[serde(default)]
+    pub rules: TableRules,
+    /// The chips every player starts with.
+    #[serde(default = "default_chips")]
+    pub chips: u32,
+    /// Set when the table is running a tournament.
+    #[serde(default)]
+    pub tournament: Option<TournamentConfig>,
+}
+
+fn default_chips() -> u32 {
+    500
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -24,8 +41,84 @@ pub enum PublishTrigger {
     CardsDrawn {
         cards: Vec<Card>,
     },
+    /// The shoe has been reshuffled, so any card count should start again.
+    ShoeShuffled {
+        decks: u8,
+    },
+    /// Sent before a shoe is dealt from. The commitment is a hash of the server's seed and the
+    /// shuffled shoe, which is made from that seed and the listed client seeds.
+    ShoeCommitted {
+        shoe_id: u32,
+        decks: u8,
+        commitment: String,
+        client_seeds: Vec<String>,
+    },
+    /// Sent once a shoe is finished so the shuffle can be checked against its commitment.
+    ShoeRevealed {
+        shoe_id: u32,
+        server_seed: String,
+        client_seeds: Vec<String>,
+        /// The number of cards that were dealt from the shoe.
+        dealt: usize,
+    },
+    BetPlaced {
+        user_name: String,
+        amount: u32,
+    },
+    ChatMessage {
+        user_name: String,
+        message: String,
+    },
+    /// A message from the people running the server.
+    Announcement {
+        message: String,
+    },
+    /// An operator has changed a player's chips between rounds.
+    ChipsAdjusted {
+        user_name: String,
+        /// The player's chips now.
+        chips: u32,
+        change: i64,
+        reason: String,
+    },
+    /// The server is stopping. With `finish_round` the round being played is finished first,
+    /// otherwise it's called off and every bet returned. Seats and chips are saved for players
+    /// who register again under the same name once the server is back.
+    ServerShuttingDown {
+        finish_round: bool,
+    },
+    /// How a player's connection is doing. Sent when the first ping comes back and whenever the
+    /// quality changes, with the time the last ping took.
+    ConnectionChanged {
+        user_name: String,
+        quality: ConnectionQuality,
+        latency_ms: Option<u64>,
+    },
+    /// Sent in place of the messages a player's connection fell too far behind to be sent, so
+    /// they can catch up with the table. Other players' bets are left out while they're secret.
+    TableSnapshot {
+        /// The round being played, if there is one.
+        round: Option<u64>,
+        dealer_card: Option<Card>,
+        active_player: Option<String>,
+        /// Every seat, with the cards and bet of the round being played.
+        players: Vec<Player>,
+    },
     // TODO: Add the dealer into the results for the clients.
     RoundFinished(Vec<TurnResult>),
+    /// Sent after each tournament hand, once the round has been settled.
+    TournamentStandings {
+        hand: u32,
+        hands: u32,
+        standings: Vec<Standing>,
+        /// The players knocked out by this hand.
+        eliminated: Vec<String>,
+    },
+    /// Sent after the final tournament hand, just before the game finishes.
+    TournamentFinished {
+        winner: String,
+        standings: Vec<Standing>,
+    },
     GameFinished,
 }
 
@@ -35,7 +128,33 @@ pub enum RequestCommand {
     Bet(u32),
     DrawCards(u16),
     Hit,
+    /// Doubles the opening bet on the first two cards. The server deals one more card and ends
+    /// the turn.
+    Double,
     EndTurn(Player),
+    Chat(String),
+    /// A seed to mix into the next shuffle.
+    ClientSeed(String),
+    /// Asks the server to seat a bot playing the named strategy. Only the host can add bots, and
+    /// only before the first round is dealt.
+    AddBot(String),
+}
+
+impl RequestCommand {
+    /// A short name for the command, for logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RequestCommand::Start => "start",
+            RequestCommand::Bet(_) => "bet",
+            RequestCommand::DrawCards(_) => "draw_cards",
+            RequestCommand::Hit => "hit",
+            RequestCommand::Double => "double",
+            RequestCommand::EndTurn(_) => "end_turn",
+            RequestCommand::Chat(_) => "chat",
+            RequestCommand::ClientSeed(_) => "client_seed",
+            RequestCommand::AddBot(_) => "add_bot",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +167,19 @@ pub struct PublishRequest {
     pub trigger: PublishTrigger,
 }
 
+/// How well a player's connection is keeping up with the server's pings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionQuality {
+    Good,
+    /// Pings are slow to come back.
+    Slow,
+    /// Nothing has been heard for a couple of pings.
+    Unresponsive,
+    /// The connection has closed or gone quiet for too long, and the seat has been given up.
+    Disconnected,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct TurnResult {
     pub player: Player,
@@ -61,3 +193,15 @@ pub enum EndState {
     Blackjack,
     Push,
 }
+
+impl EndState {
+    /// The chips won or lost on the bet. Blackjack pays three times the bet.
+    pub fn net_chips(&self, bet: u32) -> i64 {
+        match self {
+            EndState::Win => bet as i64,
+            EndState::Loss => -(bet as i64),
+            EndState::Blackjack => bet as i64 * 3,
+            EndState::Push => 0,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+mod simulation;
+
+use std::{fs, path::PathBuf, thread, time::Instant};
+
+use blackjack_shared::{counting::BetSpread, rules::TableRules, strategy::strategy_from_name};
+use clap::Parser;
+use color_eyre::eyre::{eyre, Result};
+
+use crate::simulation::{run_worker, Config, Stats};
+
+#[derive(Debug, Parser)]
+#[command(about = "Plays blackjack hands headless to measure a strategy over the long run")]
+struct Args {
+    /// A JSON file holding the table rules. The server's rules are used when this is left out.
+    #[arg(long, value_name = "FILE")]
+    rules: Option<PathBuf>,
+
+    /// How the player plays each hand.
+    #[arg(long, default_value = "basic", value_parser = ["basic", "mimic-dealer", "never-bust", "always-stand"])]
+    strategy: String,
+
+    /// Units to bet at each Hi-Lo true count, starting from a true count of one or less.
+    #[arg(long, default_value = "1")]
+    bet_spread: BetSpread,
+
+    /// The chip value of one betting unit.
+    #[arg(long, default_value_t = 10)]
+    unit: u32,
+
+    /// The total number of hands to play.
+    #[arg(long, default_value_t = 1_000_000)]
+    hands: u64,
+
+    /// The number of worker threads. Defaults to one per core.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Shuffle from this seed so a run can be repeated. Each thread gets its own seed counting
+    /// up from this one, so use the same number of threads to get the same results.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The chips each session starts with.
+    #[arg(long, default_value_t = 500)]
+    bankroll: u32,
+
+    /// The most hands in one session. Risk of ruin is the chance of losing the whole bankroll
+    /// within this many hands.
+    #[arg(long, default_value_t = 1_000)]
+    session_hands: u64,
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let args = Args::parse();
+
+    let mut rules = match &args.rules {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => TableRules::default(),
+    };
+    if rules.splitting || rules.surrender {
+        println!("Splitting and surrender aren't supported by the server yet, turning them off.");
+        rules.splitting = false;
+        rules.surrender = false;
+    }
+    if rules.decks == 0 {
+        return Err(eyre!("The rules must use at least one deck."));
+    }
+    if args.unit == 0 || args.session_hands == 0 {
+        return Err(eyre!("The unit and session length must be above zero."));
+    }
+
+    let strategy = strategy_from_name(&args.strategy, &rules)
+        .ok_or_else(|| eyre!("Unknown strategy: {}", args.strategy))?;
+    let threads = args
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1);
+
+    let config = Config {
+        rules: &rules,
+        strategy: strategy.as_ref(),
+        bet_spread: &args.bet_spread,
+        unit: args.unit,
+        bankroll: args.bankroll,
+        session_hands: args.session_hands,
+    };
+
+    println!(
+        "Playing {} hands with the {} strategy on {} thread(s)...",
+        args.hands,
+        strategy.name(),
+        threads
+    );
+    let started = Instant::now();
+
+    let mut stats = Stats::default();
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads as u64)
+            .map(|i| {
+                // Spread the remainder over the first few workers.
+                let hands =
+                    args.hands / threads as u64 + u64::from(i < args.hands % threads as u64);
+                let config = &config;
+                let seed = args.seed.map(|s| s.wrapping_add(i));
+                scope.spawn(move || run_worker(config, hands, seed))
+            })
+            .collect();
+
+        for worker in workers {
+            stats.merge(worker.join().expect("Simulation worker panicked"));
+        }
+    });
+
+    println!("Finished in {:.1?}.\n", started.elapsed());
+    print_report(&rules, &args, &mut stats);
+
+    Ok(())
+}
+
+fn print_report(rules: &TableRules, args: &Args, stats: &mut Stats) {
+    if stats.hands == 0 {
+        println!("No hands were played.");
+        return;
+    }
+
+    let hands = stats.hands as f64;
+    // Results are measured in betting units per hand.
+    let mean = stats.net as f64 / args.unit as f64 / hands;
+    let variance = stats.sum_of_squares / hands - mean * mean;
+    let std_dev = variance.sqrt();
+    let std_error = std_dev / hands.sqrt();
+    let average_bet = stats.wagered as f64 / args.unit as f64 / hands;
+    let house_edge = -mean / average_bet;
+
+    println!("Rules: {}", serde_json::to_string(rules).unwrap());
+    println!("Bet spread: {} x {} chips", args.bet_spread, args.unit);
+    println!();
+    println!("Hands played:      {}", stats.hands);
+    println!(
+        "House edge:        {:.3}% (±{:.3}%)",
+        house_edge * 100.0,
+        std_error / average_bet * 100.0
+    );
+    println!("Average bet:       {:.3} units", average_bet);
+    println!("Result per hand:   {:.4} units", mean);
+    println!("Variance per hand: {:.4} units²", variance);
+    println!("Std dev per hand:  {:.4} units", std_dev);
+    println!("Standard error:    {:.5} units", std_error);
+    println!();
+    println!(
+        "Wins {:.2}%, losses {:.2}%, pushes {:.2}%, blackjacks {:.2}%",
+        stats.wins as f64 / hands * 100.0,
+        stats.losses as f64 / hands * 100.0,
+        stats.pushes as f64 / hands * 100.0,
+        stats.blackjacks as f64 / hands * 100.0
+    );
+    println!();
+    println!(
+        "Sessions of up to {} hands starting with {} chips: {}",
+        args.session_hands, args.bankroll, stats.sessions
+    );
+    println!(
+        "Risk of ruin:      {:.2}%",
+        stats.ruined as f64 / stats.sessions as f64 * 100.0
+    );
+
+    stats.final_bankrolls.sort_unstable();
+    let percentile = |p: usize| {
+        let index = (stats.final_bankrolls.len() - 1) * p / 100;
+        stats.final_bankrolls[index]
+    };
+    println!("Final bankroll:");
+    for p in [5, 25, 50, 75, 95] {
+        println!("  {:>2}th percentile: {}", p, percentile(p));
+    }
+}
@@ -0,0 +1,191 @@
+use blackjack_server::{
+    card::{draw_cards, Shoe},
+    game::{calculate_end_state, handle_end_state, play_dealers_hand},
+};
+use blackjack_shared::{
+    card::Card,
+    counting::{BetSpread, Counter, HiLo},
+    player::{get_hand_value, Player, PlayerAction, PlayerType},
+    rules::TableRules,
+    strategy::PlayerStrategy,
+    web_socket::EndState,
+};
+
+/// Everything a worker needs to play its share of the hands.
+pub struct Config<'a> {
+    pub rules: &'a TableRules,
+    pub strategy: &'a (dyn PlayerStrategy + Sync),
+    pub bet_spread: &'a BetSpread,
+    /// The chip value of one betting unit.
+    pub unit: u32,
+    /// The chips each session starts with.
+    pub bankroll: u32,
+    /// The most hands played in one session before starting again with a fresh bankroll.
+    pub session_hands: u64,
+}
+
+/// The combined results of the hands played.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub hands: u64,
+    /// The total of the bets placed before any doubling.
+    pub wagered: u64,
+    pub net: i64,
+    /// The sum of the squared result of each hand, measured in betting units.
+    pub sum_of_squares: f64,
+    pub wins: u64,
+    pub losses: u64,
+    pub pushes: u64,
+    pub blackjacks: u64,
+    pub sessions: u64,
+    /// Sessions that ran out of chips.
+    pub ruined: u64,
+    /// The chips left at the end of each session.
+    pub final_bankrolls: Vec<u32>,
+}
+
+impl Stats {
+    pub fn merge(&mut self, other: Stats) {
+        self.hands += other.hands;
+        self.wagered += other.wagered;
+        self.net += other.net;
+        self.sum_of_squares += other.sum_of_squares;
+        self.wins += other.wins;
+        self.losses += other.losses;
+        self.pushes += other.pushes;
+        self.blackjacks += other.blackjacks;
+        self.sessions += other.sessions;
+        self.ruined += other.ruined;
+        self.final_bankrolls.extend(other.final_bankrolls);
+    }
+}
+
+/// Plays the given number of hands from a shoe of its own, split into sessions. The shoe is
+/// shuffled from the seed when one is given.
+pub fn run_worker(config: &Config, hands: u64, seed: Option<u64>) -> Stats {
+    let mut stats = Stats::default();
+    let mut shoe = match seed {
+        Some(seed) => Shoe::seeded(config.rules.decks, seed),
+        None => Shoe::new(config.rules.decks),
+    };
+    let mut counter = Counter::new(Box::new(HiLo), config.rules.decks);
+
+    while stats.hands < hands {
+        let session_hands = config.session_hands.min(hands - stats.hands);
+        play_session(config, &mut shoe, &mut counter, &mut stats, session_hands);
+    }
+
+    stats
+}
+
+fn play_session(
+    config: &Config,
+    shoe: &mut Shoe,
+    counter: &mut Counter,
+    stats: &mut Stats,
+    hands: u64,
+) {
+    let mut player = new_player("Player", PlayerType::Human, config.bankroll);
+    let mut dealer = new_player("Dealer", PlayerType::Dealer, 0);
+
+    for _ in 0..hands {
+        play_round(config, shoe, counter, stats, &mut player, &mut dealer);
+        if player.chips == 0 {
+            stats.ruined += 1;
+            break;
+        }
+    }
+
+    stats.sessions += 1;
+    stats.final_bankrolls.push(player.chips);
+}
+
+/// Plays one round in the same order as the server: the shoe is checked for the cut card, the
+/// dealer is dealt, the player takes their turn and then the dealer plays.
+fn play_round(
+    config: &Config,
+    shoe: &mut Shoe,
+    counter: &mut Counter,
+    stats: &mut Stats,
+    player: &mut Player,
+    dealer: &mut Player,
+) {
+    if shoe.needs_shuffle() {
+        shoe.shuffle();
+    }
+    if shoe.take_shuffled() {
+        counter.reset();
+    }
+
+    let units = config
+        .bet_spread
+        .units(counter.true_count().unwrap_or_default());
+    let bet = (units * config.unit).min(player.chips);
+    player.current_bet = bet;
+
+    dealer.hand = draw_cards(shoe, 2);
+    dealer.hand_value = get_hand_value(dealer.hand.clone());
+    player.hand = draw_cards(shoe, 2);
+    player.hand_value = get_hand_value(player.hand.clone());
+
+    play_players_hand(config.strategy, shoe, player, &dealer.hand[0]);
+    play_dealers_hand(dealer, shoe, config.rules);
+
+    counter.observe(&player.hand);
+    counter.observe(&dealer.hand);
+
+    let chips_before = player.chips as i64;
+    let end_state = calculate_end_state(player, dealer);
+    handle_end_state(player, end_state.clone());
+    let net = player.chips as i64 - chips_before;
+
+    match end_state {
+        EndState::Win => stats.wins += 1,
+        EndState::Loss => stats.losses += 1,
+        EndState::Push => stats.pushes += 1,
+        EndState::Blackjack => stats.blackjacks += 1,
+    }
+
+    let result = net as f64 / config.unit as f64;
+    stats.hands += 1;
+    stats.wagered += bet as u64;
+    stats.net += net;
+    stats.sum_of_squares += result * result;
+}
+
+/// Plays the hand the way the client does. Doubling draws one card and ends the turn, and is only
+/// allowed when the player can cover the doubled bet, otherwise the hand is hit instead.
+fn play_players_hand(
+    strategy: &(dyn PlayerStrategy + Sync),
+    shoe: &mut Shoe,
+    player: &mut Player,
+    dealer_card: &Card,
+) {
+    while player.hand_value <= 21 {
+        match strategy.action(&player.hand, dealer_card) {
+            PlayerAction::Stand => break,
+            PlayerAction::Double if player.current_bet * 2 <= player.chips => {
+                hit(shoe, player);
+                player.current_bet *= 2;
+                break;
+            }
+            PlayerAction::Hit | PlayerAction::Double => hit(shoe, player),
+        }
+    }
+}
+
+fn hit(shoe: &mut Shoe, player: &mut Player) {
+    player.hand.extend(draw_cards(shoe, 1));
+    player.hand_value = get_hand_value(player.hand.clone());
+}
+
+fn new_player(user_name: &str, player_type: PlayerType, chips: u32) -> Player {
+    Player {
+        user_name: user_name.to_string(),
+        player_type,
+        hand: vec![],
+        hand_value: 0,
+        chips,
+        current_bet: 0,
+    }
+}